@@ -0,0 +1,303 @@
+//! Run `cargo bench --bench lsf_reader -- --save-baseline before` on one commit, then
+//! `cargo bench --bench lsf_reader -- --baseline before` on another to compare the parse time
+//! (`read_bytes`) and retained memory (`read_bytes_memory`) of the reader between them.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::io::{BufReader, Cursor};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bg3_lib::lsf_reader::{LSFNodeEntryV3, LSFReader, NodeAttribute};
+use bg3_lib::lsf_visitor::LSFVisitor;
+use bg3_lib::record;
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use serde::Deserialize;
+
+/// System allocator keeping track of the bytes currently allocated.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Bytes still allocated when a measurement ends, i.e. held by what the routine returned.
+/// Only meaningful with `iter_with_large_drop`, which keeps the results alive until then.
+struct RetainedBytes;
+
+impl Measurement for RetainedBytes {
+    type Intermediate = usize;
+    type Value = usize;
+
+    fn start(&self) -> Self::Intermediate {
+        ALLOCATED.load(Ordering::Relaxed)
+    }
+
+    fn end(&self, start: Self::Intermediate) -> Self::Value {
+        ALLOCATED.load(Ordering::Relaxed).saturating_sub(start)
+    }
+
+    fn add(&self, v1: &Self::Value, v2: &Self::Value) -> Self::Value {
+        v1 + v2
+    }
+
+    fn zero(&self) -> Self::Value {
+        0
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &BytesFormatter
+    }
+}
+
+struct BytesFormatter;
+
+impl ValueFormatter for BytesFormatter {
+    fn scale_values(&self, typical_value: f64, values: &mut [f64]) -> &'static str {
+        let (factor, unit) = if typical_value >= 1024.0 * 1024.0 {
+            (1024.0 * 1024.0, "MiB")
+        } else if typical_value >= 1024.0 {
+            (1024.0, "KiB")
+        } else {
+            (1.0, "B")
+        };
+        values.iter_mut().for_each(|v| *v /= factor);
+        unit
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        _throughput: &Throughput,
+        _values: &mut [f64],
+    ) -> &'static str {
+        "B"
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "B"
+    }
+}
+
+const NODE_COUNTS: [usize; 2] = [10_000, 100_000];
+const ATTRIBUTE_NAMES: [&str; 5] = ["MapKey", "Template", "Level", "UUID", "Scale"];
+/// Distinct values of the `Template` attribute, which like many string values in real
+/// files repeats across nodes.
+const TEMPLATE_COUNT: usize = 300;
+
+/// Uncompressed LSF file (version 7, long node and attribute entries) with one region
+/// holding `node_count - 1` nodes of five attributes each.
+fn synthetic_lsf(node_count: usize) -> Vec<u8> {
+    let mut names = vec![];
+    // One bucket for node names, one for attribute names
+    names.extend(2u32.to_le_bytes());
+    for bucket in [&["Templates", "GameObject"][..], &ATTRIBUTE_NAMES[..]] {
+        names.extend((bucket.len() as u16).to_le_bytes());
+        for name in bucket {
+            names.extend((name.len() as u16).to_le_bytes());
+            names.extend(name.as_bytes());
+        }
+    }
+
+    let mut nodes = vec![];
+    let mut attributes = vec![];
+    let mut values = vec![];
+    for node_idx in 0..node_count {
+        let (name_offset, parent_index) = if node_idx == 0 { (0u32, -1i32) } else { (1, 0) };
+        let first_attribute = if node_idx == 0 {
+            -1
+        } else {
+            ((node_idx - 1) * ATTRIBUTE_NAMES.len()) as i32
+        };
+        nodes.extend(name_offset.to_le_bytes());
+        nodes.extend(parent_index.to_le_bytes());
+        nodes.extend((-1i32).to_le_bytes());
+        nodes.extend(first_attribute.to_le_bytes());
+
+        if node_idx == 0 {
+            continue;
+        }
+
+        let map_key = format!("Object_{node_idx:08}");
+        let template = format!("Template_{:04}", node_idx % TEMPLATE_COUNT);
+        let attribute_values: [(u32, Vec<u8>); 5] = [
+            (22, map_key.into_bytes()),
+            (22, template.into_bytes()),
+            (4, (node_idx as i32).to_le_bytes().to_vec()),
+            (31, [node_idx as u8; 16].to_vec()),
+            (6, 1.5f32.to_le_bytes().to_vec()),
+        ];
+        for (attr_idx, (type_id, value)) in attribute_values.into_iter().enumerate() {
+            let index = first_attribute + attr_idx as i32;
+            let next = if attr_idx + 1 < ATTRIBUTE_NAMES.len() {
+                index + 1
+            } else {
+                -1
+            };
+            attributes.extend(((1u32 << 16) | attr_idx as u32).to_le_bytes());
+            attributes.extend((type_id | ((value.len() as u32) << 6)).to_le_bytes());
+            attributes.extend(next.to_le_bytes());
+            attributes.extend((values.len() as u32).to_le_bytes());
+            values.extend(value);
+        }
+    }
+
+    let mut file = b"LSOF".to_vec();
+    file.extend(7u32.to_le_bytes());
+    file.extend((4i64 << 55).to_le_bytes());
+    // Sections are stored uncompressed: uncompressed size, then 0 as size on disk
+    file.extend((names.len() as u32).to_le_bytes());
+    file.extend(0u32.to_le_bytes());
+    file.extend(0u64.to_le_bytes());
+    for section in [&nodes, &attributes, &values] {
+        file.extend((section.len() as u32).to_le_bytes());
+        file.extend(0u32.to_le_bytes());
+    }
+    // compression flags, two unknown fields, has_sibling_data
+    file.extend([0, 0, 0, 0]);
+    file.extend(1u32.to_le_bytes());
+
+    for section in [names, nodes, attributes, values] {
+        file.extend(section);
+    }
+    file
+}
+
+/// Same layout as `LSFNodeEntryV3`, decoded the way the reader used to: one bincode
+/// call per entry, through a fresh `BufReader` over the table cursor.
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct BincodeNodeEntry {
+    name_hash_table_index: u32,
+    parent_index: i32,
+    next_sibling_index: i32,
+    first_attribute_index: i32,
+}
+
+fn decode_nodes_per_entry(table: &[u8]) -> Vec<BincodeNodeEntry> {
+    let mut stream = Cursor::new(table);
+    let mut entries = Vec::with_capacity(table.len() / size_of::<BincodeNodeEntry>());
+    while (stream.position() as usize) < table.len() {
+        // BufReader over-reads, so the cursor has to be moved back to the end of the entry
+        let start = stream.position();
+        let reader = BufReader::new(&mut stream);
+        match bincode::serde::decode_from_reader(reader, bincode::config::legacy()) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => break,
+        }
+        stream.set_position(start + size_of::<BincodeNodeEntry>() as u64);
+    }
+    entries
+}
+
+fn node_table(c: &mut Criterion) {
+    let mut group = c.benchmark_group("node_table");
+    for node_count in NODE_COUNTS {
+        let table: Vec<u8> = (0..node_count as u32)
+            .flat_map(|i| [i, i.wrapping_sub(1), u32::MAX, i * 4])
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        group.throughput(Throughput::Bytes(table.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("bincode_per_entry", node_count),
+            &table,
+            |b, table| b.iter(|| decode_nodes_per_entry(black_box(table))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("fixed_size_records", node_count),
+            &table,
+            |b, table| b.iter(|| record::decode_records::<LSFNodeEntryV3>(black_box(table)).0),
+        );
+    }
+    group.finish();
+}
+
+/// Counts nodes and attributes, the kind of statistics a visitor can gather without a tree.
+#[derive(Default)]
+struct CountingVisitor {
+    nodes: usize,
+    attributes: usize,
+}
+
+impl LSFVisitor for CountingVisitor {
+    fn enter_region(&mut self, _name: &str) {
+        self.nodes += 1;
+    }
+
+    fn enter_node(&mut self, _name: &str) {
+        self.nodes += 1;
+    }
+
+    fn attribute(&mut self, _name: &str, _value: &NodeAttribute) {
+        self.attributes += 1;
+    }
+}
+
+fn read_bytes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_bytes");
+    group.sample_size(20);
+    for node_count in NODE_COUNTS {
+        let file = synthetic_lsf(node_count);
+        group.throughput(Throughput::Bytes(file.len() as u64));
+        group.bench_with_input(BenchmarkId::new("tree", node_count), &file, |b, file| {
+            b.iter(|| {
+                LSFReader::new()
+                    .read_bytes(black_box(file))
+                    .map(|resource| resource.regions.node_instances.len())
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("visit", node_count), &file, |b, file| {
+            b.iter(|| {
+                let mut visitor = CountingVisitor::default();
+                LSFReader::new()
+                    .visit_bytes(black_box(file), &mut visitor)
+                    .map(|_| visitor.nodes + visitor.attributes)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn read_bytes_memory(c: &mut Criterion<RetainedBytes>) {
+    let mut group = c.benchmark_group("read_bytes_memory");
+    group.sample_size(10);
+    for node_count in NODE_COUNTS {
+        let file = synthetic_lsf(node_count);
+        group.bench_with_input(BenchmarkId::new("tree", node_count), &file, |b, file| {
+            b.iter_with_large_drop(|| LSFReader::new().read_bytes(black_box(file)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, node_table, read_bytes);
+criterion_group! {
+    name = memory;
+    config = Criterion::default().with_measurement(RetainedBytes).without_plots();
+    targets = read_bytes_memory
+}
+criterion_main!(benches, memory);
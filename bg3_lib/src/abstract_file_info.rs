@@ -91,7 +91,7 @@ fn formatted_size(s: usize) -> String {
     format!("{val:.2} {unit} ({s} Bytes)")
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CompressionMethod {
     None = 0,
     Zlib = 1,
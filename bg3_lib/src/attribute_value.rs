@@ -0,0 +1,746 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use uuid::Uuid;
+
+use crate::lsf_reader::{
+    DataType, NodeAttribute, NodeAttributeValue, TranslatedFSString, TranslatedFSStringArgument,
+    TranslatedString,
+};
+
+impl NodeAttributeValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Integer value, widened to `i64`. `UInt64` values only convert if they fit.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Byte(v) => Some(*v as i64),
+            Self::I8(v) => Some(*v as i64),
+            Self::Short(v) => Some(*v as i64),
+            Self::UShort(v) => Some(*v as i64),
+            Self::Int(v) => Some(*v as i64),
+            Self::UInt(v) => Some(*v as i64),
+            Self::Int64(v) => Some(*v),
+            Self::UInt64(v) => i64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Integer value, widened to `u64`. Negative values do not convert.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Byte(v) => Some(*v as u64),
+            Self::UShort(v) => Some(*v as u64),
+            Self::UInt(v) => Some(*v as u64),
+            Self::UInt64(v) => Some(*v),
+            Self::I8(v) => u64::try_from(*v).ok(),
+            Self::Short(v) => u64::try_from(*v).ok(),
+            Self::Int(v) => u64::try_from(*v).ok(),
+            Self::Int64(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Integer value, if it fits in an `i32` without loss.
+    pub fn as_i32(&self) -> Option<i32> {
+        self.as_i64().and_then(|v| i32::try_from(v).ok())
+    }
+
+    /// Integer value, if it fits in a `u32` without loss.
+    pub fn as_u32(&self) -> Option<u32> {
+        self.as_u64().and_then(|v| u32::try_from(v).ok())
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Self::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Floating point value, widened to `f64`.
+    /// Integers up to 32 bits are exactly representable and convert as well.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(v) => Some(*v as f64),
+            Self::Double(v) => Some(*v),
+            Self::Byte(v) => Some(*v as f64),
+            Self::I8(v) => Some(*v as f64),
+            Self::Short(v) => Some(*v as f64),
+            Self::UShort(v) => Some(*v as f64),
+            Self::Int(v) => Some(*v as f64),
+            Self::UInt(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_uuid(&self) -> Option<Uuid> {
+        match self {
+            Self::Uuid(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Bytes of a `ScratchBuffer`, or of a value of unknown type.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes(v) | Self::Raw { bytes: v, .. } => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_translated_string(&self) -> Option<&TranslatedString> {
+        match self {
+            Self::TranslatedString(v) => Some(v),
+            Self::TranslatedFSString(v) => Some(v.base()),
+            _ => None,
+        }
+    }
+
+    pub fn as_translated_fs_string(&self) -> Option<&TranslatedFSString> {
+        match self {
+            Self::TranslatedFSString(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_ivec2(&self) -> Option<[i32; 2]> {
+        match self {
+            Self::IVec2(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_ivec3(&self) -> Option<[i32; 3]> {
+        match self {
+            Self::IVec3(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_ivec4(&self) -> Option<[i32; 4]> {
+        match self {
+            Self::IVec4(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_vec2(&self) -> Option<[f32; 2]> {
+        match self {
+            Self::Vec2(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_vec3(&self) -> Option<[f32; 3]> {
+        match self {
+            Self::Vec3(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_vec4(&self) -> Option<[f32; 4]> {
+        match self {
+            Self::Vec4(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_mat2(&self) -> Option<[[f32; 2]; 2]> {
+        match self {
+            Self::Mat2(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_mat3(&self) -> Option<[[f32; 3]; 3]> {
+        match self {
+            Self::Mat3(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_mat3x4(&self) -> Option<[[f32; 4]; 3]> {
+        match self {
+            Self::Mat3x4(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_mat4x3(&self) -> Option<[[f32; 3]; 4]> {
+        match self {
+            Self::Mat4x3(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_mat4(&self) -> Option<[[f32; 4]; 4]> {
+        match self {
+            Self::Mat4(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Parses a value of the given type from its LSX string representation,
+    /// i.e. the format produced by `Display`.
+    ///
+    /// Vectors and matrices are whitespace separated (matrices in row order),
+    /// `ScratchBuffer` is base64, booleans are `True`/`False` and translated strings are
+    /// `handle;version`. `TranslatedFSString` arguments follow as `[key|string|value]`, with
+    /// `\`, `[`, `]` and `|` in them escaped with a backslash. Values of unknown types are
+    /// `type_id:hex bytes`.
+    pub fn parse(ty: DataType, s: &str) -> Result<Self, String> {
+        let value = match ty {
+            DataType::None => Self::None,
+            DataType::Byte => Self::Byte(parse_number(ty, s)?),
+            DataType::Short => Self::Short(parse_number(ty, s)?),
+            DataType::UShort => Self::UShort(parse_number(ty, s)?),
+            DataType::Int => Self::Int(parse_number(ty, s)?),
+            DataType::UInt => Self::UInt(parse_number(ty, s)?),
+            DataType::Float => Self::Float(parse_number(ty, s)?),
+            DataType::Double => Self::Double(parse_number(ty, s)?),
+            DataType::IVec2 => Self::IVec2(parse_array(ty, s)?),
+            DataType::IVec3 => Self::IVec3(parse_array(ty, s)?),
+            DataType::IVec4 => Self::IVec4(parse_array(ty, s)?),
+            DataType::Vec2 => Self::Vec2(parse_array(ty, s)?),
+            DataType::Vec3 => Self::Vec3(parse_array(ty, s)?),
+            DataType::Vec4 => Self::Vec4(parse_array(ty, s)?),
+            DataType::Mat2 => Self::Mat2(parse_matrix(ty, s)?),
+            DataType::Mat3 => Self::Mat3(parse_matrix(ty, s)?),
+            DataType::Mat3x4 => Self::Mat3x4(parse_matrix(ty, s)?),
+            DataType::Mat4x3 => Self::Mat4x3(parse_matrix(ty, s)?),
+            DataType::Mat4 => Self::Mat4(parse_matrix(ty, s)?),
+            DataType::Bool => {
+                let value = match s.trim() {
+                    b if b.eq_ignore_ascii_case("true") || b == "1" => true,
+                    b if b.eq_ignore_ascii_case("false") || b == "0" => false,
+                    b => return Err(format!("'{b}' is not a valid {ty} value")),
+                };
+                Self::Bool(value)
+            }
+            DataType::String
+            | DataType::Path
+            | DataType::FixedString
+            | DataType::LSString
+            | DataType::WString
+            | DataType::LSWString => Self::String(s.into()),
+            DataType::ULongLong => Self::UInt64(parse_number(ty, s)?),
+            DataType::ScratchBuffer => {
+                let bytes = BASE64
+                    .decode(s.trim())
+                    .map_err(|e| format!("'{s}' is not a valid base64 {ty} value: {e}"))?;
+                Self::Bytes(bytes)
+            }
+            DataType::Long | DataType::Int64 => Self::Int64(parse_number(ty, s)?),
+            DataType::Int8 => Self::I8(parse_number(ty, s)?),
+            DataType::TranslatedString => Self::TranslatedString(parse_translated_string(s)?),
+            DataType::TranslatedFSString => {
+                let (value, rest) = parse_translated_fs_string(s)?;
+                if !rest.is_empty() {
+                    return Err(format!("unexpected '{rest}' after {ty} value '{s}'"));
+                }
+                Self::TranslatedFSString(value)
+            }
+            DataType::Uuid => {
+                let value = Uuid::parse_str(s.trim())
+                    .map_err(|e| format!("'{s}' is not a valid {ty} value: {e}"))?;
+                Self::Uuid(value)
+            }
+            DataType::Unknown => parse_raw(s)?,
+        };
+
+        Ok(value)
+    }
+}
+
+impl Display for NodeAttributeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => Ok(()),
+            Self::String(v) => f.write_str(v),
+            Self::TranslatedString(v) => write!(f, "{};{}", v.handle(), v.version()),
+            Self::TranslatedFSString(v) => write_translated_fs_string(f, v),
+            Self::Bytes(v) => f.write_str(&BASE64.encode(v)),
+            Self::Raw { type_id, bytes } => {
+                write!(f, "{type_id}:")?;
+                bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+            }
+            Self::Byte(v) => write!(f, "{v}"),
+            Self::Short(v) => write!(f, "{v}"),
+            Self::UShort(v) => write!(f, "{v}"),
+            Self::Int(v) => write!(f, "{v}"),
+            Self::UInt(v) => write!(f, "{v}"),
+            Self::Float(v) => write!(f, "{v}"),
+            Self::Double(v) => write!(f, "{v}"),
+            Self::IVec2(v) => write_joined(f, v),
+            Self::IVec3(v) => write_joined(f, v),
+            Self::IVec4(v) => write_joined(f, v),
+            Self::Vec2(v) => write_joined(f, v),
+            Self::Vec3(v) => write_joined(f, v),
+            Self::Vec4(v) => write_joined(f, v),
+            Self::Mat2(v) => write_joined(f, v.as_flattened()),
+            Self::Mat3(v) => write_joined(f, v.as_flattened()),
+            Self::Mat3x4(v) => write_joined(f, v.as_flattened()),
+            Self::Mat4x3(v) => write_joined(f, v.as_flattened()),
+            Self::Mat4(v) => write_joined(f, v.as_flattened()),
+            Self::Bool(v) => f.write_str(if *v { "True" } else { "False" }),
+            Self::UInt64(v) => write!(f, "{v}"),
+            Self::Int64(v) => write!(f, "{v}"),
+            Self::I8(v) => write!(f, "{v}"),
+            Self::Uuid(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+impl NodeAttribute {
+    pub fn parse(ty: DataType, s: &str) -> Result<Self, String> {
+        let value = NodeAttributeValue::parse(ty, s)?;
+        Ok(Self { ty, value })
+    }
+
+    /// Replaces the type and value of this attribute with `s` parsed as `ty`.
+    /// The attribute is left untouched if `s` is not a valid value for `ty`.
+    pub fn set_from_str(&mut self, ty: DataType, s: &str) -> Result<(), String> {
+        self.value = NodeAttributeValue::parse(ty, s)?;
+        self.ty = ty;
+        Ok(())
+    }
+}
+
+impl Display for NodeAttribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl DataType {
+    /// Type name used by the LSX format.
+    pub fn lsx_name(&self) -> &'static str {
+        match self {
+            DataType::None => "None",
+            DataType::Byte => "uint8",
+            DataType::Short => "int16",
+            DataType::UShort => "uint16",
+            DataType::Int => "int32",
+            DataType::UInt => "uint32",
+            DataType::Float => "float",
+            DataType::Double => "double",
+            DataType::IVec2 => "ivec2",
+            DataType::IVec3 => "ivec3",
+            DataType::IVec4 => "ivec4",
+            DataType::Vec2 => "fvec2",
+            DataType::Vec3 => "fvec3",
+            DataType::Vec4 => "fvec4",
+            DataType::Mat2 => "mat2x2",
+            DataType::Mat3 => "mat3x3",
+            DataType::Mat3x4 => "mat3x4",
+            DataType::Mat4x3 => "mat4x3",
+            DataType::Mat4 => "mat4x4",
+            DataType::Bool => "bool",
+            DataType::String => "string",
+            DataType::Path => "path",
+            DataType::FixedString => "FixedString",
+            DataType::LSString => "LSString",
+            DataType::ULongLong => "uint64",
+            DataType::ScratchBuffer => "ScratchBuffer",
+            DataType::Long => "old_int64",
+            DataType::Int8 => "int8",
+            DataType::TranslatedString => "TranslatedString",
+            DataType::WString => "WString",
+            DataType::LSWString => "LSWString",
+            DataType::Uuid => "guid",
+            DataType::Int64 => "int64",
+            DataType::TranslatedFSString => "TranslatedFSString",
+            DataType::Unknown => "Unknown",
+        }
+    }
+}
+
+impl Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.lsx_name())
+    }
+}
+
+impl FromStr for DataType {
+    type Err = String;
+
+    /// Accepts LSX type names as well as numeric type ids.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = s.parse::<u32>() {
+            return match DataType::from(id) {
+                DataType::Unknown => Err(format!("unknown data type id {id}")),
+                ty => Ok(ty),
+            };
+        }
+
+        (0..=DataType::max() as u32)
+            .map(DataType::from)
+            .find(|ty| ty.lsx_name() == s)
+            .ok_or_else(|| format!("unknown data type name '{s}'"))
+    }
+}
+
+fn parse_number<T>(ty: DataType, s: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    s.trim()
+        .parse()
+        .map_err(|e| format!("'{s}' is not a valid {ty} value: {e}"))
+}
+
+fn parse_array<T, const N: usize>(ty: DataType, s: &str) -> Result<[T; N], String>
+where
+    T: FromStr + Default + Copy,
+    T::Err: Display,
+{
+    let mut value = [T::default(); N];
+    let mut parts = s.split_whitespace();
+    for v in value.iter_mut() {
+        let part = parts
+            .next()
+            .ok_or_else(|| format!("'{s}' has too few components for {ty}, expected {N}"))?;
+        *v = parse_number(ty, part)?;
+    }
+
+    if parts.next().is_some() {
+        return Err(format!(
+            "'{s}' has too many components for {ty}, expected {N}"
+        ));
+    }
+
+    Ok(value)
+}
+
+fn parse_matrix<const COLS: usize, const ROWS: usize>(
+    ty: DataType,
+    s: &str,
+) -> Result<[[f32; COLS]; ROWS], String> {
+    let mut mat = [[0f32; COLS]; ROWS];
+    let mut parts = s.split_whitespace();
+    for col in mat.as_flattened_mut() {
+        let part = parts.next().ok_or_else(|| {
+            format!(
+                "'{s}' has too few components for {ty}, expected {}",
+                COLS * ROWS
+            )
+        })?;
+        *col = parse_number(ty, part)?;
+    }
+
+    if parts.next().is_some() {
+        return Err(format!(
+            "'{s}' has too many components for {ty}, expected {}",
+            COLS * ROWS
+        ));
+    }
+
+    Ok(mat)
+}
+
+fn parse_raw(s: &str) -> Result<NodeAttributeValue, String> {
+    let (type_id, hex) = s
+        .trim()
+        .split_once(':')
+        .ok_or_else(|| format!("'{s}' is not a raw value, expected 'type_id:hex bytes'"))?;
+    let type_id = type_id
+        .parse()
+        .map_err(|e| format!("'{s}' has an invalid raw type id: {e}"))?;
+    if hex.len() % 2 != 0 {
+        return Err(format!("'{s}' has an odd number of hex digits"));
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| format!("'{s}' has invalid hex bytes"))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(NodeAttributeValue::Raw { type_id, bytes })
+}
+
+fn parse_translated_string(s: &str) -> Result<TranslatedString, String> {
+    let (handle, version) = match s.rsplit_once(';') {
+        Some((handle, version)) => {
+            let version = version
+                .trim()
+                .parse()
+                .map_err(|e| format!("'{s}' has an invalid translated string version: {e}"))?;
+            (handle, version)
+        }
+        None => (s, 0),
+    };
+
+    Ok(TranslatedString::new(handle.to_string(), version))
+}
+
+/// Characters delimiting `TranslatedFSString` arguments.
+const FS_STRING_SPECIAL_CHARS: [char; 4] = ['\\', '[', ']', '|'];
+
+fn escape_fs_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if FS_STRING_SPECIAL_CHARS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Unescaped text up to the first unescaped special character, and the rest of the input
+/// starting at that character.
+fn take_fs_string_part(s: &str) -> Result<(String, &str), String> {
+    let mut text = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            let (_, escaped) = chars
+                .next()
+                .ok_or_else(|| format!("trailing '\\' in '{s}'"))?;
+            text.push(escaped);
+        } else if FS_STRING_SPECIAL_CHARS.contains(&c) {
+            return Ok((text, &s[i..]));
+        } else {
+            text.push(c);
+        }
+    }
+    Ok((text, ""))
+}
+
+fn write_translated_fs_string(
+    f: &mut std::fmt::Formatter<'_>,
+    value: &TranslatedFSString,
+) -> std::fmt::Result {
+    write!(
+        f,
+        "{};{}",
+        escape_fs_string(value.handle()),
+        value.version()
+    )?;
+    for arg in value.arguments() {
+        write!(f, "[{}|", escape_fs_string(arg.key()))?;
+        write_translated_fs_string(f, arg.string())?;
+        write!(f, "|{}]", escape_fs_string(arg.value()))?;
+    }
+    Ok(())
+}
+
+/// Translated string and its arguments, and the rest of the input after them.
+fn parse_translated_fs_string(s: &str) -> Result<(TranslatedFSString, &str), String> {
+    let (base, mut rest) = take_fs_string_part(s)?;
+    let base = parse_translated_string(&base)?;
+
+    let mut arguments = vec![];
+    while let Some(argument) = rest.strip_prefix('[') {
+        let (key, after_key) = take_fs_string_part(argument)?;
+        let string = after_key
+            .strip_prefix('|')
+            .ok_or_else(|| format!("expected '|' after argument key '{key}' in '{s}'"))?;
+        let (string, after_string) = parse_translated_fs_string(string)?;
+        let value = after_string
+            .strip_prefix('|')
+            .ok_or_else(|| format!("expected '|' after argument string of '{key}' in '{s}'"))?;
+        let (value, after_value) = take_fs_string_part(value)?;
+        rest = after_value
+            .strip_prefix(']')
+            .ok_or_else(|| format!("unterminated argument '{key}' in '{s}'"))?;
+        arguments.push(TranslatedFSStringArgument::new(key, string, value));
+    }
+
+    Ok((TranslatedFSString::new(base, arguments), rest))
+}
+
+fn write_joined<T: Display>(f: &mut std::fmt::Formatter<'_>, values: &[T]) -> std::fmt::Result {
+    for (i, v) in values.iter().enumerate() {
+        if i > 0 {
+            f.write_str(" ")?;
+        }
+        write!(f, "{v}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_value(ty: DataType) -> NodeAttributeValue {
+        match ty {
+            DataType::None => NodeAttributeValue::None,
+            DataType::Byte => NodeAttributeValue::Byte(200),
+            DataType::Short => NodeAttributeValue::Short(-1234),
+            DataType::UShort => NodeAttributeValue::UShort(54321),
+            DataType::Int => NodeAttributeValue::Int(-123456),
+            DataType::UInt => NodeAttributeValue::UInt(4000000000),
+            DataType::Float => NodeAttributeValue::Float(1.25),
+            DataType::Double => NodeAttributeValue::Double(-0.1),
+            DataType::IVec2 => NodeAttributeValue::IVec2([1, -2]),
+            DataType::IVec3 => NodeAttributeValue::IVec3([1, -2, 3]),
+            DataType::IVec4 => NodeAttributeValue::IVec4([1, -2, 3, -4]),
+            DataType::Vec2 => NodeAttributeValue::Vec2([0.5, -1.5]),
+            DataType::Vec3 => NodeAttributeValue::Vec3([0.5, -1.5, 2.0]),
+            DataType::Vec4 => NodeAttributeValue::Vec4([0.5, -1.5, 2.0, 3.25]),
+            DataType::Mat2 => NodeAttributeValue::Mat2([[1.0, 2.0], [3.0, 4.0]]),
+            DataType::Mat3 => NodeAttributeValue::Mat3([[1.0, 2.0, 3.0]; 3]),
+            DataType::Mat3x4 => NodeAttributeValue::Mat3x4([[1.0, 2.0, 3.0, 4.0]; 3]),
+            DataType::Mat4x3 => NodeAttributeValue::Mat4x3([[1.0, 2.0, 3.0]; 4]),
+            DataType::Mat4 => NodeAttributeValue::Mat4([[1.0, -2.0, 3.5, 4.0]; 4]),
+            DataType::Bool => NodeAttributeValue::Bool(true),
+            DataType::String
+            | DataType::Path
+            | DataType::FixedString
+            | DataType::LSString
+            | DataType::WString
+            | DataType::LSWString => NodeAttributeValue::String("Some text; [with] |chars|".into()),
+            DataType::ULongLong => NodeAttributeValue::UInt64(u64::MAX),
+            DataType::ScratchBuffer => NodeAttributeValue::Bytes(vec![0, 1, 2, 255]),
+            DataType::Long | DataType::Int64 => NodeAttributeValue::Int64(i64::MIN),
+            DataType::Int8 => NodeAttributeValue::I8(-128),
+            DataType::TranslatedString => {
+                NodeAttributeValue::TranslatedString(TranslatedString::new("h1234abcd".into(), 3))
+            }
+            DataType::TranslatedFSString => NodeAttributeValue::TranslatedFSString(
+                TranslatedFSString::new(TranslatedString::new("h1234abcd".into(), 1), vec![]),
+            ),
+            DataType::Uuid => {
+                NodeAttributeValue::Uuid(Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef))
+            }
+            DataType::Unknown => NodeAttributeValue::Raw {
+                type_id: 99,
+                bytes: vec![1, 2],
+            },
+        }
+    }
+
+    #[test]
+    fn every_type_round_trips_through_its_string_form() -> Result<(), String> {
+        for id in 0..=DataType::max() as u32 + 1 {
+            let ty = DataType::from(id);
+            let value = sample_value(ty);
+            let parsed = NodeAttributeValue::parse(ty, &value.to_string())?;
+            assert_eq!(parsed, value, "{ty} did not round trip");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn raw_values_keep_their_type_id() -> Result<(), String> {
+        let value = NodeAttributeValue::Raw {
+            type_id: 40,
+            bytes: vec![0x00, 0xab, 0x10],
+        };
+        assert_eq!(value.to_string(), "40:00ab10");
+        assert_eq!(
+            NodeAttributeValue::parse(DataType::Unknown, "40:00AB10")?,
+            value
+        );
+        for s in ["00ab10", "x:00", "40:0", "40:zz"] {
+            assert!(NodeAttributeValue::parse(DataType::Unknown, s).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn vectors_and_matrices_are_whitespace_separated() -> Result<(), String> {
+        let mat = NodeAttributeValue::Mat2([[1.0, 2.0], [3.0, 4.5]]);
+        assert_eq!(mat.to_string(), "1 2 3 4.5");
+        assert_eq!(
+            NodeAttributeValue::parse(DataType::Mat2, " 1  2\n3 4.5 ")?,
+            mat
+        );
+        assert!(NodeAttributeValue::parse(DataType::Mat2, "1 2 3").is_err());
+        assert!(NodeAttributeValue::parse(DataType::IVec2, "1 2 3").is_err());
+        assert!(NodeAttributeValue::parse(DataType::IVec2, "1 x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn bools_accept_numbers() -> Result<(), String> {
+        for (s, expected) in [("1", true), ("0", false), ("true", true), ("FALSE", false)] {
+            assert_eq!(
+                NodeAttributeValue::parse(DataType::Bool, s)?,
+                NodeAttributeValue::Bool(expected)
+            );
+        }
+        assert_eq!(NodeAttributeValue::Bool(false).to_string(), "False");
+        assert!(NodeAttributeValue::parse(DataType::Bool, "2").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn uuids_use_the_hyphenated_form() -> Result<(), String> {
+        let s = "01234567-89ab-cdef-0123-456789abcdef";
+        let value = NodeAttributeValue::parse(DataType::Uuid, s)?;
+        assert_eq!(value.to_string(), s);
+        assert!(NodeAttributeValue::parse(DataType::Uuid, "not-a-uuid").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn fs_string_arguments_round_trip() -> Result<(), String> {
+        let nested = TranslatedFSString::new(
+            TranslatedString::new("hInner".into(), 2),
+            vec![TranslatedFSStringArgument::new(
+                "Depth".into(),
+                TranslatedFSString::new(TranslatedString::new("hDeep".into(), 0), vec![]),
+                "2".into(),
+            )],
+        );
+        let value = NodeAttributeValue::TranslatedFSString(TranslatedFSString::new(
+            TranslatedString::new("hOuter".into(), 1),
+            vec![
+                TranslatedFSStringArgument::new("Count".into(), nested, "[3|4]".into()),
+                TranslatedFSStringArgument::new(
+                    "Name\\".into(),
+                    TranslatedFSString::new(TranslatedString::new("".into(), 0), vec![]),
+                    "".into(),
+                ),
+            ],
+        ));
+
+        let text = value.to_string();
+        assert_eq!(
+            text,
+            r"hOuter;1[Count|hInner;2[Depth|hDeep;0|2]|\[3\|4\]][Name\\|;0|]"
+        );
+        assert_eq!(
+            NodeAttributeValue::parse(DataType::TranslatedFSString, &text)?,
+            value
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_fs_string_arguments_fail() {
+        for s in [
+            "h;1[Key",
+            "h;1[Key|h;0",
+            "h;1[Key|h;0|v",
+            "h;1[Key|h;0|v]x",
+            "h;1\\",
+        ] {
+            assert!(
+                NodeAttributeValue::parse(DataType::TranslatedFSString, s).is_err(),
+                "'{s}' should not parse"
+            );
+        }
+    }
+}
@@ -1,8 +1,14 @@
 use flate2::bufread::ZlibDecoder;
+use flate2::write::ZlibEncoder;
 use uuid::Uuid;
 
 use crate::abstract_file_info::CompressionMethod;
-use std::io::{prelude::*, Cursor};
+use std::io::{Cursor, prelude::*};
+
+pub const LZ4_FRAME_MAGIC: [u8; 4] = [0x04, 0x22, 0x4D, 0x18];
+pub const ZSTD_FRAME_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+/// Largest size LZ4 data can decompress to, per compressed byte.
+pub const LZ4_MAX_RATIO: u64 = 255;
 
 pub fn decompress(
     compressed: &[u8],
@@ -18,7 +24,8 @@ pub fn decompress(
 
     match val {
         CompressionMethod::LZ4 => {
-            if chunked {
+            // A block can't start with a match, so a frame magic number can't be a valid block start
+            if chunked || compressed.starts_with(&LZ4_FRAME_MAGIC) {
                 let br = Cursor::new(compressed);
                 let mut buf = vec![0; decompressed_size];
                 lz4_flex::frame::FrameDecoder::new(br)
@@ -52,6 +59,50 @@ pub fn decompress(
     }
 }
 
+/// Compresses `data` like `compress`, except for LZ4 which is written as a frame, the form
+/// `decompress` reads back with chunking. The frame records the size of `data`.
+pub fn compress_chunked(data: &[u8], compression_flags: u8) -> Result<Vec<u8>, String> {
+    if CompressionMethod::get(compression_flags) != Some(CompressionMethod::LZ4) {
+        return compress(data, compression_flags);
+    }
+
+    let frame_info = lz4_flex::frame::FrameInfo::new().content_size(Some(data.len() as u64));
+    let mut encoder = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, vec![]);
+    encoder
+        .write_all(data)
+        .map_err(|e| format!("failed to compress LZ4 chunked (frame) file: {e}"))?;
+    encoder
+        .finish()
+        .map_err(|e| format!("failed to compress LZ4 chunked (frame) file: {e}"))
+}
+
+/// Compresses `data` with the method of `compression_flags`, in the form `decompress` reads
+/// back without chunking: LZ4 blocks, zlib streams and zstd frames.
+pub fn compress(data: &[u8], compression_flags: u8) -> Result<Vec<u8>, String> {
+    let Some(val) = CompressionMethod::get(compression_flags) else {
+        return Err(format!(
+            "unsupported compression method - flags {compression_flags}"
+        ));
+    };
+
+    match val {
+        CompressionMethod::LZ4 => Ok(lz4_flex::block::compress(data)),
+
+        CompressionMethod::Zlib => {
+            let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+            encoder
+                .write_all(data)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| format!("failed to compress zlib file: {e}"))
+        }
+
+        CompressionMethod::ZSTD => zstd::encode_all(Cursor::new(data), 0)
+            .map_err(|e| format!("failed to compress zstd file: {e}")),
+
+        CompressionMethod::None => Ok(data.to_vec()),
+    }
+}
+
 pub trait ReadExt {
     fn read_u64(&mut self) -> Result<u64, String>;
     fn read_i64(&mut self) -> Result<i64, String>;
@@ -177,3 +228,68 @@ impl<T: Read> ReadExt for T {
         Ok(Uuid::from_bytes(buf))
     }
 }
+
+pub trait WriteExt {
+    fn write_u64(&mut self, value: u64) -> Result<(), String>;
+    fn write_i64(&mut self, value: i64) -> Result<(), String>;
+    fn write_u32(&mut self, value: u32) -> Result<(), String>;
+    fn write_i32(&mut self, value: i32) -> Result<(), String>;
+    fn write_u16(&mut self, value: u16) -> Result<(), String>;
+    fn write_i16(&mut self, value: i16) -> Result<(), String>;
+    fn write_u8(&mut self, value: u8) -> Result<(), String>;
+    fn write_i8(&mut self, value: i8) -> Result<(), String>;
+    fn write_f32(&mut self, value: f32) -> Result<(), String>;
+    fn write_f64(&mut self, value: f64) -> Result<(), String>;
+}
+
+impl<T: Write> WriteExt for T {
+    fn write_u64(&mut self, value: u64) -> Result<(), String> {
+        self.write_all(&value.to_le_bytes())
+            .map_err(|e| format!("failed writing u64: {e}"))
+    }
+
+    fn write_i64(&mut self, value: i64) -> Result<(), String> {
+        self.write_all(&value.to_le_bytes())
+            .map_err(|e| format!("failed writing i64: {e}"))
+    }
+
+    fn write_u32(&mut self, value: u32) -> Result<(), String> {
+        self.write_all(&value.to_le_bytes())
+            .map_err(|e| format!("failed writing u32: {e}"))
+    }
+
+    fn write_i32(&mut self, value: i32) -> Result<(), String> {
+        self.write_all(&value.to_le_bytes())
+            .map_err(|e| format!("failed writing i32: {e}"))
+    }
+
+    fn write_u16(&mut self, value: u16) -> Result<(), String> {
+        self.write_all(&value.to_le_bytes())
+            .map_err(|e| format!("failed writing u16: {e}"))
+    }
+
+    fn write_i16(&mut self, value: i16) -> Result<(), String> {
+        self.write_all(&value.to_le_bytes())
+            .map_err(|e| format!("failed writing i16: {e}"))
+    }
+
+    fn write_u8(&mut self, value: u8) -> Result<(), String> {
+        self.write_all(&[value])
+            .map_err(|e| format!("failed writing u8: {e}"))
+    }
+
+    fn write_i8(&mut self, value: i8) -> Result<(), String> {
+        self.write_all(&value.to_le_bytes())
+            .map_err(|e| format!("failed writing i8: {e}"))
+    }
+
+    fn write_f32(&mut self, value: f32) -> Result<(), String> {
+        self.write_all(&value.to_le_bytes())
+            .map_err(|e| format!("failed writing f32: {e}"))
+    }
+
+    fn write_f64(&mut self, value: f64) -> Result<(), String> {
+        self.write_all(&value.to_le_bytes())
+            .map_err(|e| format!("failed writing f64: {e}"))
+    }
+}
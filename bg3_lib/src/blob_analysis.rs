@@ -0,0 +1,627 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use uuid::Uuid;
+
+use crate::LSPK_SIGNATURE;
+use crate::bin_utils::{LZ4_FRAME_MAGIC, ZSTD_FRAME_MAGIC};
+use crate::lsf_reader::{LSOF_SIGNATURE, NodeAttributeValue, Resource, lsf_file_size};
+
+/// Shortest run of plausible floats reported as an array.
+const MIN_FLOAT_RUN: usize = 4;
+/// Shortest and longest string read after a length prefix.
+const MIN_STRING_LEN: usize = 3;
+const MAX_STRING_LEN: usize = 1024;
+/// Strides tried when looking for repeating records.
+const MAX_RECORD_STRIDE: usize = 512;
+const MIN_RECORD_COUNT: usize = 3;
+/// Share of non-zero bytes equal to the byte one stride later, for the stride to be reported.
+const MIN_STRIDE_SCORE: f32 = 0.5;
+
+/// Format announced by a signature found in a blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddedFormat {
+    Lsf,
+    Package,
+    Lz4Frame,
+    ZstdFrame,
+}
+
+impl Display for EmbeddedFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lsf => f.write_str("LSF"),
+            Self::Package => f.write_str("LSPK package"),
+            Self::Lz4Frame => f.write_str("LZ4 frame"),
+            Self::ZstdFrame => f.write_str("zstd frame"),
+        }
+    }
+}
+
+/// Attribute of the resource holding a UUID also found in a blob.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UuidReference {
+    pub node: usize,
+    pub attribute: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlobAnnotationKind {
+    /// Signature of an embedded file. The annotation covers the whole file when its size is
+    /// known, and the rest of the blob otherwise.
+    Signature(EmbeddedFormat),
+    /// UUID also found in attributes of the resource. `mixed_endian` is set when its first
+    /// three groups are stored little-endian, the way Windows GUIDs are.
+    Uuid {
+        uuid: Uuid,
+        mixed_endian: bool,
+        references: Vec<UuidReference>,
+    },
+    /// Text preceded by its length in bytes, stored on `prefix_size` bytes.
+    LengthPrefixedString {
+        prefix_size: usize,
+        value: String,
+    },
+    FloatArray(Vec<f32>),
+    /// Records of `stride` bytes repeating over the annotated range, whose offset is a
+    /// multiple of the stride rather than the exact start of the first record.
+    RecordStride {
+        stride: usize,
+        count: usize,
+    },
+}
+
+/// Likely structure found in a range of a blob.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlobAnnotation {
+    pub offset: usize,
+    pub len: usize,
+    pub kind: BlobAnnotationKind,
+}
+
+impl BlobAnnotation {
+    pub fn end(&self) -> usize {
+        self.offset + self.len
+    }
+}
+
+impl Display for BlobAnnotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#06X}+{}: ", self.offset, self.len)?;
+        match &self.kind {
+            BlobAnnotationKind::Signature(format) => write!(f, "{format}"),
+            BlobAnnotationKind::Uuid {
+                uuid,
+                mixed_endian,
+                references,
+            } => {
+                write!(f, "UUID {uuid}")?;
+                if *mixed_endian {
+                    write!(f, " (mixed-endian)")?;
+                }
+                write!(f, ", in {} attribute(s)", references.len())
+            }
+            BlobAnnotationKind::LengthPrefixedString { prefix_size, value } => {
+                write!(f, "string with {prefix_size}-byte length: \"{value}\"")
+            }
+            BlobAnnotationKind::FloatArray(values) => {
+                write!(f, "{} floats: {values:?}", values.len())
+            }
+            BlobAnnotationKind::RecordStride { stride, count } => {
+                write!(f, "{count} records of {stride} bytes")
+            }
+        }
+    }
+}
+
+/// Guesses at the structure of binary attributes nobody has decoded yet.
+///
+/// Annotations may overlap: a string may sit inside a record, and floats inside an
+/// embedded file. They are sorted by offset, longer ones first.
+#[derive(Debug, Clone, Default)]
+pub struct BlobAnalyzer {
+    known_uuids: HashMap<Uuid, Vec<UuidReference>>,
+}
+
+impl BlobAnalyzer {
+    /// Analyzer without a resource, which does not report UUIDs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Analyzer reporting the UUIDs found in `resource`, either in `Uuid` attributes or at the
+    /// end of strings, like `S_Player_Karlach_2c76687d-93a2-477b-8b18-8a14b549304c`.
+    pub fn with_resource(resource: &Resource) -> Self {
+        let mut known_uuids: HashMap<Uuid, Vec<UuidReference>> = HashMap::new();
+        for (node_idx, node) in resource.regions.node_instances.iter().enumerate() {
+            for (name, attr) in &node.attributes {
+                let uuid = match &attr.value {
+                    NodeAttributeValue::Uuid(uuid) => Some(*uuid),
+                    NodeAttributeValue::String(s) => uuid_suffix(s),
+                    _ => None,
+                };
+                if let Some(uuid) = uuid.filter(|uuid| !uuid.is_nil()) {
+                    known_uuids.entry(uuid).or_default().push(UuidReference {
+                        node: node_idx,
+                        attribute: name.to_string(),
+                    });
+                }
+            }
+        }
+        for references in known_uuids.values_mut() {
+            references.sort();
+        }
+        Self { known_uuids }
+    }
+
+    pub fn analyze(&self, bytes: &[u8]) -> Vec<BlobAnnotation> {
+        let mut annotations = vec![];
+        annotations.extend(find_signatures(bytes));
+        annotations.extend(self.find_uuids(bytes));
+        annotations.extend(find_strings(bytes));
+        annotations.extend(find_float_arrays(bytes));
+        annotations.extend(find_record_stride(bytes));
+        annotations
+            .sort_by_key(|annotation| (annotation.offset, std::cmp::Reverse(annotation.len)));
+        annotations
+    }
+
+    fn find_uuids(&self, bytes: &[u8]) -> Vec<BlobAnnotation> {
+        if self.known_uuids.is_empty() {
+            return vec![];
+        }
+
+        let mut annotations = vec![];
+        let mut offset = 0;
+        while offset + 16 <= bytes.len() {
+            let Ok(chunk) = <[u8; 16]>::try_from(&bytes[offset..offset + 16]) else {
+                break;
+            };
+            let found = [
+                (Uuid::from_bytes(chunk), false),
+                (Uuid::from_bytes_le(chunk), true),
+            ]
+            .into_iter()
+            .find_map(|(uuid, mixed_endian)| {
+                Some((uuid, mixed_endian, self.known_uuids.get(&uuid)?))
+            });
+
+            match found {
+                Some((uuid, mixed_endian, references)) => {
+                    annotations.push(BlobAnnotation {
+                        offset,
+                        len: 16,
+                        kind: BlobAnnotationKind::Uuid {
+                            uuid,
+                            mixed_endian,
+                            references: references.clone(),
+                        },
+                    });
+                    offset += 16;
+                }
+                None => offset += 1,
+            }
+        }
+        annotations
+    }
+}
+
+/// UUID ending a string, possibly after a name and an underscore.
+fn uuid_suffix(s: &str) -> Option<Uuid> {
+    let start = s.len().checked_sub(36)?;
+    if start > 0 && s.as_bytes()[start - 1] != b'_' {
+        return None;
+    }
+    Uuid::try_parse(s.get(start..)?).ok()
+}
+
+fn find_signatures(bytes: &[u8]) -> Vec<BlobAnnotation> {
+    let signatures = [
+        (LSOF_SIGNATURE, EmbeddedFormat::Lsf),
+        (LSPK_SIGNATURE, EmbeddedFormat::Package),
+        (LZ4_FRAME_MAGIC, EmbeddedFormat::Lz4Frame),
+        (ZSTD_FRAME_MAGIC, EmbeddedFormat::ZstdFrame),
+    ];
+
+    let mut annotations = vec![];
+    for offset in 0..bytes.len().saturating_sub(3) {
+        let rest = &bytes[offset..];
+        for (signature, format) in signatures {
+            if !rest.starts_with(&signature) {
+                continue;
+            }
+            let len = match format {
+                EmbeddedFormat::Lsf => lsf_file_size(rest).unwrap_or(rest.len()),
+                _ => rest.len(),
+            };
+            annotations.push(BlobAnnotation {
+                offset,
+                len,
+                kind: BlobAnnotationKind::Signature(format),
+            });
+        }
+    }
+    annotations
+}
+
+/// Strings of printable UTF-8 preceded by a 4-byte or 2-byte length, with or without a
+/// null terminator counted in the length.
+fn find_strings(bytes: &[u8]) -> Vec<BlobAnnotation> {
+    let mut annotations = vec![];
+    let mut offset = 0;
+    'scan: while offset < bytes.len() {
+        for prefix_size in [4, 2] {
+            let Some(prefix) = bytes.get(offset..offset + prefix_size) else {
+                continue;
+            };
+            let len = prefix
+                .iter()
+                .rev()
+                .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+            if !(MIN_STRING_LEN..=MAX_STRING_LEN).contains(&len) {
+                continue;
+            }
+            let start = offset + prefix_size;
+            let Some(text) = bytes.get(start..start + len) else {
+                continue;
+            };
+            let text = text.strip_suffix(&[0]).unwrap_or(text);
+            let Ok(value) = std::str::from_utf8(text) else {
+                continue;
+            };
+            if value.chars().count() >= MIN_STRING_LEN
+                && value
+                    .chars()
+                    .all(|c| !c.is_control() || c == '\t' || c == '\n')
+            {
+                annotations.push(BlobAnnotation {
+                    offset,
+                    len: prefix_size + len,
+                    kind: BlobAnnotationKind::LengthPrefixedString {
+                        prefix_size,
+                        value: value.to_string(),
+                    },
+                });
+                offset = start + len;
+                continue 'scan;
+            }
+        }
+        offset += 1;
+    }
+    annotations
+}
+
+/// Floats likely to be game data: zero, or neither tiny nor huge.
+/// Small integers read as floats are denormal, and so are not plausible.
+fn is_plausible_float(v: f32) -> bool {
+    v == 0.0 || (v.is_normal() && (1e-4..=1e7).contains(&v.abs()))
+}
+
+/// Runs of plausible floats that are not all zero.
+fn find_float_arrays(bytes: &[u8]) -> Vec<BlobAnnotation> {
+    let float_at = |offset: usize| {
+        let chunk = bytes.get(offset..offset + 4)?;
+        Some(f32::from_le_bytes(chunk.try_into().ok()?))
+    };
+
+    let mut annotations = vec![];
+    let mut offset = 0;
+    while offset + 4 * MIN_FLOAT_RUN <= bytes.len() {
+        let values: Vec<f32> = (0..)
+            .map_while(|i| float_at(offset + 4 * i).filter(|v| is_plausible_float(*v)))
+            .collect();
+        let non_zero = values.iter().filter(|v| **v != 0.0).count();
+
+        if values.len() >= MIN_FLOAT_RUN && non_zero * 2 >= values.len() {
+            let len = 4 * values.len();
+            annotations.push(BlobAnnotation {
+                offset,
+                len,
+                kind: BlobAnnotationKind::FloatArray(values),
+            });
+            offset += len;
+        } else {
+            offset += 1;
+        }
+    }
+    annotations
+}
+
+/// Longest range over which records of the same size repeat.
+///
+/// For each stride, the blob is cut into blocks of that size, and a block repeats when most
+/// of its non-zero bytes equal the byte one stride later. Multiples of the record size
+/// repeat as well as the size itself, so the shortest stride covering about as many bytes
+/// as the best one is kept.
+fn find_record_stride(bytes: &[u8]) -> Option<BlobAnnotation> {
+    let max_stride = MAX_RECORD_STRIDE.min(bytes.len() / MIN_RECORD_COUNT);
+
+    // Stride, first block and record count of the longest repeating range of each stride
+    let mut candidates = vec![];
+    for stride in 4..=max_stride {
+        let mut best: Option<(usize, usize)> = None;
+        let mut run_start = 0;
+        let mut run_len = 0;
+        for (block, chunk) in bytes[..bytes.len() - stride].chunks(stride).enumerate() {
+            let start = block * stride;
+            let (mut compared, mut equal) = (0usize, 0usize);
+            for (i, byte) in chunk.iter().enumerate() {
+                if *byte != 0 {
+                    compared += 1;
+                    equal += usize::from(*byte == bytes[start + i + stride]);
+                }
+            }
+
+            if compared > 0 && equal as f32 >= MIN_STRIDE_SCORE * compared as f32 {
+                if run_len == 0 {
+                    run_start = block;
+                }
+                run_len += 1;
+                // The last block of the run repeats into the next one
+                if best.is_none_or(|(_, count)| run_len + 1 > count) {
+                    best = Some((run_start, run_len + 1));
+                }
+            } else {
+                run_len = 0;
+            }
+        }
+
+        if let Some((first_block, count)) = best.filter(|(_, count)| *count >= MIN_RECORD_COUNT) {
+            candidates.push((stride, first_block, count));
+        }
+    }
+
+    let max_covered = candidates
+        .iter()
+        .map(|(stride, _, count)| stride * count)
+        .max()?;
+    let (stride, first_block, count) = candidates
+        .into_iter()
+        .find(|(stride, _, count)| 10 * stride * count >= 9 * max_covered)?;
+    let offset = first_block * stride;
+
+    Some(BlobAnnotation {
+        offset,
+        len: (count * stride).min(bytes.len() - offset),
+        kind: BlobAnnotationKind::RecordStride { stride, count },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsf_reader::{DataType, NodeAttribute};
+    use crate::lsf_writer::LSFWriter;
+    use crate::test_support::{add_node, sample_resource};
+
+    /// Bytes that look like nothing, from a linear congruential generator.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn signatures_are_found() -> Result<(), String> {
+        let lsf = LSFWriter::new()
+            .with_compression(0)
+            .write_bytes(&sample_resource())?;
+        let mut bytes = vec![1, 2, 3];
+        bytes.extend(&lsf);
+        bytes.extend([0xAA; 5]);
+        bytes.extend(LZ4_FRAME_MAGIC);
+        bytes.extend([0; 8]);
+
+        let annotations = find_signatures(&bytes);
+        assert_eq!(
+            annotations,
+            vec![
+                BlobAnnotation {
+                    offset: 3,
+                    len: lsf.len(),
+                    kind: BlobAnnotationKind::Signature(EmbeddedFormat::Lsf),
+                },
+                BlobAnnotation {
+                    offset: 8 + lsf.len(),
+                    len: 12,
+                    kind: BlobAnnotationKind::Signature(EmbeddedFormat::Lz4Frame),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn partial_signatures_are_not_found() {
+        assert!(find_signatures(&noise(512)).is_empty());
+        assert!(find_signatures(b"LSO").is_empty());
+        assert!(find_signatures(b"xxLSOxLSPxx").is_empty());
+    }
+
+    #[test]
+    fn uuids_of_the_resource_are_found() {
+        let id = Uuid::from_u128(0x2c76687d_93a2_477b_8b18_8a14b549304c);
+        let karlach = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+        let mut resource = sample_resource();
+        add_node(
+            &mut resource,
+            "Character",
+            None,
+            vec![
+                (
+                    "Id",
+                    NodeAttribute {
+                        ty: DataType::Uuid,
+                        value: NodeAttributeValue::Uuid(id),
+                    },
+                ),
+                (
+                    "Template",
+                    NodeAttribute {
+                        ty: DataType::FixedString,
+                        value: NodeAttributeValue::String(
+                            format!("S_Player_Karlach_{karlach}").into(),
+                        ),
+                    },
+                ),
+            ],
+        );
+        let analyzer = BlobAnalyzer::with_resource(&resource);
+
+        let mut bytes = vec![7; 3];
+        bytes.extend(id.as_bytes());
+        bytes.extend(karlach.to_bytes_le());
+        let annotations = analyzer.find_uuids(&bytes);
+        assert_eq!(
+            annotations,
+            vec![
+                BlobAnnotation {
+                    offset: 3,
+                    len: 16,
+                    kind: BlobAnnotationKind::Uuid {
+                        uuid: id,
+                        mixed_endian: false,
+                        references: vec![UuidReference {
+                            node: 9,
+                            attribute: "Id".to_string(),
+                        }],
+                    },
+                },
+                BlobAnnotation {
+                    offset: 19,
+                    len: 16,
+                    kind: BlobAnnotationKind::Uuid {
+                        uuid: karlach,
+                        mixed_endian: true,
+                        references: vec![UuidReference {
+                            node: 9,
+                            attribute: "Template".to_string(),
+                        }],
+                    },
+                },
+            ]
+        );
+        // Without a resource, no UUID is known
+        assert!(BlobAnalyzer::new().find_uuids(&bytes).is_empty());
+    }
+
+    #[test]
+    fn unknown_uuids_are_not_found() {
+        let analyzer = BlobAnalyzer::with_resource(&sample_resource());
+        let mut bytes = noise(64);
+        bytes.extend(Uuid::from_u128(0xdead_beef).as_bytes());
+        // Nil UUIDs of the resource are not reported either
+        bytes.extend([0; 16]);
+        assert!(analyzer.find_uuids(&bytes).is_empty());
+
+        assert_eq!(
+            uuid_suffix("S_Karlach_2c76687d-93a2-477b-8b18-8a14b549304c"),
+            Uuid::try_parse("2c76687d-93a2-477b-8b18-8a14b549304c").ok()
+        );
+        assert_eq!(
+            uuid_suffix("Karlach2c76687d-93a2-477b-8b18-8a14b549304c"),
+            None
+        );
+        assert_eq!(uuid_suffix("S_Karlach"), None);
+    }
+
+    #[test]
+    fn length_prefixed_strings_are_found() {
+        let mut bytes = vec![5, 0, 0, 0];
+        bytes.extend(b"Hello");
+        bytes.extend([5, 0]);
+        bytes.extend(b"Gale\0");
+
+        assert_eq!(
+            find_strings(&bytes),
+            vec![
+                BlobAnnotation {
+                    offset: 0,
+                    len: 9,
+                    kind: BlobAnnotationKind::LengthPrefixedString {
+                        prefix_size: 4,
+                        value: "Hello".to_string(),
+                    },
+                },
+                BlobAnnotation {
+                    offset: 9,
+                    len: 7,
+                    kind: BlobAnnotationKind::LengthPrefixedString {
+                        prefix_size: 2,
+                        value: "Gale".to_string(),
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn short_truncated_or_binary_strings_are_not_found() {
+        // Too short, control characters, and longer than the blob
+        assert!(find_strings(&[2, 0, 0, 0, b'a', b'b']).is_empty());
+        assert!(find_strings(&[4, 0, 0, 0, 1, 2, 3, 4]).is_empty());
+        assert!(find_strings(&[10, 0, 0, 0, b'a', b'b', b'c']).is_empty());
+    }
+
+    #[test]
+    fn float_runs_are_found() {
+        let values = [1.5, -2.25, 100.0, 0.0, 3.0];
+        let mut bytes = floats(&values);
+        bytes.extend([0xFF; 4]);
+
+        assert_eq!(
+            find_float_arrays(&bytes),
+            vec![BlobAnnotation {
+                offset: 0,
+                len: 20,
+                kind: BlobAnnotationKind::FloatArray(values.to_vec()),
+            }]
+        );
+    }
+
+    #[test]
+    fn integers_and_zeros_are_not_float_runs() {
+        let integers: Vec<u8> = (1u32..=8).flat_map(|v| v.to_le_bytes()).collect();
+        assert!(find_float_arrays(&integers).is_empty());
+        assert!(find_float_arrays(&[0; 64]).is_empty());
+        // Too few plausible values in a row
+        assert!(find_float_arrays(&floats(&[1.0, 2.0, 3.0])).is_empty());
+    }
+
+    #[test]
+    fn record_strides_are_found() {
+        let mut bytes = vec![];
+        for id in 1..=5u8 {
+            bytes.extend([id, 0, 0, 0, 0xAA, 0xBB, 0xCC, 0xDD]);
+            bytes.extend(1.0f32.to_le_bytes());
+        }
+
+        assert_eq!(
+            find_record_stride(&bytes),
+            Some(BlobAnnotation {
+                offset: 0,
+                len: 60,
+                kind: BlobAnnotationKind::RecordStride {
+                    stride: 12,
+                    count: 5,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn noise_has_no_record_stride() {
+        assert_eq!(find_record_stride(&noise(300)), None);
+        assert_eq!(find_record_stride(&[0; 300]), None);
+        // Two records are not enough
+        assert_eq!(find_record_stride(&[1, 2, 3, 4, 1, 2, 3, 4]), None);
+    }
+}
@@ -0,0 +1,548 @@
+use std::fmt::Display;
+
+use uuid::Uuid;
+
+use crate::blob_template::BlobTemplate;
+use crate::lsf_reader::{NodeAttributeValue, Resource};
+use crate::node_path::{NodePath, NodeSelector};
+
+/// Structured value a blob decodes to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Uuid(Uuid),
+    Bytes(Vec<u8>),
+    List(Vec<DecodedValue>),
+    /// Named fields, in the order they are stored.
+    Struct(Vec<(String, DecodedValue)>),
+}
+
+impl DecodedValue {
+    /// Field of a struct value.
+    pub fn field(&self, name: &str) -> Option<&DecodedValue> {
+        match self {
+            Self::Struct(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut DecodedValue> {
+        match self {
+            Self::Struct(fields) => fields.iter_mut().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(v) => Some(*v),
+            Self::UInt(v) => i64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl Display for DecodedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool(v) => write!(f, "{v}"),
+            Self::Int(v) => write!(f, "{v}"),
+            Self::UInt(v) => write!(f, "{v}"),
+            Self::Float(v) => write!(f, "{v}"),
+            Self::String(s) => write!(f, "\"{s}\""),
+            Self::Uuid(v) => write!(f, "{v}"),
+            Self::Bytes(bytes) => {
+                for byte in bytes {
+                    write!(f, "{byte:02X}")?;
+                }
+                Ok(())
+            }
+            Self::List(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Self::Struct(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}: {value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Decoder of one binary attribute format, like the `NewAge` ScratchBuffer.
+pub trait BlobDecoder {
+    /// Name of the format, for display.
+    fn name(&self) -> &str;
+
+    fn decode(&self, bytes: &[u8]) -> Result<DecodedValue, String>;
+
+    /// Whether `encode` is implemented.
+    fn can_encode(&self) -> bool {
+        false
+    }
+
+    /// Encodes a value back into the bytes `decode` reads.
+    fn encode(&self, _value: &DecodedValue) -> Result<Vec<u8>, String> {
+        Err(format!("{} blobs cannot be encoded", self.name()))
+    }
+}
+
+/// Blob attribute decoded by a registered decoder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedBlob {
+    pub node: usize,
+    pub attribute: String,
+    pub decoder: String,
+    pub value: Result<DecodedValue, String>,
+}
+
+struct RegisteredDecoder {
+    path: NodePath,
+    attribute: String,
+    decoder: Box<dyn BlobDecoder>,
+}
+
+/// Decoders of `Bytes` attributes, keyed by the path of their node and the attribute name.
+///
+/// Paths are patterns: a segment named `*` matches any node name, and a segment without a
+/// selector matches every child with its name rather than the first one.
+/// When several decoders match, the last one registered is used.
+#[derive(Default)]
+pub struct BlobDecoderRegistry {
+    decoders: Vec<RegisteredDecoder>,
+}
+
+impl BlobDecoderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a decoder for an attribute of the nodes matched by `path`.
+    ///
+    /// `path` is read as a pattern, not as `NodePath::resolve` reads it: a segment named `*`
+    /// matches any name, a segment without a selector matches every child with its name
+    /// instead of the first one, and an attribute selector matches every child with that
+    /// value instead of the first one. Index selectors keep their meaning.
+    pub fn register(&mut self, path: NodePath, attribute: &str, decoder: Box<dyn BlobDecoder>) {
+        self.decoders.push(RegisteredDecoder {
+            path,
+            attribute: attribute.to_string(),
+            decoder,
+        });
+    }
+
+    /// Registers a template for each node path and attribute of its `match` directives.
+    pub fn register_template(&mut self, template: BlobTemplate) {
+        for (path, attribute) in template.targets() {
+            self.register(path.clone(), attribute, Box::new(template.clone()));
+        }
+    }
+
+    /// Decoder registered for an attribute of the node at index `node_idx`.
+    pub fn decoder_for(
+        &self,
+        resource: &Resource,
+        node_idx: usize,
+        attribute: &str,
+    ) -> Option<&dyn BlobDecoder> {
+        self.decoders
+            .iter()
+            .rev()
+            .find(|registered| {
+                registered.attribute == attribute
+                    && path_matches(resource, &registered.path, node_idx)
+            })
+            .map(|registered| registered.decoder.as_ref())
+    }
+
+    /// Decodes an attribute, or returns `None` if no decoder is registered for it.
+    pub fn decode(
+        &self,
+        resource: &Resource,
+        node_idx: usize,
+        attribute: &str,
+    ) -> Option<Result<DecodedValue, String>> {
+        let decoder = self.decoder_for(resource, node_idx, attribute)?;
+        Some(blob_bytes(resource, node_idx, attribute).and_then(|bytes| decoder.decode(bytes)))
+    }
+
+    /// Decodes every `Bytes` attribute of the resource that has a decoder.
+    pub fn decode_all(&self, resource: &Resource) -> Vec<DecodedBlob> {
+        let mut blobs = vec![];
+        for (node_idx, node) in resource.regions.node_instances.iter().enumerate() {
+            let mut attributes: Vec<_> = node
+                .attributes
+                .iter()
+                .filter(|(_, attr)| matches!(attr.value, NodeAttributeValue::Bytes(_)))
+                .map(|(name, _)| name)
+                .collect();
+            attributes.sort();
+
+            for attribute in attributes {
+                if let Some(decoder) = self.decoder_for(resource, node_idx, attribute) {
+                    blobs.push(DecodedBlob {
+                        node: node_idx,
+                        attribute: attribute.to_string(),
+                        decoder: decoder.name().to_string(),
+                        value: blob_bytes(resource, node_idx, attribute)
+                            .and_then(|bytes| decoder.decode(bytes)),
+                    });
+                }
+            }
+        }
+        blobs
+    }
+
+    /// Encodes `value` with the decoder of the attribute and stores the bytes in its place.
+    /// A resource decoded from the old bytes is dropped, so the writer does not replace the
+    /// new bytes with it.
+    pub fn encode(
+        &self,
+        resource: &mut Resource,
+        node_idx: usize,
+        attribute: &str,
+        value: &DecodedValue,
+    ) -> Result<(), String> {
+        let decoder = self
+            .decoder_for(resource, node_idx, attribute)
+            .ok_or_else(|| format!("no decoder is registered for attribute {attribute}"))?;
+        let bytes = decoder.encode(value)?;
+        blob_bytes(resource, node_idx, attribute)?;
+
+        if let Some(node) = resource.regions.node_instances.get_mut(node_idx) {
+            node.embedded.remove(attribute);
+            if let Some(attr) = node.attributes.get_mut(attribute) {
+                attr.value = NodeAttributeValue::Bytes(bytes);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn blob_bytes<'a>(
+    resource: &'a Resource,
+    node_idx: usize,
+    attribute: &str,
+) -> Result<&'a [u8], String> {
+    let node = resource
+        .regions
+        .get_node(node_idx)
+        .ok_or_else(|| format!("could not find node at index {node_idx}"))?;
+    match node.attributes.get(attribute).map(|attr| &attr.value) {
+        Some(NodeAttributeValue::Bytes(bytes)) => Ok(bytes),
+        Some(_) => Err(format!(
+            "attribute {attribute} of {} is not a blob",
+            node.name
+        )),
+        None => Err(format!("{} has no attribute {attribute}", node.name)),
+    }
+}
+
+/// Whether the node at `node_idx` is matched by a path pattern, checking its ancestors
+/// from the region down.
+fn path_matches(resource: &Resource, pattern: &NodePath, node_idx: usize) -> bool {
+    let mut chain = vec![];
+    let mut current = Some(node_idx);
+    while let Some(idx) = current {
+        let Some(node) = resource.regions.get_node(idx) else {
+            return false;
+        };
+        if chain.len() == pattern.segments.len() {
+            return false;
+        }
+        chain.push(idx);
+        current = node.parent;
+    }
+    if chain.len() != pattern.segments.len() {
+        return false;
+    }
+
+    chain
+        .iter()
+        .rev()
+        .zip(&pattern.segments)
+        .all(|(idx, segment)| {
+            let Some(node) = resource.regions.get_node(*idx) else {
+                return false;
+            };
+            if segment.name != "*" && segment.name != *node.name {
+                return false;
+            }
+            match &segment.selector {
+                None => true,
+                Some(NodeSelector::Index(i)) => node
+                    .parent
+                    .and_then(|parent| resource.regions.get_node(parent))
+                    .and_then(|parent| parent.children.get(&node.name))
+                    .is_some_and(|siblings| siblings.get(*i) == Some(idx)),
+                Some(NodeSelector::Attribute { name, value }) => node
+                    .attributes
+                    .get(name.as_str())
+                    .is_some_and(|attr| attr.value.to_string() == *value),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsf_reader::{DataType, LSFReader, NodeAttribute};
+    use crate::lsf_writer::LSFWriter;
+    use crate::node_path::NodePathSegment;
+    use crate::test_support::{node_mut, sample_resource};
+
+    /// Blob holding one little-endian `u32`.
+    struct Counter(&'static str);
+
+    impl BlobDecoder for Counter {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<DecodedValue, String> {
+            let bytes: [u8; 4] = bytes.try_into().map_err(|_| "expected 4 bytes")?;
+            Ok(DecodedValue::UInt(u32::from_le_bytes(bytes).into()))
+        }
+
+        fn can_encode(&self) -> bool {
+            true
+        }
+
+        fn encode(&self, value: &DecodedValue) -> Result<Vec<u8>, String> {
+            match value {
+                DecodedValue::UInt(v) => Ok(u32::try_from(*v)
+                    .map_err(|e| e.to_string())?
+                    .to_le_bytes()
+                    .to_vec()),
+                _ => Err("expected an unsigned integer".into()),
+            }
+        }
+    }
+
+    fn blob(bytes: Vec<u8>) -> NodeAttribute {
+        NodeAttribute {
+            ty: DataType::ScratchBuffer,
+            value: NodeAttributeValue::Bytes(bytes),
+        }
+    }
+
+    /// Sample resource with a `Data` blob on every `Item` and on `Config/Option`.
+    fn resource_with_blobs() -> Result<Resource, String> {
+        let mut resource = sample_resource();
+        for (idx, path) in [
+            "Templates/Item[0]",
+            "Templates/Item[1]",
+            "Templates/Item[2]",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            node_mut(&mut resource, path)?
+                .attributes
+                .insert("Data".into(), blob((idx as u32).to_le_bytes().to_vec()));
+        }
+        node_mut(&mut resource, "Config/Option")?
+            .attributes
+            .insert("Data".into(), blob(vec![9, 0, 0, 0]));
+        Ok(resource)
+    }
+
+    fn decoder_name(
+        registry: &BlobDecoderRegistry,
+        resource: &Resource,
+        path: &str,
+    ) -> Result<Option<String>, String> {
+        let idx = path
+            .parse::<NodePath>()?
+            .resolve(resource)
+            .ok_or_else(|| format!("{path} not found"))?;
+        Ok(registry
+            .decoder_for(resource, idx, "Data")
+            .map(|decoder| decoder.name().to_string()))
+    }
+
+    #[test]
+    fn patterns_match_every_child_and_wildcards() -> Result<(), String> {
+        let resource = resource_with_blobs()?;
+        let mut registry = BlobDecoderRegistry::new();
+        registry.register("Templates/Item".parse()?, "Data", Box::new(Counter("item")));
+
+        // Without a selector every Item matches, not only the first one
+        for path in [
+            "Templates/Item[0]",
+            "Templates/Item[1]",
+            "Templates/Item[2]",
+        ] {
+            assert_eq!(
+                decoder_name(&registry, &resource, path)?.as_deref(),
+                Some("item")
+            );
+        }
+        assert_eq!(decoder_name(&registry, &resource, "Config/Option")?, None);
+
+        registry.register("*/Option".parse()?, "Data", Box::new(Counter("any")));
+        assert_eq!(
+            decoder_name(&registry, &resource, "Config/Option")?.as_deref(),
+            Some("any")
+        );
+        // Patterns match whole paths, not their prefixes or suffixes
+        registry.register(
+            NodePath::region("Templates"),
+            "Data",
+            Box::new(Counter("x")),
+        );
+        registry.register("Item".parse()?, "Data", Box::new(Counter("y")));
+        assert_eq!(
+            decoder_name(&registry, &resource, "Templates/Item[1]")?.as_deref(),
+            Some("item")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn selectors_and_registration_order() -> Result<(), String> {
+        let resource = resource_with_blobs()?;
+        let mut registry = BlobDecoderRegistry::new();
+        registry.register("Templates/Item".parse()?, "Data", Box::new(Counter("item")));
+        registry.register(
+            NodePath::region("Templates")
+                .child(NodePathSegment::with_attribute("Item", "MapKey", "Sword")),
+            "Data",
+            Box::new(Counter("sword")),
+        );
+        registry.register(
+            NodePath::region("Templates").child(NodePathSegment::with_index("Item", 2)),
+            "Data",
+            Box::new(Counter("third")),
+        );
+
+        // The attribute selector matches both swords; the last match registered wins
+        assert_eq!(
+            decoder_name(&registry, &resource, "Templates/Item[0]")?.as_deref(),
+            Some("sword")
+        );
+        assert_eq!(
+            decoder_name(&registry, &resource, "Templates/Item[1]")?.as_deref(),
+            Some("item")
+        );
+        assert_eq!(
+            decoder_name(&registry, &resource, "Templates/Item[2]")?.as_deref(),
+            Some("third")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn blobs_are_decoded() -> Result<(), String> {
+        let mut resource = resource_with_blobs()?;
+        node_mut(&mut resource, "Templates/Item[2]")?
+            .attributes
+            .insert("Data".into(), blob(vec![1]));
+        let mut registry = BlobDecoderRegistry::new();
+        registry.register("Templates/Item".parse()?, "Data", Box::new(Counter("item")));
+        registry.register(
+            "Templates/Item".parse()?,
+            "MapKey",
+            Box::new(Counter("key")),
+        );
+
+        let blobs = registry.decode_all(&resource);
+        let values: Vec<_> = blobs.iter().map(|blob| blob.value.clone()).collect();
+        assert_eq!(
+            values,
+            vec![
+                Ok(DecodedValue::UInt(0)),
+                Ok(DecodedValue::UInt(1)),
+                Err("expected 4 bytes".to_string()),
+            ]
+        );
+        assert!(blobs.iter().all(|blob| blob.decoder == "item"));
+
+        let item = NodePath::region("Templates")
+            .child(NodePathSegment::with_index("Item", 1))
+            .resolve(&resource)
+            .ok_or("Item[1] not found")?;
+        assert_eq!(
+            registry.decode(&resource, item, "Data"),
+            Some(Ok(DecodedValue::UInt(1)))
+        );
+        assert!(registry.decode(&resource, item, "Scale").is_none());
+        assert!(matches!(
+            registry.decode(&resource, item, "MapKey"),
+            Some(Err(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn encoding_replaces_the_bytes_and_embedded_resource() -> Result<(), String> {
+        let mut resource = resource_with_blobs()?;
+        let embedded = LSFWriter::new().write_bytes(&sample_resource())?;
+        node_mut(&mut resource, "Config/Option")?
+            .attributes
+            .insert("Data".into(), blob(embedded));
+        assert!(resource.decode_embedded().is_empty());
+
+        let mut registry = BlobDecoderRegistry::new();
+        registry.register(
+            "Config/Option".parse()?,
+            "Data",
+            Box::new(Counter("option")),
+        );
+        let option = NodePath::region("Config")
+            .child(NodePathSegment::new("Option"))
+            .resolve(&resource)
+            .ok_or("Option not found")?;
+        registry.encode(&mut resource, option, "Data", &DecodedValue::UInt(42))?;
+        assert!(resource.embedded(option, "Data").is_none());
+
+        let written = LSFWriter::new().write_bytes(&resource)?;
+        let read = LSFReader::new().read_bytes(&written)?;
+        assert_eq!(
+            registry.decode(&read, option, "Data"),
+            Some(Ok(DecodedValue::UInt(42)))
+        );
+
+        assert!(
+            registry
+                .encode(&mut resource, option, "Data", &DecodedValue::Int(1))
+                .is_err()
+        );
+        assert!(
+            registry
+                .encode(&mut resource, option, "Enabled", &DecodedValue::UInt(1))
+                .is_err()
+        );
+        Ok(())
+    }
+}
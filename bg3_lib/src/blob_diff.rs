@@ -0,0 +1,553 @@
+use std::fmt::Display;
+
+use uuid::Uuid;
+
+/// Edits beyond which the byte alignment gives up, and buffers are compared position by position.
+const MAX_ALIGNMENT_EDITS: usize = 1024;
+/// Changed bytes decoded as candidate values, from the start of each changed range.
+const MAX_CANDIDATE_SPAN: usize = 64;
+/// Changed ranges separated by fewer unchanged bytes are reported as one, so that a value
+/// whose bytes partly match before and after the change is not split up.
+const MIN_UNCHANGED_GAP: usize = 4;
+
+/// Range of bytes that differs between two aligned buffers.
+/// One of the two sides is empty for pure insertions and deletions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobChange {
+    pub old_offset: usize,
+    pub old_bytes: Vec<u8>,
+    pub new_offset: usize,
+    pub new_bytes: Vec<u8>,
+}
+
+impl Display for BlobChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:#06X}+{} -> {:#06X}+{}: {} -> {}",
+            self.old_offset,
+            self.old_bytes.len(),
+            self.new_offset,
+            self.new_bytes.len(),
+            hex(&self.old_bytes),
+            hex(&self.new_bytes)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlobValue {
+    U8(u8),
+    I16(i16),
+    I32(i32),
+    F32(f32),
+    Guid(Uuid),
+}
+
+impl BlobValue {
+    fn decode(kind: BlobValueKind, bytes: &[u8], offset: usize) -> Option<Self> {
+        let bytes = bytes.get(offset..offset + kind.size())?;
+        let value = match kind {
+            BlobValueKind::U8 => Self::U8(bytes[0]),
+            BlobValueKind::I16 => Self::I16(i16::from_le_bytes(bytes.try_into().ok()?)),
+            BlobValueKind::I32 => Self::I32(i32::from_le_bytes(bytes.try_into().ok()?)),
+            BlobValueKind::F32 => Self::F32(f32::from_le_bytes(bytes.try_into().ok()?)),
+            BlobValueKind::Guid => Self::Guid(Uuid::from_bytes(bytes.try_into().ok()?)),
+        };
+        Some(value)
+    }
+
+    /// Floats that are unlikely to be game data (NaN, infinite, denormal or huge)
+    /// are not worth reporting as candidates.
+    fn is_plausible(&self) -> bool {
+        match self {
+            Self::F32(v) => v.is_finite() && (*v == 0.0 || (1e-6..=1e9).contains(&v.abs())),
+            _ => true,
+        }
+    }
+}
+
+impl Display for BlobValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::U8(v) => write!(f, "{v}"),
+            Self::I16(v) => write!(f, "{v}"),
+            Self::I32(v) => write!(f, "{v}"),
+            Self::F32(v) => write!(f, "{v}"),
+            Self::Guid(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobValueKind {
+    U8,
+    I16,
+    I32,
+    F32,
+    Guid,
+}
+
+impl BlobValueKind {
+    pub const ALL: [Self; 5] = [Self::U8, Self::I16, Self::I32, Self::F32, Self::Guid];
+
+    pub fn size(&self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::I16 => 2,
+            Self::I32 | Self::F32 => 4,
+            Self::Guid => 16,
+        }
+    }
+
+    /// GUIDs are looked for at 4-byte boundaries, other values at their natural alignment.
+    fn alignment(&self) -> usize {
+        self.size().min(4)
+    }
+}
+
+/// A changed range decoded as a value of some type, before and after the change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlobCandidate {
+    pub old_offset: usize,
+    pub new_offset: usize,
+    pub old: BlobValue,
+    pub new: BlobValue,
+}
+
+impl Display for BlobCandidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.old {
+            BlobValue::U8(_) => "u8",
+            BlobValue::I16(_) => "i16",
+            BlobValue::I32(_) => "i32",
+            BlobValue::F32(_) => "f32",
+            BlobValue::Guid(_) => "guid",
+        };
+        write!(
+            f,
+            "{:#06X} -> {:#06X} ({kind}): {} -> {}",
+            self.old_offset, self.new_offset, self.old, self.new
+        )
+    }
+}
+
+/// Changed ranges between two byte buffers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlobDiff {
+    pub changes: Vec<BlobChange>,
+    /// Whether the buffers differed too much to be aligned, and were compared position by position.
+    pub positional: bool,
+}
+
+impl BlobDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Values of the given types overlapping each changed range, read in both buffers.
+    /// Unchanged values, and floats that are implausible on both sides, are left out.
+    pub fn candidates(
+        &self,
+        old: &[u8],
+        new: &[u8],
+        kinds: &[BlobValueKind],
+    ) -> Vec<(usize, Vec<BlobCandidate>)> {
+        self.changes
+            .iter()
+            .enumerate()
+            .map(|(i, change)| (i, change_candidates(change, old, new, kinds)))
+            .filter(|(_, candidates)| !candidates.is_empty())
+            .collect()
+    }
+}
+
+impl Display for BlobDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Aligns two buffers (shortest edit script over bytes), and reports the ranges that differ.
+pub fn diff_blobs(old: &[u8], new: &[u8]) -> BlobDiff {
+    // The common prefix and suffix can't be part of a shortest edit script
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let (edits, positional) = match shortest_edit_script(old_mid, new_mid) {
+        Some(edits) => (edits, false),
+        None => (positional_edits(old_mid, new_mid), true),
+    };
+
+    BlobDiff {
+        changes: group_edits(&edits, old_mid, new_mid, prefix),
+        positional,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// Myers' O(ND) algorithm. Returns `None` past `MAX_ALIGNMENT_EDITS` edits.
+fn shortest_edit_script(old: &[u8], new: &[u8]) -> Option<Vec<Edit>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max_d = (old.len() + new.len()).min(MAX_ALIGNMENT_EDITS) as isize;
+    let offset = max_d + 1;
+
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // Furthest reaching x of each diagonal, saved before each round for the backtracking
+    let mut trace: Vec<Vec<isize>> = vec![];
+
+    let mut found = None;
+    'rounds: for d in 0..=max_d {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let idx = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                break 'rounds;
+            }
+        }
+    }
+
+    let found = found?;
+
+    let mut edits = vec![];
+    let (mut x, mut y) = (n, m);
+    for d in (1..=found).rev() {
+        // `trace[d]` holds the diagonals -d..=d as they were before round d
+        let prev = &trace[d as usize];
+        let at = |k: isize| prev[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        edits.push(if x == prev_x {
+            Edit::Insert
+        } else {
+            Edit::Delete
+        });
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.extend(std::iter::repeat_n(Edit::Keep, x as usize));
+    edits.reverse();
+
+    Some(edits)
+}
+
+fn positional_edits(old: &[u8], new: &[u8]) -> Vec<Edit> {
+    let mut edits = vec![];
+    for (a, b) in old.iter().zip(new) {
+        if a == b {
+            edits.push(Edit::Keep);
+        } else {
+            edits.extend([Edit::Delete, Edit::Insert]);
+        }
+    }
+    edits.extend(std::iter::repeat_n(
+        Edit::Delete,
+        old.len().saturating_sub(new.len()),
+    ));
+    edits.extend(std::iter::repeat_n(
+        Edit::Insert,
+        new.len().saturating_sub(old.len()),
+    ));
+    edits
+}
+
+/// Merges consecutive deletions and insertions into changed ranges.
+fn group_edits(edits: &[Edit], old: &[u8], new: &[u8], base: usize) -> Vec<BlobChange> {
+    let mut changes = vec![];
+    let (mut x, mut y) = (0, 0);
+    let mut current: Option<BlobChange> = None;
+
+    for edit in edits {
+        match edit {
+            Edit::Keep => {
+                changes.extend(current.take());
+                x += 1;
+                y += 1;
+            }
+            Edit::Delete | Edit::Insert => {
+                let change = current.get_or_insert_with(|| BlobChange {
+                    old_offset: base + x,
+                    old_bytes: vec![],
+                    new_offset: base + y,
+                    new_bytes: vec![],
+                });
+                if *edit == Edit::Delete {
+                    change.old_bytes.push(old[x]);
+                    x += 1;
+                } else {
+                    change.new_bytes.push(new[y]);
+                    y += 1;
+                }
+            }
+        }
+    }
+    changes.extend(current);
+
+    merge_close_changes(changes, old, new, base)
+}
+
+fn merge_close_changes(
+    changes: Vec<BlobChange>,
+    old: &[u8],
+    new: &[u8],
+    base: usize,
+) -> Vec<BlobChange> {
+    let mut merged: Vec<BlobChange> = Vec::with_capacity(changes.len());
+
+    for change in changes {
+        if let Some(last) = merged.last_mut() {
+            let old_end = last.old_offset + last.old_bytes.len();
+            let new_end = last.new_offset + last.new_bytes.len();
+            // Both buffers advance together over unchanged bytes, so both gaps are the same
+            if change.old_offset - old_end < MIN_UNCHANGED_GAP {
+                last.old_bytes
+                    .extend_from_slice(&old[old_end - base..change.old_offset - base]);
+                last.old_bytes.extend(change.old_bytes);
+                last.new_bytes
+                    .extend_from_slice(&new[new_end - base..change.new_offset - base]);
+                last.new_bytes.extend(change.new_bytes);
+                continue;
+            }
+        }
+        merged.push(change);
+    }
+
+    merged
+}
+
+fn change_candidates(
+    change: &BlobChange,
+    old: &[u8],
+    new: &[u8],
+    kinds: &[BlobValueKind],
+) -> Vec<BlobCandidate> {
+    let span = change
+        .old_bytes
+        .len()
+        .max(change.new_bytes.len())
+        .clamp(1, MAX_CANDIDATE_SPAN);
+    let mut candidates = vec![];
+
+    for kind in kinds {
+        let size = kind.size();
+        let alignment = kind.alignment();
+
+        // Aligned (in the old buffer) values overlapping the first bytes of the change
+        let first = (change.old_offset + 1).saturating_sub(size) / alignment * alignment;
+        let mut old_offset = first;
+        while old_offset < change.old_offset + span {
+            let new_offset = (old_offset + change.new_offset).checked_sub(change.old_offset);
+            if let Some(new_offset) = new_offset
+                && let Some(old_value) = BlobValue::decode(*kind, old, old_offset)
+                && let Some(new_value) = BlobValue::decode(*kind, new, new_offset)
+                && old_value != new_value
+                && (old_value.is_plausible() || new_value.is_plausible())
+            {
+                candidates.push(BlobCandidate {
+                    old_offset,
+                    new_offset,
+                    old: old_value,
+                    new: new_value,
+                });
+            }
+            old_offset += alignment;
+        }
+    }
+
+    candidates
+}
+
+fn hex(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "-".to_string();
+    }
+    bytes
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(
+        old_offset: usize,
+        old_bytes: &[u8],
+        new_offset: usize,
+        new_bytes: &[u8],
+    ) -> BlobChange {
+        BlobChange {
+            old_offset,
+            old_bytes: old_bytes.to_vec(),
+            new_offset,
+            new_bytes: new_bytes.to_vec(),
+        }
+    }
+
+    /// Deterministic bytes without long runs of equal values.
+    fn noise(seed: u32, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_buffers_have_no_changes() {
+        let bytes = noise(1, 64);
+        assert!(diff_blobs(&bytes, &bytes).is_empty());
+        assert!(diff_blobs(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn insertions_and_deletions_are_aligned() {
+        let old: Vec<u8> = (0..16).collect();
+        let mut new = old.clone();
+        new.splice(8..8, [0xAA, 0xBB]);
+
+        let diff = diff_blobs(&old, &new);
+        assert!(!diff.positional);
+        assert_eq!(diff.changes, [change(8, &[], 8, &[0xAA, 0xBB])]);
+        assert_eq!(diff.to_string(), "0x0008+0 -> 0x0008+2: - -> AA BB\n");
+
+        let diff = diff_blobs(&new, &old);
+        assert_eq!(diff.changes, [change(8, &[0xAA, 0xBB], 8, &[])]);
+    }
+
+    #[test]
+    fn replacements_keep_offsets() {
+        let old: Vec<u8> = (0..16).collect();
+        let mut new = old.clone();
+        new[5] = 0xFF;
+        new.push(0x10);
+
+        let diff = diff_blobs(&old, &new);
+        assert_eq!(
+            diff.changes,
+            [change(5, &[5], 5, &[0xFF]), change(16, &[], 16, &[0x10])]
+        );
+    }
+
+    #[test]
+    fn nearby_changes_are_merged() {
+        let old: Vec<u8> = (0..32).collect();
+        let mut new = old.clone();
+        new[4] = 0xF4;
+        new[6] = 0xF6;
+        new[20] = 0xF0;
+
+        let diff = diff_blobs(&old, &new);
+        assert_eq!(
+            diff.changes,
+            [
+                change(4, &[4, 5, 6], 4, &[0xF4, 5, 0xF6]),
+                change(20, &[20], 20, &[0xF0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unrelated_buffers_are_compared_by_position() {
+        let old = noise(1, 4096);
+        let mut new = noise(2, 4000);
+        // Shared bytes stay unchanged, at the same offsets
+        new[100..200].copy_from_slice(&old[100..200]);
+
+        let diff = diff_blobs(&old, &new);
+        assert!(diff.positional);
+        assert!(diff.changes.iter().all(|c| c.old_offset == c.new_offset));
+        assert!(
+            diff.changes
+                .iter()
+                .all(|c| c.old_offset + c.old_bytes.len() <= 100 || c.old_offset >= 200)
+        );
+        let last = diff
+            .changes
+            .last()
+            .map(|c| (c.old_bytes.len(), c.new_bytes.len()));
+        assert!(last.is_some_and(|(old_len, new_len)| old_len == new_len + 96));
+
+        // Below the edit limit, the same kind of change is aligned
+        let diff = diff_blobs(&old[..300], &new[..300]);
+        assert!(!diff.positional);
+    }
+
+    #[test]
+    fn changed_values_are_decoded() {
+        let mut old = vec![0; 24];
+        old[8..12].copy_from_slice(&1.5f32.to_le_bytes());
+        let mut new = old.clone();
+        new[8..12].copy_from_slice(&2.5f32.to_le_bytes());
+
+        let diff = diff_blobs(&old, &new);
+        assert_eq!(diff.changes, [change(10, &[0xC0, 0x3F], 10, &[0x20, 0x40])]);
+
+        let candidates = diff.candidates(&old, &new, &[BlobValueKind::F32, BlobValueKind::I16]);
+        assert_eq!(candidates.len(), 1);
+        let (change_idx, candidates) = &candidates[0];
+        assert_eq!(*change_idx, 0);
+        let lines: Vec<String> = candidates.iter().map(BlobCandidate::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "0x0008 -> 0x0008 (f32): 1.5 -> 2.5",
+                "0x000A -> 0x000A (i16): 16320 -> 16416",
+            ]
+        );
+
+        // Implausible floats on both sides are left out
+        let old = [0xFF; 4];
+        let new = [0xFE, 0xFF, 0xFF, 0xFF];
+        let diff = diff_blobs(&old, &new);
+        assert!(
+            diff.candidates(&old, &new, &[BlobValueKind::F32])
+                .is_empty()
+        );
+        assert_eq!(diff.candidates(&old, &new, &[BlobValueKind::I32]).len(), 1);
+    }
+}
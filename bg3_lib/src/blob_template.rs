@@ -0,0 +1,1268 @@
+//! Declarative layouts of binary blobs, in a small language close to 010 Editor templates:
+//!
+//! ```text
+//! // Applies to the NewAge blobs of every entity
+//! match "Globals/*/Entity" "NewAge";
+//!
+//! struct Entry {
+//!     u32 id;
+//!     f32 position[3];
+//!     if (id > 0x100) {
+//!         cstring name;
+//!     }
+//! }
+//!
+//! u16 version;
+//! u32 count;
+//! Entry entries[count];
+//! if (version >= 2 && count != 0) {
+//!     char tag[8];
+//! } else {
+//!     bytes padding[_remaining];
+//! }
+//! bytes trailer[];
+//! ```
+//!
+//! Fields are read in order and make up a struct value. Their type is one of `u8`, `i8`,
+//! `u16`, `i16`, `u32`, `i32`, `u64`, `i64`, `f32`, `f64`, `bool`, `uuid`, `cstring`
+//! (null-terminated), `char` and `bytes` (which need a length), or the name of a struct.
+//! A field followed by `[count]` is an array, and by `[]` an array reaching the end of the blob.
+//!
+//! Expressions refer to fields read before, in the current struct or the ones enclosing it,
+//! with `.` to reach into structs. `_offset` is the position in the blob and `_remaining`
+//! the number of bytes left. Values are integers; floats are truncated and booleans are 0 or 1.
+//!
+//! When writing, an array counted by `_remaining` alone takes its length from the value.
+//! `_remaining` is unknown in other expressions, so templates using it there are read-only.
+//!
+//! References between fields are limited to these expressions: there are no offset or pointer
+//! fields, and fields are always read where the previous one ends.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::bin_utils::{ReadExt, WriteExt};
+use crate::blob_decoder::{BlobDecoder, DecodedValue};
+use crate::node_path::NodePath;
+
+/// Nesting of structs beyond which a template is considered to recurse forever.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+    Bool,
+    Uuid,
+    CString,
+    Char,
+    Bytes,
+}
+
+impl FieldType {
+    fn from_name(name: &str) -> Option<Self> {
+        let ty = match name {
+            "u8" => Self::U8,
+            "i8" => Self::I8,
+            "u16" => Self::U16,
+            "i16" => Self::I16,
+            "u32" => Self::U32,
+            "i32" => Self::I32,
+            "u64" => Self::U64,
+            "i64" => Self::I64,
+            "f32" => Self::F32,
+            "f64" => Self::F64,
+            "bool" => Self::Bool,
+            "uuid" => Self::Uuid,
+            "cstring" => Self::CString,
+            "char" => Self::Char,
+            "bytes" => Self::Bytes,
+            _ => return None,
+        };
+        Some(ty)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TypeRef {
+    Builtin(FieldType),
+    Struct(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    /// Operator and its precedence, higher binding tighter.
+    fn from_token(token: &str) -> Option<(Self, u8)> {
+        let op = match token {
+            "||" => (Self::Or, 1),
+            "&&" => (Self::And, 2),
+            "|" => (Self::BitOr, 3),
+            "^" => (Self::BitXor, 4),
+            "&" => (Self::BitAnd, 5),
+            "==" => (Self::Eq, 6),
+            "!=" => (Self::Ne, 6),
+            "<" => (Self::Lt, 7),
+            "<=" => (Self::Le, 7),
+            ">" => (Self::Gt, 7),
+            ">=" => (Self::Ge, 7),
+            "<<" => (Self::Shl, 8),
+            ">>" => (Self::Shr, 8),
+            "+" => (Self::Add, 9),
+            "-" => (Self::Sub, 9),
+            "*" => (Self::Mul, 10),
+            "/" => (Self::Div, 10),
+            "%" => (Self::Rem, 10),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn apply(self, a: i64, b: i64) -> Result<i64, String> {
+        let value = match self {
+            Self::Or => ((a != 0) || (b != 0)) as i64,
+            Self::And => ((a != 0) && (b != 0)) as i64,
+            Self::BitOr => a | b,
+            Self::BitXor => a ^ b,
+            Self::BitAnd => a & b,
+            Self::Eq => (a == b) as i64,
+            Self::Ne => (a != b) as i64,
+            Self::Lt => (a < b) as i64,
+            Self::Le => (a <= b) as i64,
+            Self::Gt => (a > b) as i64,
+            Self::Ge => (a >= b) as i64,
+            Self::Shl => a.checked_shl(b as u32).ok_or("shift overflow")?,
+            Self::Shr => a.checked_shr(b as u32).ok_or("shift overflow")?,
+            Self::Add => a.checked_add(b).ok_or("addition overflow")?,
+            Self::Sub => a.checked_sub(b).ok_or("subtraction overflow")?,
+            Self::Mul => a.checked_mul(b).ok_or("multiplication overflow")?,
+            Self::Div => a.checked_div(b).ok_or("division by zero")?,
+            Self::Rem => a.checked_rem(b).ok_or("division by zero")?,
+        };
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Int(i64),
+    /// Field read before, possibly inside structs: `header.count`.
+    Field(Vec<String>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Whether the expression is `_remaining` alone, as in `bytes padding[_remaining]`.
+    fn is_remaining(&self) -> bool {
+        matches!(self, Self::Field(path) if path == &["_remaining"])
+    }
+
+    fn uses_remaining(&self) -> bool {
+        match self {
+            Self::Int(_) => false,
+            Self::Field(path) => path.first().is_some_and(|name| name == "_remaining"),
+            Self::Neg(e) | Self::Not(e) => e.uses_remaining(),
+            Self::Binary(_, a, b) => a.uses_remaining() || b.uses_remaining(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Count {
+    One,
+    Fixed(Expr),
+    ToEnd,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Statement {
+    Field {
+        ty: TypeRef,
+        name: String,
+        count: Count,
+    },
+    If {
+        condition: Expr,
+        then: Vec<Statement>,
+        otherwise: Vec<Statement>,
+    },
+}
+
+/// Layout of a blob, parsed from the template language described in the module docs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlobTemplate {
+    name: String,
+    /// Node paths and attribute names the template applies to, from `match` directives.
+    targets: Vec<(NodePath, String)>,
+    structs: HashMap<String, Vec<Statement>>,
+    root: Vec<Statement>,
+}
+
+impl BlobTemplate {
+    pub fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source).map_err(|e| format!("template {name}: {e}"))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let mut template = parser
+            .parse_template()
+            .map_err(|e| format!("template {name}: {e}"))?;
+        template.name = name.to_string();
+        template.check_structs()?;
+        Ok(template)
+    }
+
+    /// Reads a template file, named after the file.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed reading {}: {e}", path.to_string_lossy()))?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Self::parse(&name, &source)
+    }
+
+    /// Reads every `.bt` file of a directory.
+    pub fn load_dir(dir: &Path) -> Result<Vec<Self>, String> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("could not list {}: {e}", dir.to_string_lossy()))?;
+        let mut paths = vec![];
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("could not list {}: {e}", dir.to_string_lossy()))?
+                .path();
+            if path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("bt"))
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths.iter().map(|path| Self::from_file(path)).collect()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Node path patterns and attribute names given by the `match` directives of the template.
+    pub fn targets(&self) -> &[(NodePath, String)] {
+        &self.targets
+    }
+
+    /// Parses a blob into a struct of the fields of the template.
+    pub fn read(&self, bytes: &[u8]) -> Result<DecodedValue, String> {
+        let mut reader = TemplateReader {
+            template: self,
+            stream: Cursor::new(bytes),
+            len: bytes.len(),
+        };
+        let fields = reader.read_struct(&self.root, &[], 0)?;
+        Ok(DecodedValue::Struct(fields))
+    }
+
+    /// Whether `write` is supported: `_remaining` is only known when reading, except as the
+    /// whole count of an array.
+    pub fn can_write(&self) -> bool {
+        let mut stack: Vec<&[Statement]> = vec![&self.root];
+        stack.extend(self.structs.values().map(Vec::as_slice));
+        while let Some(statements) = stack.pop() {
+            for statement in statements {
+                match statement {
+                    Statement::Field {
+                        count: Count::Fixed(expr),
+                        ..
+                    } if !expr.is_remaining() && expr.uses_remaining() => return false,
+                    Statement::Field { .. } => {}
+                    Statement::If {
+                        condition,
+                        then,
+                        otherwise,
+                    } => {
+                        if condition.uses_remaining() {
+                            return false;
+                        }
+                        stack.push(then);
+                        stack.push(otherwise);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Writes a struct value back into a blob. Array lengths must agree with the fields
+    /// giving them, and conditions are evaluated on the values being written.
+    pub fn write(&self, value: &DecodedValue) -> Result<Vec<u8>, String> {
+        if !self.can_write() {
+            return Err(format!(
+                "template {} uses _remaining, which is only known when reading",
+                self.name
+            ));
+        }
+        let DecodedValue::Struct(fields) = value else {
+            return Err(format!(
+                "template {} can only write struct values",
+                self.name
+            ));
+        };
+        let mut writer = TemplateWriter {
+            template: self,
+            bytes: vec![],
+        };
+        writer.write_struct(&self.root, fields, &[], 0)?;
+        Ok(writer.bytes)
+    }
+
+    /// Checks that every struct used is defined.
+    fn check_structs(&self) -> Result<(), String> {
+        let mut stack: Vec<&[Statement]> = vec![&self.root];
+        stack.extend(self.structs.values().map(Vec::as_slice));
+        while let Some(statements) = stack.pop() {
+            for statement in statements {
+                match statement {
+                    Statement::Field {
+                        ty: TypeRef::Struct(name),
+                        ..
+                    } if !self.structs.contains_key(name) => {
+                        return Err(format!("template {}: unknown type {name}", self.name));
+                    }
+                    Statement::Field { .. } => {}
+                    Statement::If {
+                        then, otherwise, ..
+                    } => {
+                        stack.push(then);
+                        stack.push(otherwise);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn struct_statements(&self, name: &str) -> Result<&[Statement], String> {
+        self.structs
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| format!("unknown type {name}"))
+    }
+}
+
+impl BlobDecoder for BlobTemplate {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DecodedValue, String> {
+        self.read(bytes)
+    }
+
+    fn can_encode(&self) -> bool {
+        self.can_write()
+    }
+
+    fn encode(&self, value: &DecodedValue) -> Result<Vec<u8>, String> {
+        self.write(value)
+    }
+}
+
+/// Fields visible to expressions, from the outermost struct to the current one.
+type Scope<'a> = [&'a [(String, DecodedValue)]];
+
+/// Value of an expression, `offset` and `remaining` giving `_offset` and `_remaining`.
+fn eval(
+    expr: &Expr,
+    scope: &Scope,
+    offset: usize,
+    remaining: Option<usize>,
+) -> Result<i64, String> {
+    match expr {
+        Expr::Int(v) => Ok(*v),
+        Expr::Neg(e) => eval(e, scope, offset, remaining)?
+            .checked_neg()
+            .ok_or_else(|| "negation overflow".to_string()),
+        Expr::Not(e) => Ok((eval(e, scope, offset, remaining)? == 0) as i64),
+        Expr::Binary(op, a, b) => {
+            let a = eval(a, scope, offset, remaining)?;
+            // Short-circuit, so that `count > 0 && data.first` does not need `data`
+            match (op, a != 0) {
+                (BinaryOp::And, false) => return Ok(0),
+                (BinaryOp::Or, true) => return Ok(1),
+                _ => {}
+            }
+            op.apply(a, eval(b, scope, offset, remaining)?)
+        }
+        Expr::Field(path) => {
+            let (first, rest) = path.split_first().ok_or("empty field reference")?;
+            match first.as_str() {
+                "_offset" => return Ok(offset as i64),
+                "_remaining" => {
+                    return remaining
+                        .map(|remaining| remaining as i64)
+                        .ok_or_else(|| "_remaining is only known when reading".to_string());
+                }
+                _ => {}
+            }
+
+            let mut value = scope
+                .iter()
+                .rev()
+                .find_map(|fields| fields.iter().rev().find(|(name, _)| name == first))
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("unknown field {first}"))?;
+            for name in rest {
+                value = value
+                    .field(name)
+                    .ok_or_else(|| format!("unknown field {name} in {}", path.join(".")))?;
+            }
+
+            match value {
+                DecodedValue::Int(v) => Ok(*v),
+                DecodedValue::UInt(v) => {
+                    i64::try_from(*v).map_err(|_| format!("{} is too large", path.join(".")))
+                }
+                DecodedValue::Bool(v) => Ok(*v as i64),
+                DecodedValue::Float(v) => Ok(*v as i64),
+                _ => Err(format!("{} is not a number", path.join("."))),
+            }
+        }
+    }
+}
+
+struct TemplateReader<'a> {
+    template: &'a BlobTemplate,
+    stream: Cursor<&'a [u8]>,
+    len: usize,
+}
+
+impl TemplateReader<'_> {
+    fn offset(&self) -> usize {
+        self.stream.position() as usize
+    }
+
+    fn read_struct(
+        &mut self,
+        statements: &[Statement],
+        parents: &Scope,
+        depth: usize,
+    ) -> Result<Vec<(String, DecodedValue)>, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("structs nested more than {MAX_DEPTH} deep"));
+        }
+        let mut fields = vec![];
+        self.read_statements(statements, parents, &mut fields, depth)?;
+        Ok(fields)
+    }
+
+    fn read_statements(
+        &mut self,
+        statements: &[Statement],
+        parents: &Scope,
+        fields: &mut Vec<(String, DecodedValue)>,
+        depth: usize,
+    ) -> Result<(), String> {
+        for statement in statements {
+            match statement {
+                Statement::Field { ty, name, count } => {
+                    let offset = self.offset();
+                    let value = self
+                        .read_field(ty, count, parents, fields, depth)
+                        .map_err(|e| format!("{name} at {offset:#X}: {e}"))?;
+                    fields.push((name.clone(), value));
+                }
+                Statement::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let scope = [parents, &[fields.as_slice()]].concat();
+                    let branch =
+                        if eval(condition, &scope, self.offset(), Some(self.remaining()))? != 0 {
+                            then
+                        } else {
+                            otherwise
+                        };
+                    self.read_statements(branch, parents, fields, depth)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn remaining(&self) -> usize {
+        self.len.saturating_sub(self.offset())
+    }
+
+    fn read_field(
+        &mut self,
+        ty: &TypeRef,
+        count: &Count,
+        parents: &Scope,
+        fields: &[(String, DecodedValue)],
+        depth: usize,
+    ) -> Result<DecodedValue, String> {
+        let scope = [parents, &[fields]].concat();
+        let count = match count {
+            Count::One => None,
+            Count::Fixed(expr) => {
+                let count = eval(expr, &scope, self.offset(), Some(self.remaining()))?;
+                Some(usize::try_from(count).map_err(|_| format!("invalid count {count}"))?)
+            }
+            Count::ToEnd => match ty {
+                TypeRef::Builtin(FieldType::Char | FieldType::Bytes) => Some(self.remaining()),
+                _ => {
+                    let mut values = vec![];
+                    while self.remaining() > 0 {
+                        let start = self.offset();
+                        values.push(self.read_value(ty, &scope, depth)?);
+                        // An element reading nothing would repeat forever
+                        if self.offset() == start {
+                            return Err(format!(
+                                "element {} of the array reads no bytes",
+                                values.len() - 1
+                            ));
+                        }
+                    }
+                    return Ok(DecodedValue::List(values));
+                }
+            },
+        };
+
+        match (ty, count) {
+            (TypeRef::Builtin(FieldType::Char), Some(len)) => {
+                let bytes = self.read_bytes(len)?;
+                let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                String::from_utf8(bytes[..end].to_vec())
+                    .map(DecodedValue::String)
+                    .map_err(|e| format!("invalid UTF-8 string: {e}"))
+            }
+            (TypeRef::Builtin(FieldType::Bytes), Some(len)) => {
+                Ok(DecodedValue::Bytes(self.read_bytes(len)?))
+            }
+            (TypeRef::Builtin(FieldType::Char | FieldType::Bytes), None) => {
+                Err("char and bytes fields need a length".to_string())
+            }
+            (_, Some(count)) => {
+                if count > self.remaining() {
+                    return Err(format!("count {count} exceeds the remaining bytes"));
+                }
+                (0..count)
+                    .map(|_| self.read_value(ty, &scope, depth))
+                    .collect::<Result<_, _>>()
+                    .map(DecodedValue::List)
+            }
+            (_, None) => self.read_value(ty, &scope, depth),
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, String> {
+        if len > self.remaining() {
+            return Err(format!(
+                "{len} bytes requested, only {} remaining",
+                self.remaining()
+            ));
+        }
+        let start = self.offset();
+        self.stream.set_position((start + len) as u64);
+        Ok(self.stream.get_ref()[start..start + len].to_vec())
+    }
+
+    fn read_value(
+        &mut self,
+        ty: &TypeRef,
+        scope: &Scope,
+        depth: usize,
+    ) -> Result<DecodedValue, String> {
+        let stream = &mut self.stream;
+        let value = match ty {
+            TypeRef::Struct(name) => {
+                let statements = self.template.struct_statements(name)?;
+                return self
+                    .read_struct(statements, scope, depth + 1)
+                    .map(DecodedValue::Struct);
+            }
+            TypeRef::Builtin(ty) => match ty {
+                FieldType::U8 => DecodedValue::UInt(stream.read_u8()?.into()),
+                FieldType::I8 => DecodedValue::Int(stream.read_i8()?.into()),
+                FieldType::U16 => DecodedValue::UInt(stream.read_u16()?.into()),
+                FieldType::I16 => DecodedValue::Int(stream.read_i16()?.into()),
+                FieldType::U32 => DecodedValue::UInt(stream.read_u32()?.into()),
+                FieldType::I32 => DecodedValue::Int(stream.read_i32()?.into()),
+                FieldType::U64 => DecodedValue::UInt(stream.read_u64()?),
+                FieldType::I64 => DecodedValue::Int(stream.read_i64()?),
+                FieldType::F32 => DecodedValue::Float(stream.read_f32()?.into()),
+                FieldType::F64 => DecodedValue::Float(stream.read_f64()?),
+                FieldType::Bool => DecodedValue::Bool(stream.read_u8()? != 0),
+                FieldType::Uuid => DecodedValue::Uuid(stream.read_uuid()?),
+                FieldType::CString => {
+                    let mut bytes = vec![];
+                    loop {
+                        match stream.read_u8()? {
+                            0 => break,
+                            b => bytes.push(b),
+                        }
+                    }
+                    DecodedValue::String(
+                        String::from_utf8(bytes)
+                            .map_err(|e| format!("invalid UTF-8 string: {e}"))?,
+                    )
+                }
+                FieldType::Char | FieldType::Bytes => {
+                    return Err("char and bytes fields need a length".to_string());
+                }
+            },
+        };
+        Ok(value)
+    }
+}
+
+struct TemplateWriter<'a> {
+    template: &'a BlobTemplate,
+    bytes: Vec<u8>,
+}
+
+impl TemplateWriter<'_> {
+    /// Writes `fields` in the order of the statements, returning how many were written.
+    fn write_struct(
+        &mut self,
+        statements: &[Statement],
+        fields: &[(String, DecodedValue)],
+        parents: &Scope,
+        depth: usize,
+    ) -> Result<usize, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("structs nested more than {MAX_DEPTH} deep"));
+        }
+        let mut written = 0;
+        for statement in statements {
+            match statement {
+                Statement::Field { ty, name, count } => {
+                    let (field_name, value) = fields
+                        .get(written)
+                        .ok_or_else(|| format!("missing field {name}"))?;
+                    if field_name != name {
+                        return Err(format!("expected field {name}, found {field_name}"));
+                    }
+                    let scope = [parents, &[&fields[..written]]].concat();
+                    let offset = self.bytes.len();
+                    self.write_field(ty, count, value, &scope, depth)
+                        .map_err(|e| format!("{name} at {offset:#X}: {e}"))?;
+                    written += 1;
+                }
+                Statement::If {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let scope = [parents, &[&fields[..written]]].concat();
+                    let branch = if eval(condition, &scope, self.bytes.len(), None)? != 0 {
+                        then
+                    } else {
+                        otherwise
+                    };
+                    written += self.write_struct(branch, &fields[written..], &scope, depth)?;
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    fn write_field(
+        &mut self,
+        ty: &TypeRef,
+        count: &Count,
+        value: &DecodedValue,
+        scope: &Scope,
+        depth: usize,
+    ) -> Result<(), String> {
+        let count = match count {
+            Count::One => None,
+            // The array ends the blob, and is as long as the value
+            Count::Fixed(expr) if expr.is_remaining() => None,
+            Count::Fixed(expr) => {
+                let count = eval(expr, scope, self.bytes.len(), None)?;
+                Some(usize::try_from(count).map_err(|_| format!("invalid count {count}"))?)
+            }
+            Count::ToEnd => None,
+        };
+
+        match (ty, value) {
+            (TypeRef::Builtin(FieldType::Char), DecodedValue::String(s)) => {
+                let bytes = s.as_bytes();
+                let len = count.unwrap_or(bytes.len());
+                if bytes.len() > len {
+                    return Err(format!("string is longer than {len} bytes"));
+                }
+                self.bytes.extend_from_slice(bytes);
+                self.bytes.resize(self.bytes.len() + len - bytes.len(), 0);
+                Ok(())
+            }
+            (TypeRef::Builtin(FieldType::Bytes), DecodedValue::Bytes(bytes)) => {
+                if count.is_some_and(|count| count != bytes.len()) {
+                    return Err(format!(
+                        "expected {} bytes, found {}",
+                        count.unwrap_or_default(),
+                        bytes.len()
+                    ));
+                }
+                self.bytes.extend_from_slice(bytes);
+                Ok(())
+            }
+            (TypeRef::Builtin(FieldType::Char | FieldType::Bytes), _) => {
+                Err("expected a string or bytes value".to_string())
+            }
+            (_, DecodedValue::List(values)) => {
+                if count.is_some_and(|count| count != values.len()) {
+                    return Err(format!(
+                        "expected {} elements, found {}",
+                        count.unwrap_or_default(),
+                        values.len()
+                    ));
+                }
+                for value in values {
+                    self.write_value(ty, value, scope, depth)?;
+                }
+                Ok(())
+            }
+            (_, _) if count.is_some() => Err("expected a list value".to_string()),
+            (_, _) => self.write_value(ty, value, scope, depth),
+        }
+    }
+
+    fn write_value(
+        &mut self,
+        ty: &TypeRef,
+        value: &DecodedValue,
+        scope: &Scope,
+        depth: usize,
+    ) -> Result<(), String> {
+        let ty = match ty {
+            TypeRef::Struct(name) => {
+                let DecodedValue::Struct(fields) = value else {
+                    return Err(format!("expected a {name} struct value"));
+                };
+                let statements = self.template.struct_statements(name)?;
+                let written = self.write_struct(statements, fields, scope, depth + 1)?;
+                if written != fields.len() {
+                    return Err(format!("unexpected field {}", fields[written].0));
+                }
+                return Ok(());
+            }
+            TypeRef::Builtin(ty) => *ty,
+        };
+
+        let int = || match value {
+            DecodedValue::Int(v) => Ok(*v as i128),
+            DecodedValue::UInt(v) => Ok(*v as i128),
+            DecodedValue::Bool(v) => Ok(*v as i128),
+            _ => Err(format!("expected an integer, found {value}")),
+        };
+        let out_of_range = |_| format!("{value} is out of range");
+        let float = || match value {
+            DecodedValue::Float(v) => Ok(*v),
+            DecodedValue::Int(v) => Ok(*v as f64),
+            DecodedValue::UInt(v) => Ok(*v as f64),
+            _ => Err(format!("expected a float, found {value}")),
+        };
+
+        let bytes = &mut self.bytes;
+        match ty {
+            FieldType::U8 => bytes.write_u8(u8::try_from(int()?).map_err(out_of_range)?),
+            FieldType::I8 => bytes.write_i8(i8::try_from(int()?).map_err(out_of_range)?),
+            FieldType::U16 => bytes.write_u16(u16::try_from(int()?).map_err(out_of_range)?),
+            FieldType::I16 => bytes.write_i16(i16::try_from(int()?).map_err(out_of_range)?),
+            FieldType::U32 => bytes.write_u32(u32::try_from(int()?).map_err(out_of_range)?),
+            FieldType::I32 => bytes.write_i32(i32::try_from(int()?).map_err(out_of_range)?),
+            FieldType::U64 => bytes.write_u64(u64::try_from(int()?).map_err(out_of_range)?),
+            FieldType::I64 => bytes.write_i64(i64::try_from(int()?).map_err(out_of_range)?),
+            FieldType::F32 => bytes.write_f32(float()? as f32),
+            FieldType::F64 => bytes.write_f64(float()?),
+            FieldType::Bool => bytes.write_u8((int()? != 0) as u8),
+            FieldType::Uuid => match value {
+                DecodedValue::Uuid(uuid) => {
+                    bytes.extend_from_slice(uuid.as_bytes());
+                    Ok(())
+                }
+                _ => Err(format!("expected a UUID, found {value}")),
+            },
+            FieldType::CString => match value {
+                DecodedValue::String(s) if !s.as_bytes().contains(&0) => {
+                    bytes.extend_from_slice(s.as_bytes());
+                    bytes.write_u8(0)
+                }
+                _ => Err(format!(
+                    "expected a string without null bytes, found {value}"
+                )),
+            },
+            FieldType::Char | FieldType::Bytes => {
+                Err("char and bytes fields need a length".to_string())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Str(String),
+    Punct(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ident(s) => write!(f, "'{s}'"),
+            Self::Int(v) => write!(f, "{v}"),
+            Self::Str(s) => write!(f, "\"{s}\""),
+            Self::Punct(p) => write!(f, "'{p}'"),
+        }
+    }
+}
+
+/// Longest ones first, so that `<=` is not read as `<` then `=`.
+const PUNCTUATION: [&str; 27] = [
+    "==", "!=", "<=", ">=", "<<", ">>", "&&", "||", "{", "}", "[", "]", "(", ")", ";", ".", "<",
+    ">", "+", "-", "*", "/", "%", "&", "|", "^", "!",
+];
+
+/// Tokens of the source, with the line each one starts on.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+    let mut tokens = vec![];
+    for (line_idx, line) in source.lines().enumerate() {
+        let line_number = line_idx + 1;
+        let mut rest = line;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with("//") {
+                break;
+            }
+
+            let (token, len) = if let Some(s) = rest.strip_prefix('"') {
+                let end = s
+                    .find('"')
+                    .ok_or_else(|| format!("line {line_number}: unterminated string"))?;
+                (Token::Str(s[..end].to_string()), end + 2)
+            } else if rest.starts_with(|c: char| c.is_ascii_digit()) {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                let literal = &rest[..len];
+                let value = match literal
+                    .strip_prefix("0x")
+                    .or_else(|| literal.strip_prefix("0X"))
+                {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => literal.parse(),
+                }
+                .map_err(|e| format!("line {line_number}: invalid number {literal}: {e}"))?;
+                (Token::Int(value), len)
+            } else if rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (Token::Ident(rest[..len].to_string()), len)
+            } else if let Some(punct) = PUNCTUATION.iter().find(|p| rest.starts_with(**p)) {
+                (Token::Punct(punct), punct.len())
+            } else {
+                return Err(format!(
+                    "line {line_number}: unexpected character '{}'",
+                    rest.chars().next().unwrap_or_default()
+                ));
+            };
+
+            tokens.push((token, line_number));
+            rest = &rest[len..];
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn error(&self, message: &str) -> String {
+        match self.tokens.get(self.pos) {
+            Some((token, line)) => format!("line {line}: {message}, found {token}"),
+            None => format!("{message}, found the end of the template"),
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{punct}'")))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("expected a name")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Str(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => Err(self.error("expected a string")),
+        }
+    }
+
+    fn parse_template(&mut self) -> Result<BlobTemplate, String> {
+        let mut template = BlobTemplate {
+            name: String::new(),
+            targets: vec![],
+            structs: HashMap::new(),
+            root: vec![],
+        };
+
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Ident(keyword) if keyword == "match" => {
+                    self.pos += 1;
+                    let path = self.string()?;
+                    let path = path.parse::<NodePath>().map_err(|e| self.error(&e))?;
+                    let attribute = self.string()?;
+                    self.expect(";")?;
+                    template.targets.push((path, attribute));
+                }
+                Token::Ident(keyword) if keyword == "struct" => {
+                    self.pos += 1;
+                    let name = self.ident()?;
+                    if FieldType::from_name(&name).is_some() || template.structs.contains_key(&name)
+                    {
+                        return Err(self.error(&format!("{name} is already a type")));
+                    }
+                    let body = self.parse_block()?;
+                    template.structs.insert(name, body);
+                }
+                _ => template.root.push(self.parse_statement()?),
+            }
+        }
+        Ok(template)
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<Statement>, String> {
+        self.expect("{")?;
+        let mut statements = vec![];
+        while !self.eat("}") {
+            if self.peek().is_none() {
+                return Err(self.error("expected '}'"));
+            }
+            statements.push(self.parse_statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, String> {
+        let ty_name = self.ident()?;
+        if ty_name == "if" {
+            self.expect("(")?;
+            let condition = self.parse_expr(0)?;
+            self.expect(")")?;
+            let then = self.parse_block()?;
+            let otherwise = if self.peek() == Some(&Token::Ident("else".to_string())) {
+                self.pos += 1;
+                if self.peek() == Some(&Token::Ident("if".to_string())) {
+                    vec![self.parse_statement()?]
+                } else {
+                    self.parse_block()?
+                }
+            } else {
+                vec![]
+            };
+            return Ok(Statement::If {
+                condition,
+                then,
+                otherwise,
+            });
+        }
+
+        let ty = match FieldType::from_name(&ty_name) {
+            Some(ty) => TypeRef::Builtin(ty),
+            None => TypeRef::Struct(ty_name),
+        };
+        let name = self.ident()?;
+        let count = if self.eat("[") {
+            if self.eat("]") {
+                Count::ToEnd
+            } else {
+                let count = self.parse_expr(0)?;
+                self.expect("]")?;
+                Count::Fixed(count)
+            }
+        } else {
+            Count::One
+        };
+        if matches!(
+            (&ty, &count),
+            (
+                TypeRef::Builtin(FieldType::Char | FieldType::Bytes),
+                Count::One
+            )
+        ) {
+            return Err(self.error(&format!("{name} needs a length")));
+        }
+        self.expect(";")?;
+
+        Ok(Statement::Field { ty, name, count })
+    }
+
+    /// Parses operators binding tighter than `min_precedence`.
+    fn parse_expr(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(Token::Punct(punct)) = self.peek() {
+            let Some((op, precedence)) = BinaryOp::from_token(punct) else {
+                break;
+            };
+            if precedence <= min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.parse_expr(precedence)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.parse_unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.parse_expr(0)?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+
+        match self.next() {
+            Some(Token::Int(v)) => Ok(Expr::Int(v)),
+            Some(Token::Ident(name)) => {
+                let mut path = vec![name];
+                while self.eat(".") {
+                    path.push(self.ident()?);
+                }
+                Ok(Expr::Field(path))
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected a value"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Template of the module docs.
+    const DOC_EXAMPLE: &str = r#"
+        // Applies to the NewAge blobs of every entity
+        match "Globals/*/Entity" "NewAge";
+
+        struct Entry {
+            u32 id;
+            f32 position[3];
+            if (id > 0x100) {
+                cstring name;
+            }
+        }
+
+        u16 version;
+        u32 count;
+        Entry entries[count];
+        if (version >= 2 && count != 0) {
+            char tag[8];
+        } else {
+            bytes padding[_remaining];
+        }
+        bytes trailer[];
+    "#;
+
+    fn floats(values: [f32; 3]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// Blob of the module docs example, with two entries, a tag and a trailer.
+    fn tagged_blob() -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend(2u16.to_le_bytes());
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(5u32.to_le_bytes());
+        bytes.extend(floats([1.0, 2.0, 3.0]));
+        bytes.extend(0x200u32.to_le_bytes());
+        bytes.extend(floats([-1.5, 0.0, 8.0]));
+        bytes.extend(b"Astarion\0");
+        bytes.extend(b"Tag\0\0\0\0\0");
+        bytes.extend([9, 9]);
+        bytes
+    }
+
+    #[test]
+    fn doc_example_round_trips() -> Result<(), String> {
+        let template = BlobTemplate::parse("NewAge", DOC_EXAMPLE)?;
+        assert!(template.can_encode());
+        assert_eq!(template.targets().len(), 1);
+
+        let bytes = tagged_blob();
+        let value = template.read(&bytes)?;
+        let entries = match value.field("entries") {
+            Some(DecodedValue::List(entries)) => entries,
+            _ => return Err("entries is not a list".into()),
+        };
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].field("name"), None);
+        assert_eq!(
+            entries[1].field("name").and_then(DecodedValue::as_str),
+            Some("Astarion")
+        );
+        assert_eq!(
+            value.field("tag").and_then(DecodedValue::as_str),
+            Some("Tag")
+        );
+        assert_eq!(value.field("padding"), None);
+        assert_eq!(
+            value.field("trailer"),
+            Some(&DecodedValue::Bytes(vec![9, 9]))
+        );
+        assert_eq!(template.write(&value)?, bytes);
+
+        // The other branch reads the rest of the blob into the padding, which is written
+        // back with the length of its value
+        let mut bytes = vec![];
+        bytes.extend(1u16.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend([1, 2, 3]);
+        let mut value = template.read(&bytes)?;
+        assert_eq!(
+            value.field("padding"),
+            Some(&DecodedValue::Bytes(vec![1, 2, 3]))
+        );
+        assert_eq!(value.field("trailer"), Some(&DecodedValue::Bytes(vec![])));
+        assert_eq!(template.write(&value)?, bytes);
+
+        if let Some(padding) = value.field_mut("padding") {
+            *padding = DecodedValue::Bytes(vec![4; 5]);
+        }
+        let written = template.write(&value)?;
+        assert_eq!(written.len(), 11);
+        assert_eq!(template.read(&written)?, value);
+        Ok(())
+    }
+
+    #[test]
+    fn edited_values_round_trip() -> Result<(), String> {
+        let template = BlobTemplate::parse("NewAge", DOC_EXAMPLE)?;
+        let mut value = template.read(&tagged_blob())?;
+        if let Some(DecodedValue::List(entries)) = value.field_mut("entries")
+            && let Some(name) = entries[1].field_mut("name")
+        {
+            *name = DecodedValue::String("Shadowheart".to_string());
+        }
+        let written = template.write(&value)?;
+        assert_eq!(written.len(), tagged_blob().len() + 3);
+        assert_eq!(template.read(&written)?, value);
+
+        // Counts must agree with the arrays they give the length of
+        if let Some(count) = value.field_mut("count") {
+            *count = DecodedValue::UInt(3);
+        }
+        assert!(template.write(&value).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn templates_using_remaining_in_expressions_are_read_only() -> Result<(), String> {
+        for source in [
+            "u8 kind; if (_remaining > 2) { u16 extra; }",
+            "u8 kind; bytes data[_remaining - 1]; u8 last;",
+            "struct S { u8 x[_remaining / 2]; } S s;",
+        ] {
+            let template = BlobTemplate::parse("remaining", source)?;
+            assert!(!template.can_encode(), "{source}");
+            let value = template.read(&[1, 2, 3, 4])?;
+            assert!(
+                template
+                    .write(&value)
+                    .is_err_and(|e| e.contains("only known when reading")),
+                "{source}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn array_to_end_of_empty_structs_fails() -> Result<(), String> {
+        let template = BlobTemplate::parse("empty", "struct E { if (0) { u8 x; } }\nE items[];")?;
+        let result = template.read(&[1, 2, 3]);
+        assert!(result.is_err_and(|e| e.contains("reads no bytes")));
+        Ok(())
+    }
+}
@@ -0,0 +1,596 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+
+use crate::blob_diff::{BlobDiff, diff_blobs};
+use crate::lsf_reader::{Node, NodeAttribute, NodeAttributeValue, Resource};
+use crate::node_path::{NodePath, NodePathSegment};
+
+/// How nodes and attribute values are matched when diffing resources.
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Attributes identifying a node among its siblings of the same name, tried in order,
+    /// e.g. `["UUID", "MapKey"]`. Siblings without any of them are matched by position.
+    pub match_keys: Vec<String>,
+    /// Largest difference for float values (and their vector and matrix components)
+    /// to still be considered equal.
+    pub float_tolerance: f64,
+}
+
+impl DiffOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Node only found in the new resource, its children included.
+    NodeAdded { path: NodePath },
+    /// Node only found in the old resource, its children included.
+    NodeRemoved { path: NodePath },
+    AttributeAdded {
+        path: NodePath,
+        name: String,
+        value: NodeAttribute,
+    },
+    AttributeRemoved {
+        path: NodePath,
+        name: String,
+        value: NodeAttribute,
+    },
+    AttributeChanged {
+        path: NodePath,
+        name: String,
+        old: NodeAttribute,
+        new: NodeAttribute,
+    },
+}
+
+impl Change {
+    /// Path of the node the change was found in.
+    pub fn path(&self) -> &NodePath {
+        match self {
+            Self::NodeAdded { path }
+            | Self::NodeRemoved { path }
+            | Self::AttributeAdded { path, .. }
+            | Self::AttributeRemoved { path, .. }
+            | Self::AttributeChanged { path, .. } => path,
+        }
+    }
+
+    /// Byte-level diff of a changed blob attribute, see `blob_diff`.
+    pub fn blob_diff(&self) -> Option<BlobDiff> {
+        match self {
+            Self::AttributeChanged { old, new, .. } => {
+                Some(diff_blobs(old.value.as_bytes()?, new.value.as_bytes()?))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NodeAdded { path } => write!(f, "+ {path}"),
+            Self::NodeRemoved { path } => write!(f, "- {path}"),
+            Self::AttributeAdded { path, name, value } => {
+                write!(f, "+ {path}@{name} ({}): {value}", value.ty)
+            }
+            Self::AttributeRemoved { path, name, value } => {
+                write!(f, "- {path}@{name} ({}): {value}", value.ty)
+            }
+            Self::AttributeChanged {
+                path,
+                name,
+                old,
+                new,
+            } => {
+                if old.ty == new.ty {
+                    write!(f, "~ {path}@{name} ({}): {old} -> {new}", old.ty)
+                } else {
+                    write!(
+                        f,
+                        "~ {path}@{name}: {old} ({}) -> {new} ({})",
+                        old.ty, new.ty
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// Differences between two resources, in tree order.
+/// Paths of added nodes point into the new resource, all other paths into the old one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceDiff {
+    pub changes: Vec<Change>,
+}
+
+impl ResourceDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+}
+
+impl Display for ResourceDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Resource {
+    /// Differences from `self` to `other`, matching nodes by name and position.
+    pub fn diff(&self, other: &Resource) -> ResourceDiff {
+        self.diff_with(other, &DiffOptions::default())
+    }
+
+    /// Differences from `self` to `other`.
+    pub fn diff_with(&self, other: &Resource, options: &DiffOptions) -> ResourceDiff {
+        let mut differ = Differ {
+            old: self,
+            new: other,
+            options,
+            changes: vec![],
+        };
+
+        let region_names: BTreeSet<_> = self
+            .regions
+            .regions_indices
+            .keys()
+            .chain(other.regions.regions_indices.keys())
+            .collect();
+
+        for name in region_names {
+            let path = NodePath::region(name);
+            let old_node = self.regions.regions_indices.get(name);
+            let new_node = other.regions.regions_indices.get(name);
+            match (old_node, new_node) {
+                (Some(old_idx), Some(new_idx)) => differ.diff_nodes(&path, *old_idx, *new_idx),
+                (Some(_), None) => differ.changes.push(Change::NodeRemoved { path }),
+                (None, Some(_)) => differ.changes.push(Change::NodeAdded { path }),
+                (None, None) => {}
+            }
+        }
+
+        ResourceDiff {
+            changes: differ.changes,
+        }
+    }
+}
+
+struct Differ<'a> {
+    old: &'a Resource,
+    new: &'a Resource,
+    options: &'a DiffOptions,
+    changes: Vec<Change>,
+}
+
+impl Differ<'_> {
+    fn diff_nodes(&mut self, path: &NodePath, old_idx: usize, new_idx: usize) {
+        let (Some(old), Some(new)) = (
+            self.old.regions.get_node(old_idx),
+            self.new.regions.get_node(new_idx),
+        ) else {
+            return;
+        };
+
+        self.diff_attributes(path, old, new);
+
+        let child_names: BTreeSet<_> = old.children.keys().chain(new.children.keys()).collect();
+        for name in child_names {
+            let old_children = old
+                .children
+                .get(name)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let new_children = new
+                .children
+                .get(name)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            self.diff_children(path, name, old_children, new_children);
+        }
+    }
+
+    fn diff_attributes(&mut self, path: &NodePath, old: &Node, new: &Node) {
+        let names: BTreeSet<_> = old.attributes.keys().chain(new.attributes.keys()).collect();
+        for name in names {
+            let change = match (old.attributes.get(name), new.attributes.get(name)) {
+                (Some(old_value), Some(new_value)) => {
+                    if self.attributes_equal(old_value, new_value) {
+                        continue;
+                    }
+                    Change::AttributeChanged {
+                        path: path.clone(),
+                        name: name.to_string(),
+                        old: old_value.clone(),
+                        new: new_value.clone(),
+                    }
+                }
+                (Some(value), None) => Change::AttributeRemoved {
+                    path: path.clone(),
+                    name: name.to_string(),
+                    value: value.clone(),
+                },
+                (None, Some(value)) => Change::AttributeAdded {
+                    path: path.clone(),
+                    name: name.to_string(),
+                    value: value.clone(),
+                },
+                (None, None) => continue,
+            };
+            self.changes.push(change);
+        }
+    }
+
+    fn diff_children(
+        &mut self,
+        path: &NodePath,
+        name: &str,
+        old_children: &[usize],
+        new_children: &[usize],
+    ) {
+        let matches = match_children(
+            self.old,
+            old_children,
+            self.new,
+            new_children,
+            name,
+            self.options,
+        );
+
+        for child in matches {
+            let child_path = path.child(child.segment);
+            match (child.old, child.new) {
+                (Some(old_idx), Some(new_idx)) => self.diff_nodes(&child_path, old_idx, new_idx),
+                (Some(_), None) => self.changes.push(Change::NodeRemoved { path: child_path }),
+                (None, Some(_)) => self.changes.push(Change::NodeAdded { path: child_path }),
+                (None, None) => {}
+            }
+        }
+    }
+
+    fn attributes_equal(&self, old: &NodeAttribute, new: &NodeAttribute) -> bool {
+        attributes_equal(old, new, self.options.float_tolerance)
+    }
+}
+
+/// Changes from the subtree at `old_idx` to the one at `new_idx`, both rooted at `path`.
+pub(crate) fn diff_subtrees(
+    old: &Resource,
+    old_idx: usize,
+    new: &Resource,
+    new_idx: usize,
+    path: &NodePath,
+    options: &DiffOptions,
+) -> Vec<Change> {
+    let mut differ = Differ {
+        old,
+        new,
+        options,
+        changes: vec![],
+    };
+    differ.diff_nodes(path, old_idx, new_idx);
+    differ.changes
+}
+
+/// Children of two nodes matched with each other, see `match_children`.
+pub(crate) struct ChildMatch {
+    /// Node index in the old resource.
+    pub old: Option<usize>,
+    /// Node index in the new resource.
+    pub new: Option<usize>,
+    /// Path segment of the child, in the old resource unless only found in the new one.
+    pub segment: NodePathSegment,
+}
+
+/// Matches the children named `name` of two matched nodes: by key attribute first,
+/// then by position among the remaining ones. Matches come in old order,
+/// followed by the children only found in the new resource.
+pub(crate) fn match_children(
+    old: &Resource,
+    old_children: &[usize],
+    new: &Resource,
+    new_children: &[usize],
+    name: &str,
+    options: &DiffOptions,
+) -> Vec<ChildMatch> {
+    let old_keys: Vec<_> = old_children
+        .iter()
+        .map(|idx| match_key(old, *idx, options))
+        .collect();
+    let new_keys: Vec<_> = new_children
+        .iter()
+        .map(|idx| match_key(new, *idx, options))
+        .collect();
+
+    // Keyed new children, by key; duplicate keys are matched in order
+    let mut new_by_key: HashMap<&(String, String), Vec<usize>> = HashMap::new();
+    for (position, key) in new_keys.iter().enumerate().rev() {
+        if let Some(key) = key {
+            new_by_key.entry(key).or_default().push(position);
+        }
+    }
+
+    // Key values shared by several siblings would resolve to the first of them
+    let old_duplicates = duplicate_keys(&old_keys);
+    let new_duplicates = duplicate_keys(&new_keys);
+
+    let mut new_matched = vec![false; new_children.len()];
+    let mut unkeyed_new = (0..new_children.len()).filter(|p| new_keys[*p].is_none());
+    let has_siblings = old_children.len() > 1 || new_children.len() > 1;
+    let mut matches = Vec::with_capacity(old_children.len().max(new_children.len()));
+
+    for (position, key) in old_keys.iter().enumerate() {
+        let matched = match key {
+            Some(key) => new_by_key.get_mut(key).and_then(Vec::pop),
+            None => unkeyed_new.next(),
+        };
+        if let Some(new_position) = matched {
+            new_matched[new_position] = true;
+        }
+        matches.push(ChildMatch {
+            old: Some(old_children[position]),
+            new: matched.map(|p| new_children[p]),
+            segment: segment(name, key.as_ref(), position, has_siblings, &old_duplicates),
+        });
+    }
+
+    for (position, key) in new_keys.iter().enumerate() {
+        if !new_matched[position] {
+            matches.push(ChildMatch {
+                old: None,
+                new: Some(new_children[position]),
+                segment: segment(name, key.as_ref(), position, has_siblings, &new_duplicates),
+            });
+        }
+    }
+
+    matches
+}
+
+fn duplicate_keys(keys: &[Option<(String, String)>]) -> HashSet<&(String, String)> {
+    let mut seen = HashSet::new();
+    keys.iter()
+        .flatten()
+        .filter(|key| !seen.insert(*key))
+        .collect()
+}
+
+/// First configured key attribute of a node, with its value.
+fn match_key(
+    resource: &Resource,
+    node_idx: usize,
+    options: &DiffOptions,
+) -> Option<(String, String)> {
+    let node = resource.regions.get_node(node_idx)?;
+    options.match_keys.iter().find_map(|key| {
+        node.attributes
+            .get(key.as_str())
+            .map(|value| (key.clone(), value.to_string()))
+    })
+}
+
+/// Whether two attributes have the same type and value, floats being compared within `tolerance`.
+/// NaN components are equal to each other.
+pub(crate) fn attributes_equal(old: &NodeAttribute, new: &NodeAttribute, tolerance: f64) -> bool {
+    if old.ty != new.ty {
+        return false;
+    }
+
+    match (float_components(&old.value), float_components(&new.value)) {
+        (Some(old_floats), Some(new_floats)) => old_floats
+            .iter()
+            .zip(&new_floats)
+            .all(|(a, b)| a == b || (a.is_nan() && b.is_nan()) || (a - b).abs() <= tolerance),
+        _ => old.value == new.value,
+    }
+}
+
+/// Path segment of a child, by key unless other siblings share its key value.
+fn segment(
+    name: &str,
+    key: Option<&(String, String)>,
+    position: usize,
+    has_siblings: bool,
+    duplicates: &HashSet<&(String, String)>,
+) -> NodePathSegment {
+    match key {
+        Some(key) if duplicates.contains(key) => NodePathSegment::with_index(name, position),
+        Some((key, value)) => NodePathSegment::with_attribute(name, key, value),
+        None if has_siblings => NodePathSegment::with_index(name, position),
+        None => NodePathSegment::new(name),
+    }
+}
+
+fn float_components(value: &NodeAttributeValue) -> Option<Vec<f64>> {
+    let floats: &[f32] = match value {
+        NodeAttributeValue::Double(v) => return Some(vec![*v]),
+        NodeAttributeValue::Float(v) => std::slice::from_ref(v),
+        NodeAttributeValue::Vec2(v) => v,
+        NodeAttributeValue::Vec3(v) => v,
+        NodeAttributeValue::Vec4(v) => v,
+        NodeAttributeValue::Mat2(v) => v.as_flattened(),
+        NodeAttributeValue::Mat3(v) => v.as_flattened(),
+        NodeAttributeValue::Mat3x4(v) => v.as_flattened(),
+        NodeAttributeValue::Mat4x3(v) => v.as_flattened(),
+        NodeAttributeValue::Mat4(v) => v.as_flattened(),
+        _ => return None,
+    };
+    Some(floats.iter().map(|f| f64::from(*f)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsf_reader::DataType;
+    use crate::test_support::{add_node, node_mut, sample_resource};
+
+    fn float(value: f32) -> NodeAttribute {
+        NodeAttribute {
+            ty: DataType::Float,
+            value: NodeAttributeValue::Float(value),
+        }
+    }
+
+    fn keyed_options() -> DiffOptions {
+        DiffOptions {
+            match_keys: vec!["MapKey".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn identical_resources_have_no_changes() {
+        let mut resource = sample_resource();
+        add_node(
+            &mut resource,
+            "Floats",
+            None,
+            vec![("NaN", float(f32::NAN))],
+        );
+        assert!(resource.diff(&resource).is_empty());
+        assert!(resource.diff_with(&resource, &keyed_options()).is_empty());
+    }
+
+    #[test]
+    fn floats_are_compared_within_tolerance() {
+        assert!(attributes_equal(&float(f32::NAN), &float(f32::NAN), 0.0));
+        assert!(!attributes_equal(&float(f32::NAN), &float(1.0), 1.0));
+        assert!(!attributes_equal(&float(1.0), &float(1.5), 0.0));
+        assert!(attributes_equal(&float(1.0), &float(1.5), 0.5));
+    }
+
+    #[test]
+    fn attribute_and_node_changes() -> Result<(), String> {
+        let old = sample_resource();
+        let mut new = old.clone();
+        let option = node_mut(&mut new, "Config/Option")?;
+        option.attributes.insert("Enabled".into(), float(1.0));
+        option.attributes.insert("Added".into(), float(2.0));
+        node_mut(&mut new, "Config")?.attributes.remove("Version");
+        let templates = new.regions.regions_indices["Templates"];
+        add_node(&mut new, "Extra", Some(templates), vec![]);
+        new.regions.regions_indices.remove("Templates");
+        add_node(&mut new, "Added", None, vec![]);
+
+        let lines: Vec<String> = old
+            .diff(&new)
+            .changes
+            .iter()
+            .map(Change::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "+ Added",
+                "- Config@Version (int32): 3",
+                "+ Config/Option@Added (float): 2",
+                "~ Config/Option@Enabled: True (bool) -> 1 (float)",
+                "- Templates",
+            ]
+        );
+
+        let lines: Vec<String> = new
+            .diff(&old)
+            .changes
+            .iter()
+            .map(Change::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "- Added",
+                "+ Config@Version (int32): 3",
+                "- Config/Option@Added (float): 2",
+                "~ Config/Option@Enabled: 1 (float) -> True (bool)",
+                "+ Templates",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn children_are_added_and_removed() -> Result<(), String> {
+        let old = sample_resource();
+        let mut new = old.clone();
+        let templates = new.regions.regions_indices["Templates"];
+        add_node(&mut new, "Item", Some(templates), vec![]);
+        add_node(&mut new, "Extra", Some(templates), vec![]);
+        let option = node_mut(&mut new, "Config")?;
+        option.children.clear();
+
+        let lines: Vec<String> = old
+            .diff(&new)
+            .changes
+            .iter()
+            .map(Change::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "- Config/Option",
+                "+ Templates/Extra",
+                "+ Templates/Item[3]",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keyed_children_are_matched_regardless_of_order() -> Result<(), String> {
+        let old = sample_resource();
+        let mut new = old.clone();
+        let templates = new.regions.regions_indices["Templates"];
+        // Swap the first two items, siblings sharing a key value keep their order
+        new.regions.node_instances[templates]
+            .children
+            .values_mut()
+            .for_each(|items| items.swap(0, 1));
+        assert!(old.diff_with(&new, &keyed_options()).is_empty());
+        assert!(!old.diff(&new).is_empty());
+
+        node_mut(&mut new, "Templates/Item[MapKey=Shield]/Tag")?
+            .attributes
+            .insert("Count".into(), float(1.0));
+        let diff = old.diff_with(&new, &keyed_options());
+        assert_eq!(diff.len(), 1);
+        assert_eq!(
+            diff.changes[0].path().to_string(),
+            "Templates/Item[MapKey=Shield]/Tag"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_keys_get_index_paths() -> Result<(), String> {
+        let old = sample_resource();
+        let mut new = old.clone();
+        // The third item shares its MapKey with the first one
+        node_mut(&mut new, "Templates/Item[2]")?
+            .attributes
+            .insert("Count".into(), float(1.0));
+
+        let diff = old.diff_with(&new, &keyed_options());
+        assert_eq!(diff.len(), 1);
+        let path = diff.changes[0].path();
+        assert_eq!(path.to_string(), "Templates/Item[2]");
+        assert_eq!(
+            path.resolve(&old),
+            "Templates/Item[2]".parse::<NodePath>()?.resolve(&old)
+        );
+        assert_ne!(
+            path.resolve(&old),
+            "Templates/Item".parse::<NodePath>()?.resolve(&old)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt::Debug;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Read;
+
+use crate::abstract_file_info::CompressionMethod;
+use crate::bin_utils::{self, LZ4_FRAME_MAGIC, LZ4_MAX_RATIO, ZSTD_FRAME_MAGIC};
+use crate::lsf_reader::{
+    LSFReader, LSFVersion, LSOF_SIGNATURE, NodeAttributeValue, Resource, lsf_file_size,
+};
+use crate::lsf_writer::LSFWriter;
+
+/// How a resource is stored in a `Bytes` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbeddedEncoding {
+    /// LSF file stored as is.
+    Lsf,
+    /// LSF file in an LZ4 frame.
+    Lz4Frame,
+    /// LSF file in a zstd frame.
+    ZstdFrame,
+}
+
+impl EmbeddedEncoding {
+    /// Encoding suggested by the first bytes of a blob. The blob may still not hold a resource.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&LSOF_SIGNATURE) {
+            Some(Self::Lsf)
+        } else if bytes.starts_with(&LZ4_FRAME_MAGIC) {
+            Some(Self::Lz4Frame)
+        } else if bytes.starts_with(&ZSTD_FRAME_MAGIC) {
+            Some(Self::ZstdFrame)
+        } else {
+            None
+        }
+    }
+}
+
+/// Resource decoded from a `Bytes` attribute, which is re-encoded the same way, in place of
+/// the attribute, when the resource holding it is written.
+#[derive(Clone, PartialEq)]
+pub struct EmbeddedResource {
+    pub encoding: EmbeddedEncoding,
+    pub resource: Resource,
+    version: LSFVersion,
+    compression_flags: u8,
+    /// Hash of the bytes the resource was decoded from, or last encoded into.
+    source_hash: u64,
+}
+
+impl Debug for EmbeddedResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmbeddedResource")
+            .field("encoding", &self.encoding)
+            .field("version", &self.version)
+            .field("compression_flags", &self.compression_flags)
+            .field("nodes", &self.resource.regions.node_instances.len())
+            .finish()
+    }
+}
+
+impl EmbeddedResource {
+    /// Decodes the resource stored in a blob, and the resources embedded in it in turn.
+    /// Returns `Ok(None)` for blobs that do not hold a resource.
+    pub fn decode(bytes: &[u8]) -> Result<Option<Self>, String> {
+        let Some(encoding) = EmbeddedEncoding::detect(bytes) else {
+            return Ok(None);
+        };
+        let lsf_bytes = match encoding {
+            EmbeddedEncoding::Lsf => bytes.to_vec(),
+            EmbeddedEncoding::Lz4Frame => decompress_lz4_frame(bytes)?,
+            EmbeddedEncoding::ZstdFrame => {
+                bin_utils::decompress(bytes, 0, CompressionMethod::ZSTD as u8, false)?
+            }
+        };
+        if !lsf_bytes.starts_with(&LSOF_SIGNATURE) {
+            return Ok(None);
+        }
+        // Bytes after the resource would be lost when re-encoding it
+        match lsf_file_size(&lsf_bytes) {
+            Some(size) if size == lsf_bytes.len() => {}
+            Some(size) => {
+                return Err(format!(
+                    "embedded LSF resource spans {size} of the {} bytes of the blob",
+                    lsf_bytes.len()
+                ));
+            }
+            None => return Err("embedded LSF resource has invalid or truncated headers".into()),
+        }
+
+        let mut reader = LSFReader::new();
+        let mut resource = reader.read_bytes(&lsf_bytes)?;
+        let nested_failures = resource.decode_embedded();
+        if let Some(failure) = nested_failures.first() {
+            return Err(format!(
+                "failed decoding resource embedded in attribute {} of node {}: {}",
+                failure.attribute, failure.node, failure.reason
+            ));
+        }
+
+        Ok(Some(Self {
+            encoding,
+            resource,
+            version: reader.version.unwrap_or(LSFVersion::VerBg3Patch3),
+            compression_flags: reader.metadata.compression_flags(),
+            source_hash: bytes_hash(bytes),
+        }))
+    }
+
+    /// Whether `bytes` are the ones the resource was decoded from, or last encoded into by
+    /// `Resource::encode_embedded`. Otherwise the attribute was changed since, and writing
+    /// the resource in its place would lose the change.
+    pub fn is_decoded_from(&self, bytes: &[u8]) -> bool {
+        self.source_hash == bytes_hash(bytes)
+    }
+
+    /// Writes the resource back with the LSF version, compression and encoding it was read with.
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let lsf_bytes = LSFWriter::new()
+            .with_version(self.version)
+            .with_compression(self.compression_flags)
+            .write_bytes(&self.resource)?;
+        match self.encoding {
+            EmbeddedEncoding::Lsf => Ok(lsf_bytes),
+            EmbeddedEncoding::Lz4Frame => {
+                bin_utils::compress_chunked(&lsf_bytes, CompressionMethod::LZ4 as u8)
+            }
+            EmbeddedEncoding::ZstdFrame => {
+                bin_utils::compress(&lsf_bytes, CompressionMethod::ZSTD as u8)
+            }
+        }
+    }
+}
+
+/// Blob that looked like an embedded resource, but could not be decoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedDecodeFailure {
+    pub node: usize,
+    pub attribute: String,
+    pub reason: String,
+}
+
+impl Resource {
+    /// Decodes the resources embedded in `Bytes` attributes into the `embedded` subtrees of
+    /// their nodes, recursively. Blobs that do not start with an LSF signature or a known
+    /// compression frame are left alone; the others that fail to decode are reported.
+    pub fn decode_embedded(&mut self) -> Vec<EmbeddedDecodeFailure> {
+        let mut failures = vec![];
+        for (node_idx, node) in self.regions.node_instances.iter_mut().enumerate() {
+            for (name, attribute) in &node.attributes {
+                let NodeAttributeValue::Bytes(bytes) = &attribute.value else {
+                    continue;
+                };
+                match EmbeddedResource::decode(bytes) {
+                    Ok(Some(embedded)) => {
+                        node.embedded.insert(name.clone(), embedded);
+                    }
+                    Ok(None) => {}
+                    Err(reason) => failures.push(EmbeddedDecodeFailure {
+                        node: node_idx,
+                        attribute: name.to_string(),
+                        reason,
+                    }),
+                }
+            }
+        }
+        failures.sort_by(|a, b| (a.node, &a.attribute).cmp(&(b.node, &b.attribute)));
+        failures
+    }
+
+    /// Resource embedded in an attribute of the node at index `node_idx`.
+    pub fn embedded(&self, node_idx: usize, attribute: &str) -> Option<&Resource> {
+        self.regions
+            .get_node(node_idx)?
+            .embedded
+            .get(attribute)
+            .map(|embedded| &embedded.resource)
+    }
+
+    pub fn embedded_mut(&mut self, node_idx: usize, attribute: &str) -> Option<&mut Resource> {
+        self.regions
+            .node_instances
+            .get_mut(node_idx)?
+            .embedded
+            .get_mut(attribute)
+            .map(|embedded| &mut embedded.resource)
+    }
+
+    /// Re-encodes every embedded resource into the attribute it was decoded from, without
+    /// waiting for the resource to be written.
+    pub fn encode_embedded(&mut self) -> Result<(), String> {
+        for node in &mut self.regions.node_instances {
+            for (name, embedded) in &mut node.embedded {
+                let bytes = embedded.encode()?;
+                if let Some(attribute) = node.attributes.get_mut(name) {
+                    embedded.source_hash = bytes_hash(&bytes);
+                    attribute.value = NodeAttributeValue::Bytes(bytes);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the decoded subtree of an attribute; its last encoded bytes are kept.
+    pub fn remove_embedded(&mut self, node_idx: usize, attribute: &str) -> bool {
+        self.regions
+            .node_instances
+            .get_mut(node_idx)
+            .is_some_and(|node| node.embedded.remove(attribute).is_some())
+    }
+}
+
+fn bytes_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Decompresses an LZ4 frame, which only records its content size optionally.
+///
+/// The blob is untrusted, so nothing is allocated from the content size: the frame is
+/// streamed, up to the most its compressed bytes can expand to.
+fn decompress_lz4_frame(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let max_size = bytes.len() as u64 * LZ4_MAX_RATIO;
+    let content_size = lz4_frame_content_size(bytes);
+    if let Some(size) = content_size.filter(|size| *size > max_size) {
+        return Err(format!(
+            "LZ4 frame of {} bytes cannot hold {size} bytes of content",
+            bytes.len()
+        ));
+    }
+
+    let mut buf = vec![];
+    lz4_flex::frame::FrameDecoder::new(bytes)
+        .take(max_size + 1)
+        .read_to_end(&mut buf)
+        .map_err(|e| format!("failed to decompress LZ4 chunked (frame) file: {e}"))?;
+    if buf.len() as u64 > max_size {
+        return Err(format!(
+            "LZ4 frame of {} bytes decompresses to more than {max_size} bytes",
+            bytes.len()
+        ));
+    }
+    if let Some(size) = content_size.filter(|size| *size != buf.len() as u64) {
+        return Err(format!(
+            "LZ4 frame content is {} bytes, {size} expected",
+            buf.len()
+        ));
+    }
+    Ok(buf)
+}
+
+/// Content size from the frame descriptor, present when bit 3 of its flags is set.
+fn lz4_frame_content_size(bytes: &[u8]) -> Option<u64> {
+    let flags = *bytes.get(4)?;
+    if flags & 0x08 == 0 {
+        return None;
+    }
+    let size: [u8; 8] = bytes.get(6..14)?.try_into().ok()?;
+    Some(u64::from_le_bytes(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::sample_resource;
+
+    #[test]
+    fn lz4_frames_are_decoded() -> Result<(), String> {
+        let lsf = LSFWriter::new().write_bytes(&sample_resource())?;
+        let frame = bin_utils::compress_chunked(&lsf, CompressionMethod::LZ4 as u8)?;
+        let embedded = EmbeddedResource::decode(&frame)?.ok_or("no resource found")?;
+        assert_eq!(embedded.encoding, EmbeddedEncoding::Lz4Frame);
+        assert!(embedded.resource == sample_resource());
+        assert_eq!(decompress_lz4_frame(&embedded.encode()?)?, lsf);
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_lz4_content_sizes_are_rejected() {
+        // Frame descriptor announcing 2^40 bytes of content, and an end mark
+        let mut frame = LZ4_FRAME_MAGIC.to_vec();
+        frame.extend([0x48, 0x40]);
+        frame.extend((1u64 << 40).to_le_bytes());
+        frame.push(0);
+        frame.extend([0; 4]);
+
+        assert!(EmbeddedResource::decode(&frame).is_err_and(|e| e.contains("cannot hold")));
+    }
+}
@@ -1,10 +1,7 @@
-use bincode::Decode;
-use serde::Deserialize;
-use serde_big_array::BigArray;
+use crate::record::{self, FixedSizeRecord};
 
-#[derive(Debug, Deserialize, Decode)]
+#[derive(Debug)]
 pub struct FileEntry18 {
-    #[serde(with = "BigArray")]
     pub name: [u8; 256],
     pub offset_in_file_1: u32,
     pub offset_in_file_2: u16,
@@ -14,4 +11,35 @@ pub struct FileEntry18 {
     pub uncompressed_size: u32,
 }
 
-pub const SIZE_OF_FILE_ENTRY_18: usize = std::mem::size_of::<FileEntry18>();
+impl FixedSizeRecord for FileEntry18 {
+    const SIZE: usize = 272;
+
+    fn decode(bytes: &[u8]) -> Self {
+        let mut name = [0u8; 256];
+        name.copy_from_slice(&bytes[..256]);
+        Self {
+            name,
+            offset_in_file_1: record::u32_at(bytes, 256),
+            offset_in_file_2: record::u16_at(bytes, 260),
+            archive_part: bytes[262],
+            flags: bytes[263],
+            size_on_disk: record::u32_at(bytes, 264),
+            uncompressed_size: record::u32_at(bytes, 268),
+        }
+    }
+}
+
+impl FileEntry18 {
+    /// Encodes the entry into the `SIZE` bytes `decode` reads.
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[..256].copy_from_slice(&self.name);
+        bytes[256..260].copy_from_slice(&self.offset_in_file_1.to_le_bytes());
+        bytes[260..262].copy_from_slice(&self.offset_in_file_2.to_le_bytes());
+        bytes[262] = self.archive_part;
+        bytes[263] = self.flags;
+        bytes[264..268].copy_from_slice(&self.size_on_disk.to_le_bytes());
+        bytes[268..272].copy_from_slice(&self.uncompressed_size.to_le_bytes());
+        bytes
+    }
+}
@@ -0,0 +1,242 @@
+use std::cell::OnceCell;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::sync::Arc;
+
+use crate::lsf_reader::{
+    LSFReader, LSMetadata, Node, NodeKind, PackedVersion, RegionArena, Resource,
+};
+
+/// View over an LSF file whose tables are decoded, but whose nodes are only built
+/// (attribute values included) the first time they are accessed.
+///
+/// Built with `LSFReader::read_lazy` or `LSFReader::read_bytes_lazy`.
+pub struct LazyResource {
+    reader: LSFReader,
+    metadata: LSMetadata,
+    regions_indices: BTreeMap<Arc<str>, usize>,
+    children_indices: Vec<Vec<usize>>,
+    nodes: Vec<OnceCell<Node>>,
+}
+
+impl LazyResource {
+    pub(crate) fn new(reader: LSFReader) -> Result<Self, String> {
+        let node_count = reader.node_infos.len();
+        let mut regions_indices = BTreeMap::new();
+        let mut children_indices = vec![vec![]; node_count];
+
+        for (node_idx, node_info) in reader.node_infos.iter().enumerate() {
+            match node_info.parent_index {
+                // Parents always come before their children in the node table
+                Some(parent_idx) if parent_idx < node_idx => {
+                    children_indices[parent_idx].push(node_idx);
+                }
+                Some(parent_idx) => {
+                    return Err(format!(
+                        "could not find parent node at index {parent_idx} of node {node_idx}"
+                    ));
+                }
+                None => {
+                    let name =
+                        reader.get_name(node_info.name_index, node_info.name_offset, "node")?;
+                    regions_indices.insert(name.clone(), node_idx);
+                }
+            }
+        }
+
+        let metadata = LSMetadata {
+            game_version: reader.game_version,
+            ..Default::default()
+        };
+
+        Ok(Self {
+            reader,
+            metadata,
+            regions_indices,
+            children_indices,
+            nodes: std::iter::repeat_with(OnceCell::new)
+                .take(node_count)
+                .collect(),
+        })
+    }
+
+    pub fn metadata(&self) -> &LSMetadata {
+        &self.metadata
+    }
+
+    pub fn game_version(&self) -> PackedVersion {
+        self.reader.game_version
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of nodes built so far.
+    pub fn loaded_node_count(&self) -> usize {
+        self.nodes.iter().filter(|n| n.get().is_some()).count()
+    }
+
+    pub fn region_names(&self) -> impl Iterator<Item = &str> {
+        self.regions_indices.keys().map(|name| &**name)
+    }
+
+    pub fn region_index(&self, name: &str) -> Option<usize> {
+        self.regions_indices.get(name).copied()
+    }
+
+    /// Root node of a region, built on first access.
+    pub fn region(&self, name: &str) -> Result<Option<&Node>, String> {
+        self.region_index(name)
+            .map(|idx| self.node(idx))
+            .transpose()
+    }
+
+    /// Indices of the direct children of a node, without building them.
+    pub fn children_indices(&self, node_idx: usize) -> &[usize] {
+        self.children_indices
+            .get(node_idx)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Node at `node_idx`, built on first access.
+    pub fn node(&self, node_idx: usize) -> Result<&Node, String> {
+        let cell = self
+            .nodes
+            .get(node_idx)
+            .ok_or_else(|| format!("node index {node_idx} is out of range"))?;
+
+        if let Some(node) = cell.get() {
+            return Ok(node);
+        }
+
+        let node = self.build_node(node_idx)?;
+        Ok(cell.get_or_init(|| node))
+    }
+
+    /// Index of the node found by following child names from a region root,
+    /// e.g. `["Globals", "Party"]`. The first child with a matching name is followed at each step.
+    pub fn find(&self, path: &[&str]) -> Option<usize> {
+        let (region, children) = path.split_first()?;
+        let mut node_idx = self.region_index(region)?;
+
+        for child_name in children {
+            node_idx = *self.children_indices(node_idx).iter().find(|child_idx| {
+                self.node_name(**child_idx)
+                    .is_ok_and(|name| &**name == *child_name)
+            })?;
+        }
+
+        Some(node_idx)
+    }
+
+    /// Builds every node of the subtree rooted at `node_idx`.
+    pub fn load_subtree(&self, node_idx: usize) -> Result<(), String> {
+        let mut pending = vec![node_idx];
+        while let Some(idx) = pending.pop() {
+            self.node(idx)?;
+            pending.extend_from_slice(self.children_indices(idx));
+        }
+        Ok(())
+    }
+
+    /// Builds the remaining nodes, and returns the whole resource.
+    pub fn into_resource(self) -> Result<Resource, String> {
+        for node_idx in 0..self.nodes.len() {
+            self.node(node_idx)?;
+        }
+
+        let node_instances = self
+            .nodes
+            .into_iter()
+            .map(|cell| cell.into_inner().unwrap_or_default())
+            .collect();
+
+        Ok(Resource {
+            metadata: self.metadata,
+            regions: RegionArena {
+                regions_indices: self.regions_indices,
+                node_instances,
+            },
+        })
+    }
+
+    fn node_name(&self, node_idx: usize) -> Result<&Arc<str>, String> {
+        let node_info = self
+            .reader
+            .node_infos
+            .get(node_idx)
+            .ok_or_else(|| format!("node index {node_idx} is out of range"))?;
+        self.reader
+            .get_name(node_info.name_index, node_info.name_offset, "node")
+    }
+
+    fn build_node(&self, node_idx: usize) -> Result<Node, String> {
+        let node_info = &self.reader.node_infos[node_idx];
+        let mut values_stream = Cursor::new(&self.reader.values[..]);
+        let node_data =
+            self.reader
+                .read_node(node_idx, node_info, &mut values_stream, &mut vec![])?;
+
+        let kind = match node_info.parent_index {
+            Some(_) => NodeKind::Node,
+            None => NodeKind::Region {
+                name: node_data.name.clone(),
+            },
+        };
+
+        let mut node = Node {
+            kind,
+            name: node_data.name,
+            parent: node_info.parent_index,
+            attributes: node_data.attributes.unwrap_or_default(),
+            children: Default::default(),
+            embedded: Default::default(),
+        };
+
+        for &child_idx in self.children_indices(node_idx) {
+            node.append_child(self.node_name(child_idx)?, child_idx);
+        }
+
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsf_reader::LSFReader;
+    use crate::lsf_writer::LSFWriter;
+    use crate::test_support::sample_resource;
+
+    #[test]
+    fn lazy_nodes_match_eager_read() -> Result<(), String> {
+        let bytes = LSFWriter::new().write_bytes(&sample_resource())?;
+        let eager = LSFReader::new().read_bytes(&bytes)?;
+
+        let lazy = LSFReader::new().read_bytes_lazy(&bytes)?;
+        assert_eq!(lazy.node_count(), eager.regions.node_instances.len());
+        assert_eq!(lazy.loaded_node_count(), 0);
+        assert!(
+            lazy.region_names()
+                .eq(eager.regions.regions_indices.keys().map(|n| &**n))
+        );
+
+        let item = lazy.find(&["Templates", "Item"]).ok_or("Item not found")?;
+        let tag = lazy
+            .find(&["Templates", "Item", "Tag"])
+            .ok_or("Tag not found")?;
+        assert_eq!(eager.regions.node_instances[tag].parent, Some(item));
+        assert_eq!(lazy.find(&["Templates", "Missing"]), None);
+        assert_eq!(lazy.loaded_node_count(), 0);
+
+        lazy.load_subtree(item)?;
+        assert_eq!(lazy.loaded_node_count(), 2);
+        for idx in [item, tag] {
+            assert_eq!(lazy.node(idx)?, &eager.regions.node_instances[idx]);
+        }
+
+        assert!(lazy.into_resource()? == eager);
+        Ok(())
+    }
+}
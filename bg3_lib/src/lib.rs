@@ -1,14 +1,43 @@
 #![deny(clippy::unwrap_used)]
 #![deny(clippy::expect_used)]
 pub mod abstract_file_info;
+mod attribute_value;
 mod bin_utils;
+pub mod blob_analysis;
+pub mod blob_decoder;
+pub mod blob_diff;
+pub mod blob_template;
+pub mod diff;
+pub mod embedded_resource;
 mod file_entry;
+pub mod lazy_resource;
+pub mod loca;
+pub mod localization;
 pub mod lsf_reader;
+pub mod lsf_visitor;
+pub mod lsf_writer;
 mod lspk_header;
+pub mod merge;
+pub mod node_path;
+pub mod osiris;
+pub mod osiris_reader;
+pub mod osiris_writer;
 pub mod package;
 mod package_metadata;
 pub mod package_reader;
 pub mod package_version;
+pub mod package_writer;
+pub mod patch;
+pub mod record;
+pub mod salvage;
+pub mod save;
+#[cfg(feature = "save-game")]
+pub mod save_game;
+pub mod save_info;
+pub mod save_meta;
+#[cfg(test)]
+mod test_support;
+pub mod thumbnail;
 
 // hexadecimal values for "LSPK" signature
 const LSPK_SIGNATURE: [u8; 4] = [0x4C, 0x53, 0x50, 0x4B];
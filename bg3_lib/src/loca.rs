@@ -0,0 +1,428 @@
+use std::io::{Cursor, SeekFrom, prelude::*};
+
+use bincode::{Decode, Encode};
+use quick_xml::events::{BytesDecl, BytesStart, BytesText, Event};
+
+use crate::{abstract_file_info::PackagedFileInfo, package_reader::PackageReader};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalizedText {
+    pub key: String,
+    pub version: u16,
+    pub text: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LocaResource {
+    pub entries: Vec<LocalizedText>,
+}
+
+impl LocaResource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LocaReader;
+
+impl LocaReader {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn read(
+        &mut self,
+        package_reader: &mut PackageReader,
+        pfi: &PackagedFileInfo,
+    ) -> Result<LocaResource, String> {
+        println!("Reading LOCA file {}", pfi.name.to_string_lossy());
+        let file_bytes = package_reader.decompress_file(pfi)?;
+        self.read_bytes(&file_bytes)
+    }
+
+    /// Reads either a binary `.loca` file or an XML `contentList`, depending on its signature.
+    pub fn read_bytes(&mut self, bytes: &[u8]) -> Result<LocaResource, String> {
+        if bytes.starts_with(&LocaHeader::LOCA_SIGNATURE) {
+            self.read_loca(bytes)
+        } else {
+            let xml = std::str::from_utf8(bytes)
+                .map_err(|e| format!("localization file is neither LOCA nor UTF-8 XML: {e}"))?;
+            self.read_xml(xml)
+        }
+    }
+
+    pub fn read_loca(&mut self, bytes: &[u8]) -> Result<LocaResource, String> {
+        let mut stream = Cursor::new(bytes);
+
+        let header: LocaHeader =
+            bincode::decode_from_std_read(&mut stream, bincode::config::legacy())
+                .map_err(|e| format!("could not deserialize LOCA header: {e}"))?;
+
+        if header.signature != LocaHeader::LOCA_SIGNATURE {
+            return Err(format!(
+                "invalid LOCA signature; expected {:?}, got {:?}",
+                LocaHeader::LOCA_SIGNATURE,
+                header.signature
+            ));
+        }
+
+        // The entry count is untrusted, so it only bounds the allocation by what the file holds
+        let max_entries = (bytes.len() - LocaHeader::SIZE) / LocaEntry::SIZE;
+        let mut entries = Vec::with_capacity((header.num_entries as usize).min(max_entries));
+        for i in 0..header.num_entries {
+            let entry: LocaEntry =
+                bincode::decode_from_std_read(&mut stream, bincode::config::legacy())
+                    .map_err(|e| format!("failed to read LOCA entry {i}: {e}"))?;
+            entries.push(entry);
+        }
+
+        stream
+            .seek(SeekFrom::Start(header.texts_offset as u64))
+            .map_err(|e| format!("failed to seek LOCA texts at {}: {e}", header.texts_offset))?;
+
+        let mut resource = LocaResource {
+            entries: Vec::with_capacity(entries.len()),
+        };
+
+        for entry in entries {
+            let key_len = entry.key.iter().take_while(|c| **c != 0).count();
+            let key = String::from_utf8_lossy(&entry.key[..key_len]).to_string();
+
+            let remaining = bytes.len().saturating_sub(stream.position() as usize);
+            if entry.length as usize > remaining {
+                return Err(format!(
+                    "{}-bytes long text of LOCA entry '{key}' is past the end of the file",
+                    entry.length
+                ));
+            }
+            let mut text_bytes = vec![0u8; entry.length as usize];
+            stream.read_exact(&mut text_bytes).map_err(|e| {
+                format!(
+                    "failed to read {}-bytes long text of LOCA entry '{key}': {e}",
+                    entry.length
+                )
+            })?;
+
+            // Texts are stored with their null terminator
+            if let Some(text_len) = text_bytes.iter().rposition(|&byte| byte != 0) {
+                text_bytes.truncate(text_len + 1);
+            } else {
+                text_bytes.clear();
+            }
+
+            let text = String::from_utf8(text_bytes)
+                .map_err(|e| format!("LOCA entry '{key}' is not valid UTF-8: {e}"))?;
+
+            resource.entries.push(LocalizedText {
+                key,
+                version: entry.version,
+                text,
+            });
+        }
+
+        Ok(resource)
+    }
+
+    /// Reads the `<contentList><content contentuid="..." version="...">` XML format.
+    /// Duplicate `contentuid`s are kept as separate entries, in document order.
+    pub fn read_xml(&mut self, xml: &str) -> Result<LocaResource, String> {
+        let mut reader = quick_xml::Reader::from_str(xml);
+        reader.config_mut().trim_text(false);
+
+        let mut resource = LocaResource::new();
+        let mut current: Option<LocalizedText> = None;
+
+        loop {
+            let event = reader.read_event().map_err(|e| {
+                format!(
+                    "failed to parse localization XML at position {}: {e}",
+                    reader.error_position()
+                )
+            })?;
+
+            match event {
+                Event::Start(e) if e.name().as_ref() == b"content" => {
+                    current = Some(read_xml_content_attributes(&e)?);
+                }
+                Event::Empty(e) if e.name().as_ref() == b"content" => {
+                    resource.entries.push(read_xml_content_attributes(&e)?);
+                }
+                Event::Text(e) => {
+                    if let Some(content) = current.as_mut() {
+                        let text = e.unescape().map_err(|e| {
+                            format!("invalid text in content '{}': {e}", content.key)
+                        })?;
+                        content.text.push_str(&text);
+                    }
+                }
+                Event::CData(e) => {
+                    if let Some(content) = current.as_mut() {
+                        let text = std::str::from_utf8(&e).map_err(|e| {
+                            format!("invalid CDATA in content '{}': {e}", content.key)
+                        })?;
+                        content.text.push_str(text);
+                    }
+                }
+                Event::End(e) if e.name().as_ref() == b"content" => {
+                    if let Some(content) = current.take() {
+                        resource.entries.push(content);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        if let Some(content) = current {
+            return Err(format!("unterminated content element '{}'", content.key));
+        }
+
+        Ok(resource)
+    }
+}
+
+fn read_xml_content_attributes(element: &BytesStart) -> Result<LocalizedText, String> {
+    let mut key = None;
+    // Content without an explicit version is considered to be at version 1
+    let mut version = 1;
+
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(|e| format!("invalid content attribute: {e}"))?;
+        let value = attribute
+            .unescape_value()
+            .map_err(|e| format!("invalid content attribute value: {e}"))?;
+        match attribute.key.as_ref() {
+            b"contentuid" => key = Some(value.to_string()),
+            b"version" => {
+                version = value
+                    .parse()
+                    .map_err(|e| format!("invalid content version '{value}': {e}"))?;
+            }
+            _ => {}
+        }
+    }
+
+    let key = key.ok_or("content element without a contentuid attribute")?;
+
+    Ok(LocalizedText {
+        key,
+        version,
+        text: String::new(),
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct LocaWriter;
+
+impl LocaWriter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn write<W: Write>(
+        &mut self,
+        resource: &LocaResource,
+        writer: &mut W,
+    ) -> Result<(), String> {
+        let texts_offset = LocaHeader::SIZE + LocaEntry::SIZE * resource.entries.len();
+        let header = LocaHeader {
+            signature: LocaHeader::LOCA_SIGNATURE,
+            num_entries: u32::try_from(resource.entries.len())
+                .map_err(|_| format!("too many LOCA entries: {}", resource.entries.len()))?,
+            texts_offset: u32::try_from(texts_offset)
+                .map_err(|_| format!("LOCA texts offset {texts_offset} is too large"))?,
+        };
+
+        bincode::encode_into_std_write(&header, writer, bincode::config::legacy())
+            .map_err(|e| format!("failed to write LOCA header: {e}"))?;
+
+        for text in &resource.entries {
+            let key_bytes = text.key.as_bytes();
+            // Keep at least one null byte at the end of the key
+            if key_bytes.len() >= LocaEntry::KEY_SIZE {
+                return Err(format!(
+                    "LOCA key '{}' is longer than {} bytes",
+                    text.key,
+                    LocaEntry::KEY_SIZE - 1
+                ));
+            }
+
+            let mut key = [0u8; LocaEntry::KEY_SIZE];
+            key[..key_bytes.len()].copy_from_slice(key_bytes);
+
+            let length = u32::try_from(text.text.len() + 1)
+                .map_err(|_| format!("LOCA text of '{}' is too long", text.key))?;
+            let entry = LocaEntry {
+                key,
+                version: text.version,
+                length,
+            };
+            bincode::encode_into_std_write(&entry, writer, bincode::config::legacy())
+                .map_err(|e| format!("failed to write LOCA entry '{}': {e}", text.key))?;
+        }
+
+        for text in &resource.entries {
+            writer
+                .write_all(text.text.as_bytes())
+                .and_then(|_| writer.write_all(&[0]))
+                .map_err(|e| format!("failed to write LOCA text of '{}': {e}", text.key))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the entries as an XML `contentList`, the format used to edit localization.
+    pub fn write_xml<W: Write>(
+        &mut self,
+        resource: &LocaResource,
+        writer: &mut W,
+    ) -> Result<(), String> {
+        let mut xml = quick_xml::Writer::new_with_indent(writer, b'\t', 1);
+
+        xml.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))
+            .map_err(|e| format!("failed to write XML declaration: {e}"))?;
+
+        xml.create_element("contentList")
+            .write_inner_content(|xml| {
+                for text in &resource.entries {
+                    let version = text.version.to_string();
+                    xml.create_element("content")
+                        .with_attributes([
+                            ("contentuid", text.key.as_str()),
+                            ("version", version.as_str()),
+                        ])
+                        .write_text_content(BytesText::new(&text.text))?;
+                }
+                Ok(())
+            })
+            .map_err(|e| format!("failed to write localization XML: {e}"))?;
+
+        Ok(())
+    }
+}
+
+#[derive(Decode, Encode)]
+struct LocaHeader {
+    signature: [u8; 4],
+    num_entries: u32,
+    texts_offset: u32,
+}
+
+impl LocaHeader {
+    // hexadecimal values for "LOCA" signature
+    const LOCA_SIGNATURE: [u8; 4] = [0x4C, 0x4F, 0x43, 0x41];
+    const SIZE: usize = 12;
+}
+
+#[derive(Decode, Encode)]
+struct LocaEntry {
+    key: [u8; LocaEntry::KEY_SIZE],
+    version: u16,
+    length: u32,
+}
+
+impl LocaEntry {
+    const KEY_SIZE: usize = 64;
+    const SIZE: usize = Self::KEY_SIZE + 2 + 4;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_resource() -> LocaResource {
+        LocaResource {
+            entries: vec![
+                LocalizedText {
+                    key: "h0001".into(),
+                    version: 1,
+                    text: "Hello".into(),
+                },
+                LocalizedText {
+                    key: "h0002".into(),
+                    version: 3,
+                    text: String::new(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn loca_round_trip() -> Result<(), String> {
+        let resource = sample_resource();
+        let mut bytes = vec![];
+        LocaWriter::new().write(&resource, &mut bytes)?;
+        assert_eq!(LocaReader::new().read_bytes(&bytes)?, resource);
+        Ok(())
+    }
+
+    #[test]
+    fn untrusted_counts_do_not_allocate() -> Result<(), String> {
+        let mut bytes = LocaHeader::LOCA_SIGNATURE.to_vec();
+        bytes.extend(u32::MAX.to_le_bytes());
+        bytes.extend(12u32.to_le_bytes());
+        assert!(LocaReader::new().read_loca(&bytes).is_err());
+
+        let mut bytes = vec![];
+        LocaWriter::new().write(&sample_resource(), &mut bytes)?;
+        // Length of the first text
+        let length_offset = LocaHeader::SIZE + LocaEntry::KEY_SIZE + 2;
+        bytes[length_offset..length_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(LocaReader::new().read_loca(&bytes).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn loca_to_xml_round_trip() -> Result<(), String> {
+        let mut resource = sample_resource();
+        resource.entries.push(LocalizedText {
+            key: "h0003".into(),
+            version: 2,
+            text: "<b>Tom & \"Jerry\"</b>\n's".into(),
+        });
+        let mut loca = vec![];
+        LocaWriter::new().write(&resource, &mut loca)?;
+
+        let mut xml = vec![];
+        LocaWriter::new().write_xml(&LocaReader::new().read_bytes(&loca)?, &mut xml)?;
+        let from_xml = LocaReader::new().read_bytes(&xml)?;
+        assert_eq!(from_xml, resource);
+
+        let mut loca_again = vec![];
+        LocaWriter::new().write(&from_xml, &mut loca_again)?;
+        assert_eq!(loca_again, loca);
+        Ok(())
+    }
+
+    #[test]
+    fn xml_escapes_and_cdata_are_decoded() -> Result<(), String> {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<contentList>
+	<content contentuid="h&amp;1" version="4">a &lt;b&gt; &amp;amp; <![CDATA[<c> & d]]></content>
+	<content contentuid="h2"/>
+</contentList>"#;
+        let resource = LocaReader::new().read_xml(xml)?;
+        assert_eq!(
+            resource.entries,
+            vec![
+                LocalizedText {
+                    key: "h&1".into(),
+                    version: 4,
+                    text: "a <b> &amp; <c> & d".into(),
+                },
+                LocalizedText {
+                    key: "h2".into(),
+                    version: 1,
+                    text: String::new(),
+                },
+            ]
+        );
+        assert!(
+            LocaReader::new()
+                .read_xml("<contentList><content version=\"1\">x</content></contentList>")
+                .is_err()
+        );
+        Ok(())
+    }
+}
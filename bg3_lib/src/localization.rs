@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::loca::{LocaResource, LocalizedText};
+use crate::lsf_reader::{NodeAttributeValue, TranslatedFSString, TranslatedString};
+
+/// Lookup table from translated string handles to their localized text.
+#[derive(Debug, Default)]
+pub struct LocalizationTable {
+    texts: HashMap<String, LocalizedText>,
+}
+
+impl LocalizationTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_resource(resource: LocaResource) -> Self {
+        let mut table = Self::new();
+        table.extend(resource);
+        table
+    }
+
+    /// Adds all entries of `resource`. Entries with an existing handle replace the previous text.
+    pub fn extend(&mut self, resource: LocaResource) {
+        self.texts.extend(
+            resource
+                .entries
+                .into_iter()
+                .map(|text| (text.key.clone(), text)),
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.texts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty()
+    }
+
+    pub fn get(&self, handle: &str) -> Option<&LocalizedText> {
+        self.texts.get(handle)
+    }
+
+    pub fn get_text(&self, handle: &str) -> Option<&str> {
+        self.get(handle).map(|t| t.text.as_str())
+    }
+
+    /// Text of a translated string: its own value if it has one, otherwise the text
+    /// its handle points to.
+    pub fn resolve<'a>(&'a self, string: &'a TranslatedString) -> Option<&'a str> {
+        string.value().or_else(|| self.get_text(string.handle()))
+    }
+
+    /// Text of a translated string, with its `[key]` placeholders replaced by the
+    /// (recursively resolved) arguments.
+    pub fn resolve_fs(&self, string: &TranslatedFSString) -> Option<String> {
+        let mut text = self.resolve(string.base())?.to_string();
+
+        for argument in string {
+            let arg_text = if argument.string().handle().is_empty() {
+                None
+            } else {
+                self.resolve_fs(argument.string())
+            };
+            let arg_text = arg_text.unwrap_or_else(|| argument.value().to_string());
+            text = text.replace(&format!("[{}]", argument.key()), &arg_text);
+        }
+
+        Some(text)
+    }
+
+    /// Readable text for translated string attribute values, `None` for other types
+    /// or handles missing from the table.
+    pub fn display(&self, value: &NodeAttributeValue) -> Option<String> {
+        match value {
+            NodeAttributeValue::TranslatedString(s) => self.resolve(s).map(str::to_string),
+            NodeAttributeValue::TranslatedFSString(s) => self.resolve_fs(s),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsf_reader::TranslatedFSStringArgument;
+
+    fn table() -> LocalizationTable {
+        let text = |key: &str, text: &str| LocalizedText {
+            key: key.into(),
+            version: 1,
+            text: text.into(),
+        };
+        LocalizationTable::from_resource(LocaResource {
+            entries: vec![
+                text("hGreeting", "Hello, [Name]!"),
+                text("hName", "Tav"),
+                text("hOld", "replaced"),
+                text("hOld", "Old text"),
+            ],
+        })
+    }
+
+    #[test]
+    fn resolve_prefers_own_value() {
+        let table = table();
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table.resolve(&TranslatedString::new("hOld".into(), 1)),
+            Some("Old text")
+        );
+        assert_eq!(
+            table.resolve(&TranslatedString::with_value(
+                "hOld".into(),
+                1,
+                "Inline".into()
+            )),
+            Some("Inline")
+        );
+        assert_eq!(
+            table.resolve(&TranslatedString::new("hMissing".into(), 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve_fs_replaces_arguments() {
+        let table = table();
+        let greeting = |argument: TranslatedFSString, value: &str| {
+            TranslatedFSString::new(
+                TranslatedString::new("hGreeting".into(), 1),
+                vec![TranslatedFSStringArgument::new(
+                    "Name".into(),
+                    argument,
+                    value.into(),
+                )],
+            )
+        };
+        let name = TranslatedFSString::new(TranslatedString::new("hName".into(), 1), vec![]);
+        let no_handle = TranslatedFSString::new(TranslatedString::new(String::new(), 0), vec![]);
+
+        assert_eq!(
+            table.resolve_fs(&greeting(name, "unused")).as_deref(),
+            Some("Hello, Tav!")
+        );
+        assert_eq!(
+            table.resolve_fs(&greeting(no_handle, "Karlach")).as_deref(),
+            Some("Hello, Karlach!")
+        );
+    }
+}
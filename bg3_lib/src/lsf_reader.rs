@@ -1,12 +1,18 @@
-use std::collections::{BTreeMap, HashMap};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
-use std::io::{BufReader, Cursor, SeekFrom, prelude::*};
+use std::io::{Cursor, SeekFrom, prelude::*};
+use std::sync::Arc;
 
 use bincode::Decode;
-use serde::{Deserialize, de::DeserializeOwned};
+use serde::Deserialize;
 
 use crate::abstract_file_info::CompressionMethod;
 use crate::bin_utils::{self, ReadExt};
+use crate::embedded_resource::EmbeddedResource;
+use crate::lazy_resource::LazyResource;
+use crate::lsf_visitor::{self, LSFVisitor};
+use crate::record::{self, FixedSizeRecord};
 use crate::{abstract_file_info::PackagedFileInfo, package_reader::PackageReader};
 
 #[derive(Debug, Default)]
@@ -14,10 +20,16 @@ pub struct LSFReader {
     pub version: Option<LSFVersion>,
     pub game_version: PackedVersion,
     pub metadata: LSFMetadataV6,
-    pub names: Vec<Vec<String>>,
+    /// Names table; nodes and attributes of read resources share these strings.
+    pub names: Vec<Vec<Arc<str>>>,
     pub node_infos: Vec<LSFNodeInfo>,
     pub attributes: Vec<LSFAttributeInfo>,
     pub values: Vec<u8>,
+    /// String values read so far, so that repeated values share one allocation.
+    value_strings: RefCell<HashSet<Arc<str>>>,
+    lenient: bool,
+    /// Values only live for a visitor callback, so sharing them would not save anything.
+    visiting: bool,
 }
 
 impl LSFReader {
@@ -32,7 +44,112 @@ impl LSFReader {
     ) -> Result<Resource, String> {
         println!("Reading LSF file {}", pfi.name.to_string_lossy());
         let file_bytes = package_reader.decompress_file(pfi)?;
-        let mut lsf_reader = Cursor::new(&file_bytes[..]);
+        self.read_bytes(&file_bytes)
+    }
+
+    /// Reads a resource from the (decompressed) bytes of an LSF file.
+    pub fn read_bytes(&mut self, bytes: &[u8]) -> Result<Resource, String> {
+        self.lenient = false;
+        let (resource, _) = self.read_resource(bytes)?;
+        Ok(resource)
+    }
+
+    /// Reads the resource like `read`, but keeps going past broken names, nodes and
+    /// attributes instead of failing. Broken attributes are skipped, nodes with a broken
+    /// name or parent are kept under a placeholder name or left detached from the tree.
+    /// Every problem is reported in the returned diagnostics.
+    ///
+    /// Errors are still returned when the file headers or sections cannot be read at all.
+    pub fn read_lenient(
+        &mut self,
+        package_reader: &mut PackageReader,
+        pfi: &PackagedFileInfo,
+    ) -> Result<(Resource, Vec<LSFDiagnostic>), String> {
+        println!("Reading LSF file {}", pfi.name.to_string_lossy());
+        let file_bytes = package_reader.decompress_file(pfi)?;
+        self.read_bytes_lenient(&file_bytes)
+    }
+
+    /// Lenient counterpart of `read_bytes`, see `read_lenient`.
+    pub fn read_bytes_lenient(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<(Resource, Vec<LSFDiagnostic>), String> {
+        self.lenient = true;
+        self.read_resource(bytes)
+    }
+
+    /// Reads the headers and tables of an LSF file, but builds no node: nodes and their
+    /// attribute values are only decoded when first accessed through the returned view.
+    /// The tables are moved into the view, which leaves this reader empty.
+    pub fn read_lazy(
+        &mut self,
+        package_reader: &mut PackageReader,
+        pfi: &PackagedFileInfo,
+    ) -> Result<LazyResource, String> {
+        println!("Reading LSF file {} (lazily)", pfi.name.to_string_lossy());
+        let file_bytes = package_reader.decompress_file(pfi)?;
+        self.read_bytes_lazy(&file_bytes)
+    }
+
+    /// Lazy counterpart of `read_bytes`, see `read_lazy`.
+    pub fn read_bytes_lazy(&mut self, bytes: &[u8]) -> Result<LazyResource, String> {
+        self.lenient = false;
+        self.read_tables(bytes, &mut vec![])?;
+        LazyResource::new(std::mem::take(self))
+    }
+
+    /// Walks the resource depth-first, calling `visitor` for every region, node and attribute,
+    /// straight from the node and attribute tables: no node is built along the way.
+    pub fn visit<V: LSFVisitor>(
+        &mut self,
+        package_reader: &mut PackageReader,
+        pfi: &PackagedFileInfo,
+        visitor: &mut V,
+    ) -> Result<(), String> {
+        println!("Visiting LSF file {}", pfi.name.to_string_lossy());
+        let file_bytes = package_reader.decompress_file(pfi)?;
+        self.visit_bytes(&file_bytes, visitor)
+    }
+
+    /// Visitor counterpart of `read_bytes`, see `visit`.
+    pub fn visit_bytes<V: LSFVisitor>(
+        &mut self,
+        bytes: &[u8],
+        visitor: &mut V,
+    ) -> Result<(), String> {
+        self.lenient = false;
+        self.read_tables(bytes, &mut vec![])?;
+        self.visiting = true;
+        let result = lsf_visitor::walk(self, visitor);
+        self.visiting = false;
+        result
+    }
+
+    fn read_resource(&mut self, bytes: &[u8]) -> Result<(Resource, Vec<LSFDiagnostic>), String> {
+        let mut diagnostics = vec![];
+        self.read_tables(bytes, &mut diagnostics)?;
+
+        let mut values_stream = Cursor::new(&self.values[..]);
+        let regions = self.read_regions(&mut values_stream, &mut diagnostics)?;
+
+        let mut resource = Resource {
+            regions,
+            ..Default::default()
+        };
+
+        resource.metadata.game_version = self.game_version;
+
+        Ok((resource, diagnostics))
+    }
+
+    /// Reads the headers, then decodes the names, node and attribute tables and the values buffer.
+    fn read_tables(
+        &mut self,
+        bytes: &[u8],
+        diagnostics: &mut Vec<LSFDiagnostic>,
+    ) -> Result<(), String> {
+        let mut lsf_reader = Cursor::new(bytes);
 
         self.read_headers(&mut lsf_reader)?;
 
@@ -44,7 +161,7 @@ impl LSFReader {
                 false,
             )?;
             let mut names_stream = Cursor::new(&names_bytes[..]);
-            self.read_names(&mut names_stream)?
+            self.read_names(&mut names_stream, diagnostics)?
         };
 
         self.node_infos = {
@@ -55,20 +172,10 @@ impl LSFReader {
                 true,
             )?;
 
-            let mut nodes_stream = Cursor::new(&nodes_bytes[..]);
-
-            let long_nodes = self
-                .version
-                .as_ref()
-                .is_some_and(|v| *v >= LSFVersion::VerExtendedNodes)
-                && self.metadata.has_sibling_data == 1;
-
-            if long_nodes {
-                println!("v3 nodes");
-                self.read_nodes::<LSFNodeEntryV3>(&mut nodes_stream)?
+            if self.has_long_entries() {
+                self.read_nodes::<LSFNodeEntryV3>(&nodes_bytes, diagnostics)?
             } else {
-                println!("v2 nodes");
-                self.read_nodes::<LSFNodeEntryV2>(&mut nodes_stream)?
+                self.read_nodes::<LSFNodeEntryV2>(&nodes_bytes, diagnostics)?
             }
         };
 
@@ -80,22 +187,14 @@ impl LSFReader {
                 true,
             )?;
 
-            let mut attributes_stream = Cursor::new(&attributes_bytes[..]);
-            let has_sibling_data = self
-                .version
-                .as_ref()
-                .is_some_and(|v| *v >= LSFVersion::VerExtendedNodes)
-                && self.metadata.has_sibling_data == 1;
-
-            if has_sibling_data {
-                println!("v3 attributes");
-                self.read_attributes_v3(&mut attributes_stream)?
+            if self.has_long_entries() {
+                self.read_attributes_v3(&attributes_bytes, diagnostics)?
             } else {
-                println!("v2 attributes");
-                self.read_attributes_v2(&mut attributes_stream)?
+                self.read_attributes_v2(&attributes_bytes, diagnostics)?
             }
         };
 
+        self.value_strings.get_mut().clear();
         self.values = self.decompress(
             &mut lsf_reader,
             self.metadata.values_size_on_disk as usize,
@@ -103,25 +202,33 @@ impl LSFReader {
             true,
         )?;
 
-        let mut values_stream = Cursor::new(&self.values[..]);
-        let regions = self.read_regions(&mut values_stream)?;
-
-        let mut resource = Resource {
-            regions,
-            ..Default::default()
-        };
-
-        resource.metadata.game_version = self.game_version;
+        Ok(())
+    }
 
-        Ok(resource)
+    /// Fails with the diagnostic's reason in strict mode, records it in lenient mode.
+    fn report(
+        &self,
+        diagnostics: &mut Vec<LSFDiagnostic>,
+        diagnostic: LSFDiagnostic,
+    ) -> Result<(), String> {
+        if self.lenient {
+            diagnostics.push(diagnostic);
+            Ok(())
+        } else {
+            Err(diagnostic.reason)
+        }
     }
 
-    fn read_regions(&self, stream: &mut Cursor<&[u8]>) -> Result<RegionArena, String> {
+    fn read_regions(
+        &self,
+        stream: &mut Cursor<&[u8]>,
+        diagnostics: &mut Vec<LSFDiagnostic>,
+    ) -> Result<RegionArena, String> {
         let mut node_instances: Vec<Node> = Vec::with_capacity(self.node_infos.len());
-        let mut regions: BTreeMap<String, usize> = BTreeMap::new();
+        let mut regions: BTreeMap<Arc<str>, usize> = BTreeMap::new();
 
-        for node_info in self.node_infos.iter() {
-            let node_data = self.read_node(node_info, stream)?;
+        for (node_idx, node_info) in self.node_infos.iter().enumerate() {
+            let node_data = self.read_node(node_idx, node_info, stream, diagnostics)?;
             let node_name = node_data.name;
 
             if let Some(parent_index) = node_info.parent_index {
@@ -138,18 +245,29 @@ impl LSFReader {
                     name: node_name.clone(),
                     parent,
                     children: Default::default(),
+                    embedded: Default::default(),
                 };
 
-                let node_idx = node_instances.len();
                 node_instances.push(node);
-                node_instances
+                if let Some(parent_node) = node_instances
                     .get_mut(parent_idx)
-                    .ok_or_else(|| {
-                        format!(
-                            "could not find parent node at index {parent_index} in node_instances"
-                        )
-                    })?
-                    .append_child(&node_name, node_idx);
+                    .filter(|_| parent_idx < node_idx)
+                {
+                    parent_node.append_child(&node_name, node_idx);
+                } else {
+                    // The node stays in the arena so that indices keep matching the node table
+                    self.report(
+                        diagnostics,
+                        LSFDiagnostic::new(
+                            LSFSection::Nodes,
+                            node_idx,
+                            self.node_table_offset(node_idx),
+                            format!(
+                                "could not find parent node at index {parent_index} in node_instances"
+                            ),
+                        ),
+                    )?;
+                }
             } else {
                 let kind = NodeKind::Region {
                     name: node_name.clone(),
@@ -160,6 +278,7 @@ impl LSFReader {
                     name: node_name.clone(),
                     parent: None,
                     children: Default::default(),
+                    embedded: Default::default(),
                 };
 
                 let node_idx = node_instances.len();
@@ -176,28 +295,28 @@ impl LSFReader {
         Ok(regions)
     }
 
-    fn read_node(
+    pub(crate) fn read_node(
         &self,
+        node_idx: usize,
         defn: &LSFNodeInfo,
         stream: &mut Cursor<&[u8]>,
+        diagnostics: &mut Vec<LSFDiagnostic>,
     ) -> Result<NodeData, String> {
-        let name = self
-            .names
-            .get(defn.name_index as usize)
-            .ok_or_else(|| {
-                format!(
-                    "failed getting node name collection at name_index {}",
-                    defn.name_index
-                )
-            })?
-            .get(defn.name_offset as usize)
-            .ok_or_else(|| {
-                format!(
-                    "failed getting node name at name_offset {}",
-                    defn.name_offset
-                )
-            })?
-            .clone();
+        let name = match self.get_name(defn.name_index, defn.name_offset, "node") {
+            Ok(name) => name.clone(),
+            Err(reason) => {
+                self.report(
+                    diagnostics,
+                    LSFDiagnostic::new(
+                        LSFSection::Nodes,
+                        node_idx,
+                        self.node_table_offset(node_idx),
+                        reason,
+                    ),
+                )?;
+                format!("<invalid name {}:{}>", defn.name_index, defn.name_offset).into()
+            }
+        };
 
         let first_attribute_index = if let Some(idx) = defn.first_attribute_index {
             idx
@@ -208,64 +327,154 @@ impl LSFReader {
             });
         };
 
-        let mut attribute = self.attributes.get(first_attribute_index).ok_or_else(|| {
-            format!(
-                "failed getting LSFAttributeInfo at first_attribute_index {first_attribute_index}"
-            )
-        })?;
-
         let mut attributes = HashMap::with_capacity(10);
+        let mut attribute_idx = first_attribute_index;
+        let mut reason = "first_attribute_index";
+        let mut referrer_offset = self.node_table_offset(node_idx);
+        let mut chain_len = 0;
 
         loop {
-            stream
-                .seek(SeekFrom::Start(attribute.data_offset as u64))
-                .map_err(|e| {
-                    format!(
-                        "failed seeking attribute data in values at data_offset {}: {e}",
-                        attribute.data_offset,
-                    )
-                })?;
-
-            let type_id_enum: DataType = attribute.type_id.into();
-            let value = self.read_attribute(type_id_enum, stream, attribute.length)?;
+            let Some(attribute) = self.attributes.get(attribute_idx) else {
+                self.report(
+                    diagnostics,
+                    LSFDiagnostic::new(
+                        LSFSection::Attributes,
+                        attribute_idx,
+                        referrer_offset,
+                        format!("failed getting LSFAttributeInfo at {reason} {attribute_idx}"),
+                    ),
+                )?;
+                break;
+            };
 
-            let attr_name = self
-                .names
-                .get(attribute.name_index as usize)
-                .ok_or_else(|| {
-                    format!(
-                        "failed getting attribute name collection at name_index {}",
-                        attribute.name_index
-                    )
-                })?
-                .get(attribute.name_offset as usize)
-                .ok_or_else(|| {
-                    format!(
-                        "failed getting attribute name at name_offset {}",
-                        attribute.name_offset
-                    )
-                })?
-                .clone();
+            match self.read_node_attribute(attribute, stream) {
+                Ok((attr_name, value)) => {
+                    attributes.insert(attr_name, value);
+                }
+                Err((section, reason)) => {
+                    let offset = match section {
+                        LSFSection::Values => attribute.data_offset as u64,
+                        _ => self.attribute_table_offset(attribute_idx),
+                    };
+                    self.report(
+                        diagnostics,
+                        LSFDiagnostic::new(section, attribute_idx, offset, reason),
+                    )?;
+                }
+            }
 
-            attributes.insert(attr_name, value);
+            // Guard against attribute chains looping back on themselves
+            chain_len += 1;
+            if chain_len > self.attributes.len() {
+                self.report(
+                    diagnostics,
+                    LSFDiagnostic::new(
+                        LSFSection::Attributes,
+                        attribute_idx,
+                        self.attribute_table_offset(attribute_idx),
+                        format!("attribute chain of node {node_idx} loops on itself"),
+                    ),
+                )?;
+                break;
+            }
 
             if let Some(next_attribute_idx) = attribute.next_attribute_index {
-                attribute = self.attributes.get(next_attribute_idx).ok_or_else(|| {
-                    format!(
-                        "failed getting LSFAttributeInfo at next_attribute_idx {next_attribute_idx}"
-                    )
-                })?;
+                referrer_offset = self.attribute_table_offset(attribute_idx);
+                attribute_idx = next_attribute_idx;
+                reason = "next_attribute_idx";
             } else {
                 break;
             }
         }
 
+        // Most nodes have fewer attributes than reserved, and the resource keeps every map
+        attributes.shrink_to_fit();
         Ok(NodeData {
             name,
             attributes: Some(attributes),
         })
     }
 
+    pub(crate) fn read_node_attribute(
+        &self,
+        attribute: &LSFAttributeInfo,
+        stream: &mut Cursor<&[u8]>,
+    ) -> Result<(Arc<str>, NodeAttribute), (LSFSection, String)> {
+        let attr_name = self
+            .get_name(attribute.name_index, attribute.name_offset, "attribute")
+            .map_err(|e| (LSFSection::Attributes, e))?
+            .clone();
+
+        stream
+            .seek(SeekFrom::Start(attribute.data_offset as u64))
+            .map_err(|e| {
+                format!(
+                    "failed seeking attribute data in values at data_offset {}: {e}",
+                    attribute.data_offset,
+                )
+            })
+            .and_then(|_| self.read_attribute(attribute.type_id, stream, attribute.length))
+            .map(|value| (attr_name, value))
+            .map_err(|e| (LSFSection::Values, e))
+    }
+
+    /// Shared copy of a string value borrowed from the values buffer. Only the first
+    /// occurrence of each value is allocated.
+    fn intern_value(&self, value: &str) -> Arc<str> {
+        if self.visiting {
+            return value.into();
+        }
+        let mut value_strings = self.value_strings.borrow_mut();
+        if let Some(shared) = value_strings.get(value) {
+            return shared.clone();
+        }
+        let shared: Arc<str> = value.into();
+        value_strings.insert(shared.clone());
+        shared
+    }
+
+    fn has_long_entries(&self) -> bool {
+        self.version
+            .as_ref()
+            .is_some_and(|v| *v >= LSFVersion::VerExtendedNodes)
+            && self.metadata.has_sibling_data == 1
+    }
+
+    /// Byte offset of a node entry in the (uncompressed) node table.
+    fn node_table_offset(&self, node_idx: usize) -> u64 {
+        let entry_size = if self.has_long_entries() {
+            LSFNodeEntryV3::SIZE
+        } else {
+            LSFNodeEntryV2::SIZE
+        };
+        (node_idx * entry_size) as u64
+    }
+
+    /// Byte offset of an attribute entry in the (uncompressed) attribute table.
+    fn attribute_table_offset(&self, attribute_idx: usize) -> u64 {
+        let entry_size = if self.has_long_entries() {
+            LSFAttributeEntryV3::SIZE
+        } else {
+            LSFAttributeEntryV2::SIZE
+        };
+        (attribute_idx * entry_size) as u64
+    }
+
+    pub(crate) fn get_name(
+        &self,
+        name_index: i32,
+        name_offset: i32,
+        kind: &str,
+    ) -> Result<&Arc<str>, String> {
+        self.names
+            .get(name_index as usize)
+            .ok_or_else(|| {
+                format!("failed getting {kind} name collection at name_index {name_index}")
+            })?
+            .get(name_offset as usize)
+            .ok_or_else(|| format!("failed getting {kind} name at name_offset {name_offset}"))
+    }
+
     fn decompress(
         &self,
         stream: &mut Cursor<&[u8]>,
@@ -369,31 +578,27 @@ impl LSFReader {
         Ok(())
     }
 
-    fn read_names(&self, stream: &mut Cursor<&[u8]>) -> Result<Vec<Vec<String>>, String> {
+    fn read_names(
+        &self,
+        stream: &mut Cursor<&[u8]>,
+        diagnostics: &mut Vec<LSFDiagnostic>,
+    ) -> Result<Vec<Vec<Arc<str>>>, String> {
         let mut num_hash_entries = stream
             .read_u32()
             .map_err(|e| format!("failed reading number of hash entries: {e}"))?;
 
         let mut names = Vec::with_capacity(num_hash_entries as usize);
         while num_hash_entries > 0 {
-            let mut num_strings = stream
-                .read_u16()
-                .map_err(|e| format!("failed reading number of strings: {e}"))?;
-
-            let mut hash = Vec::with_capacity(num_strings as usize);
-
-            while num_strings > 0 {
-                num_strings -= 1;
-                let name_len = stream
-                    .read_u16()
-                    .map_err(|e| format!("failed reading name length: {e}"))?;
-
-                let mut name_bytes = vec![0u8; name_len as usize];
-                stream
-                    .read_exact(&mut name_bytes)
-                    .map_err(|e| format!("failed to read {name_len}-bytes long name: {e}"))?;
-                let name = String::from_utf8_lossy(&name_bytes);
-                hash.push(name.to_string());
+            let hash_offset = stream.position();
+            let mut hash = vec![];
+            // A truncated hash bucket keeps the names read so far, and ends the table
+            if let Err(reason) = Self::read_name_hash(stream, &mut hash) {
+                names.push(hash);
+                self.report(
+                    diagnostics,
+                    LSFDiagnostic::new(LSFSection::Names, names.len() - 1, hash_offset, reason),
+                )?;
+                break;
             }
 
             names.push(hash);
@@ -403,80 +608,84 @@ impl LSFReader {
         Ok(names)
     }
 
-    fn read_nodes<T>(&self, stream: &mut Cursor<&[u8]>) -> Result<Vec<LSFNodeInfo>, String>
-    where
-        T: DeserializeOwned + Into<LSFNodeInfo>,
-    {
-        let stream_len = stream
-            .seek(SeekFrom::End(0))
-            .map_err(|e| format!("failed to seek last byte in node stream: {e}"))?;
+    fn read_name_hash(stream: &mut Cursor<&[u8]>, hash: &mut Vec<Arc<str>>) -> Result<(), String> {
+        let mut num_strings = stream
+            .read_u16()
+            .map_err(|e| format!("failed reading number of strings: {e}"))?;
 
-        stream
-            .rewind()
-            .map_err(|e| format!("failed to rewind node stream: {e}"))?;
+        hash.reserve(num_strings as usize);
 
-        let struct_size = std::mem::size_of::<T>();
-        let deserialize_count = stream_len as usize / struct_size;
-
-        let mut node_infos = Vec::with_capacity(deserialize_count);
+        while num_strings > 0 {
+            num_strings -= 1;
+            let name_len = stream
+                .read_u16()
+                .map_err(|e| format!("failed reading name length: {e}"))?;
 
-        while stream.position() < stream_len {
-            let reader = BufReader::new(&mut *stream);
-            let item: T = bincode::serde::decode_from_reader(reader, bincode::config::legacy())
-                .map_err(|e| format!("failed to read LSFNodeEntry bytes: {e}"))?;
-            let resolved = item.into();
-            node_infos.push(resolved);
+            let name_bytes = read_slice(stream, name_len as usize)
+                .map_err(|e| format!("failed to read {name_len}-bytes long name: {e}"))?;
+            hash.push(String::from_utf8_lossy(name_bytes).into());
         }
 
-        Ok(node_infos)
+        Ok(())
+    }
+
+    fn read_nodes<T>(
+        &self,
+        bytes: &[u8],
+        diagnostics: &mut Vec<LSFDiagnostic>,
+    ) -> Result<Vec<LSFNodeInfo>, String>
+    where
+        T: FixedSizeRecord + Into<LSFNodeInfo>,
+    {
+        let (entries, remainder) = record::decode_records::<T>(bytes);
+        self.report_truncated_entry(
+            LSFSection::Nodes,
+            entries.len(),
+            T::SIZE,
+            remainder,
+            diagnostics,
+        )?;
+
+        Ok(entries.into_iter().map(Into::into).collect())
     }
 
     fn read_attributes_v3(
         &self,
-        stream: &mut Cursor<&[u8]>,
+        bytes: &[u8],
+        diagnostics: &mut Vec<LSFDiagnostic>,
     ) -> Result<Vec<LSFAttributeInfo>, String> {
-        let stream_len = stream
-            .seek(SeekFrom::End(0))
-            .map_err(|e| format!("failed to seek last byte in attribute v3 stream: {e}"))?;
-
-        stream
-            .rewind()
-            .map_err(|e| format!("failed to rewind attribute v3 stream: {e}"))?;
-
-        let mut attributes = vec![];
-        while stream.position() < stream_len {
-            let item: LSFAttributeEntryV3 =
-                bincode::decode_from_std_read(stream.by_ref(), bincode::config::legacy())
-                    .map_err(|e| format!("failed to read LSFAttributeEntryV3 bytes: {e}"))?;
-            attributes.push(item.into());
-        }
+        let (entries, remainder) = record::decode_records::<LSFAttributeEntryV3>(bytes);
+        self.report_truncated_entry(
+            LSFSection::Attributes,
+            entries.len(),
+            LSFAttributeEntryV3::SIZE,
+            remainder,
+            diagnostics,
+        )?;
 
-        Ok(attributes)
+        Ok(entries.into_iter().map(Into::into).collect())
     }
 
     fn read_attributes_v2(
         &self,
-        stream: &mut Cursor<&[u8]>,
+        bytes: &[u8],
+        diagnostics: &mut Vec<LSFDiagnostic>,
     ) -> Result<Vec<LSFAttributeInfo>, String> {
-        let stream_len = stream
-            .seek(SeekFrom::End(0))
-            .map_err(|e| format!("failed to seek last byte in attribute v2 stream: {e}"))?;
-
-        stream
-            .rewind()
-            .map_err(|e| format!("failed to rewind attribute v2 stream: {e}"))?;
+        let (entries, remainder) = record::decode_records::<LSFAttributeEntryV2>(bytes);
+        self.report_truncated_entry(
+            LSFSection::Attributes,
+            entries.len(),
+            LSFAttributeEntryV2::SIZE,
+            remainder,
+            diagnostics,
+        )?;
 
         let mut prev_attribute_refs: Vec<Option<usize>> = vec![];
         let mut data_offset = 0;
-        let mut index = 0;
-
-        let mut attributes: Vec<LSFAttributeInfo> = vec![];
 
-        while stream.position() < stream_len {
-            let attribute: LSFAttributeEntryV2 =
-                bincode::decode_from_std_read(stream.by_ref(), bincode::config::legacy())
-                    .map_err(|e| format!("failed to read LSFAttributeEntryV2 bytes: {e}"))?;
+        let mut attributes: Vec<LSFAttributeInfo> = Vec::with_capacity(entries.len());
 
+        for (index, attribute) in entries.into_iter().enumerate() {
             let resolved = LSFAttributeInfo {
                 name_index: (attribute.name_hash_table_index >> 16) as i32,
                 name_offset: (attribute.name_hash_table_index & 0xffff) as i32,
@@ -489,33 +698,60 @@ impl LSFReader {
             let node_index = attribute.node_index + 1;
             if prev_attribute_refs.len() > node_index as usize {
                 if let Some(prev_ref) = prev_attribute_refs.get_mut(node_index as usize) {
-                    if let Some(prev_ref) = prev_ref {
-                        if let Some(prev_att) = attributes.get_mut(*prev_ref) {
-                            prev_att.next_attribute_index = Some(index);
-                        }
+                    if let Some(prev_ref) = prev_ref
+                        && let Some(prev_att) = attributes.get_mut(*prev_ref)
+                    {
+                        prev_att.next_attribute_index = Some(index);
                     }
                     *prev_ref = Some(index);
                 }
             } else {
                 let padding_len = node_index as usize - prev_attribute_refs.len();
-                prev_attribute_refs.extend(std::iter::repeat(None).take(padding_len));
+                prev_attribute_refs.extend(std::iter::repeat_n(None, padding_len));
                 prev_attribute_refs.push(Some(index));
             }
 
             data_offset += resolved.length;
             attributes.push(resolved);
-            index += 1;
         }
 
         Ok(attributes)
     }
 
+    /// Reports the bytes left after the last whole entry of a table, if any.
+    fn report_truncated_entry(
+        &self,
+        section: LSFSection,
+        entry_count: usize,
+        entry_size: usize,
+        remainder: &[u8],
+        diagnostics: &mut Vec<LSFDiagnostic>,
+    ) -> Result<(), String> {
+        if remainder.is_empty() {
+            return Ok(());
+        }
+
+        self.report(
+            diagnostics,
+            LSFDiagnostic::new(
+                section,
+                entry_count,
+                (entry_count * entry_size) as u64,
+                format!(
+                    "truncated {section:?} entry: {} trailing bytes, expected {entry_size}",
+                    remainder.len()
+                ),
+            ),
+        )
+    }
+
     fn read_attribute(
         &self,
-        type_id: DataType,
+        raw_type_id: u32,
         stream: &mut Cursor<&[u8]>,
         length: u32,
     ) -> Result<NodeAttribute, String> {
+        let type_id: DataType = raw_type_id.into();
         let attr_val = match type_id {
             DataType::String
             | DataType::Path
@@ -523,8 +759,8 @@ impl LSFReader {
             | DataType::LSString
             | DataType::WString
             | DataType::LSWString => {
-                let value: String = read_string(stream, length)?;
-                NodeAttributeValue::String(value)
+                let value = read_str(stream, length)?;
+                NodeAttributeValue::String(self.intern_value(value))
             }
 
             DataType::TranslatedString => {
@@ -560,7 +796,7 @@ impl LSFReader {
             }
 
             DataType::TranslatedFSString => {
-                let value = read_translated_fs_string(stream, self.version)?;
+                let value = read_translated_fs_string(stream, self.version, 0)?;
                 NodeAttributeValue::TranslatedFSString(value)
             }
 
@@ -666,10 +902,19 @@ impl LSFReader {
                 NodeAttributeValue::Uuid(value)
             }
             DataType::None => NodeAttributeValue::None,
-            _ => {
-                return Err(format!(
-                    "read_attribute not inplemented for type id {type_id:?}"
-                ));
+            DataType::Unknown => {
+                // Types added by newer game versions are kept as-is, so that they survive a round trip
+                let mut bytes = vec![0; length as usize];
+                stream.read_exact(&mut bytes).map_err(|e| {
+                    format!(
+                        "failed to read raw attribute value of unknown type id {raw_type_id} (length: {length}): {e}"
+                    )
+                })?;
+
+                NodeAttributeValue::Raw {
+                    type_id: raw_type_id,
+                    bytes,
+                }
             }
         };
 
@@ -682,46 +927,80 @@ impl LSFReader {
     }
 }
 
-fn read_string(stream: &mut Cursor<&[u8]>, length: u32) -> Result<String, String> {
-    let mut bytes = vec![0; length as usize];
-    stream
-        .read_exact(&mut bytes)
+/// Borrows the next `length` bytes of the underlying buffer, and advances past them.
+fn read_slice<'a>(stream: &mut Cursor<&'a [u8]>, length: usize) -> Result<&'a [u8], String> {
+    let buffer: &'a [u8] = stream.get_ref();
+    let start = stream.position() as usize;
+    let bytes = start
+        .checked_add(length)
+        .and_then(|end| buffer.get(start..end))
+        .ok_or_else(|| {
+            format!(
+                "unexpected end of buffer ({} bytes) at position {start}",
+                buffer.len()
+            )
+        })?;
+    stream.set_position((start + length) as u64);
+    Ok(bytes)
+}
+
+/// String in the values buffer, without its trailing null bytes.
+fn read_str<'a>(stream: &mut Cursor<&'a [u8]>, length: u32) -> Result<&'a str, String> {
+    let bytes = read_slice(stream, length as usize)
         .map_err(|e| format!("could not read {length} bytes from attribute reader: {e}"))?;
 
     // Remove trailing null bytes if present
-    if let Some(last_index) = bytes.iter().rposition(|&byte| byte != 0) {
-        bytes.truncate(last_index + 1);
-    }
+    let end = bytes
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |i| i + 1);
 
-    // Convert bytes to UTF-8 string
-    String::from_utf8(bytes).map_err(|e| format!("error converting bytes to UTF8 string: {e}"))
+    std::str::from_utf8(&bytes[..end])
+        .map_err(|e| format!("error converting bytes to UTF8 string: {e}"))
+}
+
+fn read_string(stream: &mut Cursor<&[u8]>, length: u32) -> Result<String, String> {
+    read_str(stream, length).map(str::to_string)
 }
+
+/// Arguments of translated strings are translated strings in turn; deeper nesting than this
+/// is taken to be corrupt data.
+const MAX_FS_STRING_DEPTH: usize = 32;
+
 fn read_translated_fs_string(
     stream: &mut Cursor<&[u8]>,
     version: Option<LSFVersion>,
+    depth: usize,
 ) -> Result<TranslatedFSString, String> {
+    if depth > MAX_FS_STRING_DEPTH {
+        return Err(format!(
+            "translated string arguments are nested more than {MAX_FS_STRING_DEPTH} levels deep"
+        ));
+    }
+
     let mut str_version = 0;
     let mut value = None;
     if version.is_some_and(|v| v >= LSFVersion::VerBG3) {
         str_version = stream.read_u16()?;
     } else {
-        let value_length = stream.read_i32()?;
-        value = Some(read_string(stream, value_length as u32)?);
+        let value_length = read_fs_string_length(stream, "value")?;
+        value = Some(read_string(stream, value_length)?);
     }
 
-    let handle_length = stream.read_i32()?;
-    let handle = read_string(stream, handle_length as u32)?;
+    let handle_length = read_fs_string_length(stream, "handle")?;
+    let handle = read_string(stream, handle_length)?;
 
-    let arguments_len = stream.read_i32()? as usize;
-    let mut arguments = Vec::with_capacity(arguments_len);
+    // The count is untrusted, so arguments are only allocated as they are read
+    let arguments_len = read_fs_string_length(stream, "argument count")?;
+    let mut arguments = vec![];
     for _ in 0..arguments_len {
-        let arg_key_length = stream.read_i32()?;
-        let key = read_string(stream, arg_key_length as u32)?;
+        let arg_key_length = read_fs_string_length(stream, "argument key")?;
+        let key = read_string(stream, arg_key_length)?;
 
-        let arg_string = read_translated_fs_string(stream, version)?;
+        let arg_string = read_translated_fs_string(stream, version, depth + 1)?;
 
-        let arg_value_length = stream.read_i32()?;
-        let value = read_string(stream, arg_value_length as u32)?;
+        let arg_value_length = read_fs_string_length(stream, "argument value")?;
+        let value = read_string(stream, arg_value_length)?;
 
         let arg = TranslatedFSStringArgument {
             key,
@@ -740,34 +1019,42 @@ fn read_translated_fs_string(
     Ok(TranslatedFSString { base, arguments })
 }
 
-#[derive(Default, Debug, PartialEq, Deserialize)]
+fn read_fs_string_length(stream: &mut Cursor<&[u8]>, what: &str) -> Result<u32, String> {
+    let length = stream.read_i32()?;
+    u32::try_from(length).map_err(|_| format!("negative translated string {what}: {length}"))
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
 pub enum NodeKind {
     #[default]
     Node,
     Region {
-        name: String,
+        name: Arc<str>,
     },
 }
 
-#[derive(Default, Debug, PartialEq, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
 pub struct Node {
     pub kind: NodeKind,
-    pub name: String,
+    pub name: Arc<str>,
     pub parent: Option<usize>,
-    pub attributes: HashMap<String, NodeAttribute>,
-    pub children: BTreeMap<String, Vec<usize>>,
+    pub attributes: HashMap<Arc<str>, NodeAttribute>,
+    pub children: BTreeMap<Arc<str>, Vec<usize>>,
+    /// Resources decoded from `Bytes` attributes, by attribute name.
+    #[serde(skip)]
+    pub embedded: BTreeMap<Arc<str>, EmbeddedResource>,
 }
 
 impl Node {
-    fn append_child(&mut self, child_name: &str, child_idx: usize) {
+    pub(crate) fn append_child(&mut self, child_name: &Arc<str>, child_idx: usize) {
         self.children
-            .entry(child_name.to_string())
+            .entry(child_name.clone())
             .or_default()
             .push(child_idx);
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
 pub enum DataType {
     None = 0,
     Byte = 1,
@@ -873,40 +1160,136 @@ impl From<u32> for DataType {
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct TranslatedString {
     version: u16,
     value: Option<String>,
     handle: String,
 }
 
+impl TranslatedString {
+    /// Creates a BG3-style translated string, which only refers to its text by handle.
+    pub fn new(handle: String, version: u16) -> Self {
+        Self {
+            version,
+            value: None,
+            handle,
+        }
+    }
+
+    /// Creates a translated string carrying its own text, as found in pre-BG3 resources.
+    pub fn with_value(handle: String, version: u16, value: String) -> Self {
+        Self {
+            version,
+            value: Some(value),
+            handle,
+        }
+    }
+
+    pub fn handle(&self) -> &str {
+        &self.handle
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+}
+
 impl Display for TranslatedString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(val) = self.value.as_ref() {
             f.write_str(val)
         } else {
-            f.write_str("Option::None")
+            f.write_str(&self.handle)
         }
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct TranslatedFSString {
     base: TranslatedString,
     arguments: Vec<TranslatedFSStringArgument>,
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+impl TranslatedFSString {
+    pub fn new(base: TranslatedString, arguments: Vec<TranslatedFSStringArgument>) -> Self {
+        Self { base, arguments }
+    }
+
+    pub fn base(&self) -> &TranslatedString {
+        &self.base
+    }
+
+    pub fn handle(&self) -> &str {
+        self.base.handle()
+    }
+
+    pub fn version(&self) -> u16 {
+        self.base.version()
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        self.base.value()
+    }
+
+    pub fn arguments(&self) -> &[TranslatedFSStringArgument] {
+        &self.arguments
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, TranslatedFSStringArgument> {
+        self.arguments.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a TranslatedFSString {
+    type Item = &'a TranslatedFSStringArgument;
+    type IntoIter = std::slice::Iter<'a, TranslatedFSStringArgument>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Display for TranslatedFSString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.base.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct TranslatedFSStringArgument {
     key: String,
     string: TranslatedFSString,
     value: String,
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+impl TranslatedFSStringArgument {
+    pub fn new(key: String, string: TranslatedFSString, value: String) -> Self {
+        Self { key, string, value }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn string(&self) -> &TranslatedFSString {
+        &self.string
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub enum NodeAttributeValue {
     None,
-    String(String),
+    /// Values read from a file share one allocation per distinct string.
+    String(Arc<str>),
     TranslatedString(TranslatedString),
     TranslatedFSString(TranslatedFSString),
     Bytes(Vec<u8>),
@@ -933,14 +1316,29 @@ pub enum NodeAttributeValue {
     Int64(i64),
     I8(i8),
     Uuid(uuid::Uuid),
+    /// Value of a type this library does not know about, with its on-disk type id and bytes.
+    Raw {
+        type_id: u32,
+        bytes: Vec<u8>,
+    },
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct NodeAttribute {
     pub ty: DataType,
     pub value: NodeAttributeValue,
 }
 
+impl NodeAttribute {
+    /// On-disk type id of the attribute, including ids of unknown types.
+    pub fn type_id(&self) -> u32 {
+        match &self.value {
+            NodeAttributeValue::Raw { type_id, .. } => *type_id,
+            _ => self.ty as u32,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum LSFVersion {
     VerInitial = 0x01,
@@ -988,7 +1386,14 @@ pub struct LSFMetadataV6 {
     has_sibling_data: u32,
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+impl LSFMetadataV6 {
+    pub fn compression_flags(&self) -> u8 {
+        self.compression_flags
+    }
+}
+
+/// Engine or module version. Versions compare field by field, from `major` to `build`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub struct PackedVersion {
     major: u32,
     minor: u32,
@@ -996,12 +1401,57 @@ pub struct PackedVersion {
     build: u32,
 }
 
+impl PackedVersion {
+    pub fn new(major: u32, minor: u32, revision: u32, build: u32) -> Self {
+        Self {
+            major,
+            minor,
+            revision,
+            build,
+        }
+    }
+
+    pub fn major(&self) -> u32 {
+        self.major
+    }
+
+    pub fn minor(&self) -> u32 {
+        self.minor
+    }
+
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    pub fn build(&self) -> u32 {
+        self.build
+    }
+
+    /// 64-bit packed form, as stored in LSF headers and `Version64` attributes.
+    pub fn to_i64(&self) -> i64 {
+        ((self.major as i64 & 0x7f) << 55)
+            | ((self.minor as i64 & 0xff) << 47)
+            | ((self.revision as i64 & 0xffff) << 31)
+            | (self.build as i64 & 0x7fffffff)
+    }
+}
+
 impl Default for PackedVersion {
     fn default() -> Self {
         0.into()
     }
 }
 
+impl Display for PackedVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.revision, self.build
+        )
+    }
+}
+
 impl From<i64> for PackedVersion {
     fn from(packed: i64) -> Self {
         Self {
@@ -1023,6 +1473,9 @@ impl From<i32> for PackedVersion {
     }
 }
 
+// hexadecimal values for "LSOF" signature
+pub(crate) const LSOF_SIGNATURE: [u8; 4] = [0x4C, 0x53, 0x4F, 0x46];
+
 #[derive(Deserialize, Decode)]
 struct LSFMagic {
     magic: [u8; 4],
@@ -1030,15 +1483,68 @@ struct LSFMagic {
 }
 
 impl LSFMagic {
-    const LSOF_SIGNATURE: [u8; 4] = [0x4C, 0x53, 0x4F, 0x46];
+    const LSOF_SIGNATURE: [u8; 4] = LSOF_SIGNATURE;
     const fn signature_u32() -> u32 {
         u32::from_ne_bytes(Self::LSOF_SIGNATURE)
     }
 }
 
-#[derive(PartialEq, Default)]
+/// Total size of the LSF file at the start of `bytes`, computed from its headers.
+/// Returns `None` if `bytes` does not start with a supported LSF header, or is too short.
+pub(crate) fn lsf_file_size(bytes: &[u8]) -> Option<usize> {
+    let mut stream = Cursor::new(bytes);
+    let magic: LSFMagic =
+        bincode::decode_from_std_read(&mut stream, bincode::config::legacy()).ok()?;
+    if magic.magic != LSFMagic::LSOF_SIGNATURE
+        || magic.version < LSFVersion::VerBG3AdditionalBlob as u32
+        || LSFVersion::get(magic.version as u64).is_none()
+    {
+        return None;
+    }
+
+    let engine_version_size = if magic.version >= LSFVersion::VerBG3ExtendedHeader as u32 {
+        8
+    } else {
+        4
+    };
+    stream.seek(SeekFrom::Current(engine_version_size)).ok()?;
+
+    let metadata: LSFMetadataV6 =
+        bincode::decode_from_std_read(&mut stream, bincode::config::legacy()).ok()?;
+    let is_compressed = CompressionMethod::get(metadata.compression_flags)
+        .is_some_and(|c| c != CompressionMethod::None);
+    let section_size = |size_on_disk: u32, uncompressed_size: u32| {
+        if size_on_disk == 0 || !is_compressed {
+            uncompressed_size as usize
+        } else {
+            size_on_disk as usize
+        }
+    };
+
+    let total = stream.position() as usize
+        + section_size(
+            metadata.strings_size_on_disk,
+            metadata.strings_uncompressed_size,
+        )
+        + section_size(
+            metadata.nodes_size_on_disk,
+            metadata.nodes_uncompressed_size,
+        )
+        + section_size(
+            metadata.attributes_size_on_disk,
+            metadata.attributes_uncompressed_size,
+        )
+        + section_size(
+            metadata.values_size_on_disk,
+            metadata.values_uncompressed_size,
+        );
+
+    (total <= bytes.len()).then_some(total)
+}
+
+#[derive(Clone, PartialEq, Default)]
 pub struct RegionArena {
-    pub regions_indices: BTreeMap<String, usize>,
+    pub regions_indices: BTreeMap<Arc<str>, usize>,
     pub node_instances: Vec<Node>,
 }
 
@@ -1056,9 +1562,60 @@ impl RegionArena {
     pub fn region_count(&self) -> usize {
         self.regions_indices.len()
     }
+
+    /// Drops the nodes no longer reachable from a region root, and renumbers the others
+    /// while keeping their relative order.
+    pub(crate) fn remove_unreachable(&mut self) {
+        let mut reachable = vec![false; self.node_instances.len()];
+        let mut stack: Vec<usize> = self.regions_indices.values().copied().collect();
+        while let Some(node_idx) = stack.pop() {
+            let Some(node) = self.node_instances.get(node_idx) else {
+                continue;
+            };
+            if !std::mem::replace(&mut reachable[node_idx], true) {
+                stack.extend(node.children.values().flatten());
+            }
+        }
+
+        let mut new_indices = vec![None; self.node_instances.len()];
+        let mut kept = 0;
+        for (node_idx, is_reachable) in reachable.iter().enumerate() {
+            if *is_reachable {
+                new_indices[node_idx] = Some(kept);
+                kept += 1;
+            }
+        }
+        if kept == self.node_instances.len() {
+            return;
+        }
+
+        let remap = |idx: usize| new_indices.get(idx).copied().flatten();
+        let node_instances = std::mem::take(&mut self.node_instances);
+        self.node_instances = node_instances
+            .into_iter()
+            .zip(reachable)
+            .filter_map(|(node, is_reachable)| is_reachable.then_some(node))
+            .map(|mut node| {
+                node.parent = node.parent.and_then(remap);
+                for children in node.children.values_mut() {
+                    children.retain_mut(|child_idx| match remap(*child_idx) {
+                        Some(new_idx) => {
+                            *child_idx = new_idx;
+                            true
+                        }
+                        None => false,
+                    });
+                }
+                node
+            })
+            .collect();
+        for root_idx in self.regions_indices.values_mut() {
+            *root_idx = remap(*root_idx).unwrap_or(*root_idx);
+        }
+    }
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Resource {
     pub metadata: LSMetadata,
     pub regions: RegionArena,
@@ -1086,7 +1643,7 @@ impl Resource {
     }
 }
 
-#[derive(Default, PartialEq)]
+#[derive(Clone, Default, PartialEq)]
 pub struct LSMetadata {
     pub timestamp: u64,
     pub game_version: PackedVersion,
@@ -1105,13 +1662,26 @@ pub struct LSFNodeInfo {
     pub next_sibling_index: Option<i32>,
 }
 
-#[derive(Deserialize)]
 pub struct LSFNodeEntryV3 {
     name_hash_table_index: u32,
     parent_index: i32,
     next_sibling_index: i32,
     first_attribute_index: i32,
 }
+
+impl FixedSizeRecord for LSFNodeEntryV3 {
+    const SIZE: usize = 16;
+
+    fn decode(bytes: &[u8]) -> Self {
+        Self {
+            name_hash_table_index: record::u32_at(bytes, 0),
+            parent_index: record::i32_at(bytes, 4),
+            next_sibling_index: record::i32_at(bytes, 8),
+            first_attribute_index: record::i32_at(bytes, 12),
+        }
+    }
+}
+
 impl From<LSFNodeEntryV3> for LSFNodeInfo {
     fn from(val: LSFNodeEntryV3) -> Self {
         LSFNodeInfo {
@@ -1132,13 +1702,24 @@ impl From<LSFNodeEntryV3> for LSFNodeInfo {
     }
 }
 
-#[derive(Deserialize)]
 pub struct LSFNodeEntryV2 {
     name_hash_table_index: u32,
     first_attribute_index: i32,
     parent_index: i32,
 }
 
+impl FixedSizeRecord for LSFNodeEntryV2 {
+    const SIZE: usize = 12;
+
+    fn decode(bytes: &[u8]) -> Self {
+        Self {
+            name_hash_table_index: record::u32_at(bytes, 0),
+            first_attribute_index: record::i32_at(bytes, 4),
+            parent_index: record::i32_at(bytes, 8),
+        }
+    }
+}
+
 impl From<LSFNodeEntryV2> for LSFNodeInfo {
     fn from(val: LSFNodeEntryV2) -> Self {
         LSFNodeInfo {
@@ -1183,7 +1764,6 @@ impl From<LSFAttributeEntryV3> for LSFAttributeInfo {
     }
 }
 
-#[derive(Deserialize, Decode)]
 pub struct LSFAttributeEntryV3 {
     pub name_hash_table_index: u32,
     pub type_and_length: u32,
@@ -1191,15 +1771,199 @@ pub struct LSFAttributeEntryV3 {
     pub offset: u32,
 }
 
-#[derive(Deserialize, Decode)]
+impl FixedSizeRecord for LSFAttributeEntryV3 {
+    const SIZE: usize = 16;
+
+    fn decode(bytes: &[u8]) -> Self {
+        Self {
+            name_hash_table_index: record::u32_at(bytes, 0),
+            type_and_length: record::u32_at(bytes, 4),
+            next_attribute_index: record::i32_at(bytes, 8),
+            offset: record::u32_at(bytes, 12),
+        }
+    }
+}
+
 pub struct LSFAttributeEntryV2 {
     pub name_hash_table_index: u32,
     pub type_and_length: u32,
     pub node_index: i32,
 }
 
+impl FixedSizeRecord for LSFAttributeEntryV2 {
+    const SIZE: usize = 12;
+
+    fn decode(bytes: &[u8]) -> Self {
+        Self {
+            name_hash_table_index: record::u32_at(bytes, 0),
+            type_and_length: record::u32_at(bytes, 4),
+            node_index: record::i32_at(bytes, 8),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NodeData {
-    name: String,
-    attributes: Option<HashMap<String, NodeAttribute>>,
+    pub(crate) name: Arc<str>,
+    pub(crate) attributes: Option<HashMap<Arc<str>, NodeAttribute>>,
+}
+
+/// Section of an LSF file a diagnostic refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LSFSection {
+    Names,
+    Nodes,
+    Attributes,
+    Values,
+}
+
+/// Problem found while reading an LSF file in lenient mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LSFDiagnostic {
+    pub section: LSFSection,
+    /// Index of the name hash bucket, node or attribute the problem was found in.
+    pub index: usize,
+    /// Byte offset in the uncompressed section.
+    pub offset: u64,
+    pub reason: String,
+}
+
+impl LSFDiagnostic {
+    fn new(section: LSFSection, index: usize, offset: u64, reason: String) -> Self {
+        Self {
+            section,
+            index,
+            offset,
+            reason,
+        }
+    }
+}
+
+impl Display for LSFDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} #{} (offset {:#X}): {}",
+            self.section, self.index, self.offset, self.reason
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::lsf_with_attributes;
+
+    #[test]
+    fn unknown_type_ids_are_kept_raw() -> Result<(), String> {
+        let bytes = lsf_with_attributes(&[
+            ("Future", 40, vec![1, 2, 3]),
+            ("Level", DataType::Int as u32, 7i32.to_le_bytes().to_vec()),
+        ]);
+        let resource = LSFReader::new().read_bytes(&bytes)?;
+        let node = &resource.regions.node_instances[1];
+        let future = &node.attributes["Future"];
+        assert_eq!(future.ty, DataType::Unknown);
+        assert_eq!(future.type_id(), 40);
+        assert_eq!(
+            future.value,
+            NodeAttributeValue::Raw {
+                type_id: 40,
+                bytes: vec![1, 2, 3]
+            }
+        );
+        assert_eq!(node.attributes["Level"].value, NodeAttributeValue::Int(7));
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_string_values_share_one_allocation() -> Result<(), String> {
+        let string = DataType::FixedString as u32;
+        let bytes = lsf_with_attributes(&[
+            ("A", string, b"Shared\0".to_vec()),
+            ("B", string, b"Shared".to_vec()),
+            ("C", string, b"Other".to_vec()),
+        ]);
+        let resource = LSFReader::new().read_bytes(&bytes)?;
+        let attributes = &resource.regions.node_instances[1].attributes;
+        let value = |name: &str| match &attributes[name].value {
+            NodeAttributeValue::String(value) => Ok(value.clone()),
+            other => Err(format!("{name} is not a string: {other:?}")),
+        };
+        assert_eq!(&*value("A")?, "Shared");
+        assert!(Arc::ptr_eq(&value("A")?, &value("B")?));
+        assert_eq!(&*value("C")?, "Other");
+        Ok(())
+    }
+
+    /// BG3 translated string value with `arguments` given as their raw count, followed by
+    /// `nested` levels of single arguments.
+    fn fs_string_bytes(arguments: i32, nested: usize) -> Vec<u8> {
+        let mut bytes = 1u16.to_le_bytes().to_vec();
+        bytes.extend(2i32.to_le_bytes());
+        bytes.extend(b"h1");
+        if nested == 0 {
+            bytes.extend(arguments.to_le_bytes());
+            return bytes;
+        }
+        bytes.extend(1i32.to_le_bytes());
+        bytes.extend(1i32.to_le_bytes());
+        bytes.extend(b"K");
+        bytes.extend(fs_string_bytes(arguments, nested - 1));
+        bytes.extend(1i32.to_le_bytes());
+        bytes.extend(b"V");
+        bytes
+    }
+
+    #[test]
+    fn nested_fs_string_arguments_are_read() -> Result<(), String> {
+        let bytes = lsf_with_attributes(&[(
+            "Text",
+            DataType::TranslatedFSString as u32,
+            fs_string_bytes(0, 2),
+        )]);
+        let resource = LSFReader::new().read_bytes(&bytes)?;
+        let text = resource.regions.node_instances[1].attributes["Text"]
+            .value
+            .as_translated_fs_string()
+            .ok_or("not a translated string")?;
+        let argument = &text.arguments()[0];
+        assert_eq!((argument.key(), argument.value()), ("K", "V"));
+        assert_eq!(argument.string().arguments().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_fs_strings_become_diagnostics() -> Result<(), String> {
+        let level = ("Level", DataType::Int as u32, 7i32.to_le_bytes().to_vec());
+        for (text, reason) in [
+            (
+                fs_string_bytes(-1, 0),
+                "negative translated string argument count",
+            ),
+            (fs_string_bytes(i32::MAX, 0), "could not read"),
+            (
+                fs_string_bytes(0, MAX_FS_STRING_DEPTH + 1),
+                "nested more than",
+            ),
+        ] {
+            let bytes = lsf_with_attributes(&[
+                ("Text", DataType::TranslatedFSString as u32, text),
+                level.clone(),
+            ]);
+            assert!(LSFReader::new().read_bytes(&bytes).is_err());
+
+            let (resource, diagnostics) = LSFReader::new().read_bytes_lenient(&bytes)?;
+            assert_eq!(diagnostics.len(), 1);
+            assert!(
+                diagnostics[0].reason.contains(reason),
+                "unexpected diagnostic: {}",
+                diagnostics[0]
+            );
+            let attributes = &resource.regions.node_instances[1].attributes;
+            assert!(!attributes.contains_key("Text"));
+            assert_eq!(attributes["Level"].value, NodeAttributeValue::Int(7));
+        }
+        Ok(())
+    }
 }
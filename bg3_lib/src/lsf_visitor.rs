@@ -0,0 +1,223 @@
+use std::io::Cursor;
+
+use crate::lsf_reader::{LSFReader, NodeAttribute};
+
+/// Callbacks of a depth-first walk over an LSF resource, see `LSFReader::visit`.
+///
+/// Every region or node is entered, then its attributes are reported, then its children
+/// are walked, and finally it is exited. All methods do nothing by default.
+pub trait LSFVisitor {
+    /// Called when entering the root node of a region.
+    fn enter_region(&mut self, _name: &str) {}
+
+    /// Called when entering a node below a region root.
+    fn enter_node(&mut self, _name: &str) {}
+
+    /// Called for each attribute of the region or node entered last.
+    fn attribute(&mut self, _name: &str, _value: &NodeAttribute) {}
+
+    /// Called after the attributes and children of a region or node were visited.
+    fn exit_node(&mut self) {}
+}
+
+/// Walks the decoded tables of `reader`, in node table order among siblings.
+pub(crate) fn walk<V: LSFVisitor>(reader: &LSFReader, visitor: &mut V) -> Result<(), String> {
+    let node_count = reader.node_infos.len();
+
+    // Children lists as first child / next sibling links, to keep away from a vector per node
+    let mut first_child = vec![None; node_count];
+    let mut next_sibling = vec![None; node_count];
+    let mut region_roots = vec![];
+
+    for (node_idx, node_info) in reader.node_infos.iter().enumerate().rev() {
+        match node_info.parent_index {
+            // Parents always come before their children in the node table
+            Some(parent_idx) if parent_idx < node_idx => {
+                next_sibling[node_idx] = first_child[parent_idx];
+                first_child[parent_idx] = Some(node_idx);
+            }
+            Some(parent_idx) => {
+                return Err(format!(
+                    "could not find parent node at index {parent_idx} of node {node_idx}"
+                ));
+            }
+            None => region_roots.push(node_idx),
+        }
+    }
+
+    let mut values_stream = Cursor::new(&reader.values[..]);
+
+    for root_idx in region_roots.into_iter().rev() {
+        let mut node_idx = root_idx;
+        enter(reader, node_idx, &mut values_stream, visitor)?;
+
+        'walk: loop {
+            if let Some(child_idx) = first_child[node_idx] {
+                node_idx = child_idx;
+                enter(reader, node_idx, &mut values_stream, visitor)?;
+                continue;
+            }
+
+            // Leaf: exit nodes until one has a next sibling, or the region is done
+            loop {
+                visitor.exit_node();
+                if node_idx == root_idx {
+                    break 'walk;
+                }
+
+                if let Some(sibling_idx) = next_sibling[node_idx] {
+                    node_idx = sibling_idx;
+                    enter(reader, node_idx, &mut values_stream, visitor)?;
+                    break;
+                }
+
+                // Nodes below a region root always have a valid parent, checked above
+                node_idx = reader.node_infos[node_idx].parent_index.unwrap_or(root_idx);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn enter<V: LSFVisitor>(
+    reader: &LSFReader,
+    node_idx: usize,
+    values_stream: &mut Cursor<&[u8]>,
+    visitor: &mut V,
+) -> Result<(), String> {
+    let node_info = &reader.node_infos[node_idx];
+    let name = reader.get_name(node_info.name_index, node_info.name_offset, "node")?;
+
+    if node_info.parent_index.is_some() {
+        visitor.enter_node(name);
+    } else {
+        visitor.enter_region(name);
+    }
+
+    let mut next_attribute_idx = node_info.first_attribute_index;
+    let mut chain_len = 0;
+
+    while let Some(attribute_idx) = next_attribute_idx {
+        let attribute = reader.attributes.get(attribute_idx).ok_or_else(|| {
+            format!("failed getting LSFAttributeInfo at index {attribute_idx} of node {node_idx}")
+        })?;
+
+        let (attr_name, value) = reader
+            .read_node_attribute(attribute, values_stream)
+            .map_err(|(_, reason)| reason)?;
+        visitor.attribute(&attr_name, &value);
+
+        // Guard against attribute chains looping back on themselves
+        chain_len += 1;
+        if chain_len > reader.attributes.len() {
+            return Err(format!(
+                "attribute chain of node {node_idx} loops on itself"
+            ));
+        }
+
+        next_attribute_idx = attribute.next_attribute_index;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsf_reader::Resource;
+    use crate::lsf_writer::LSFWriter;
+    use crate::test_support::sample_resource;
+
+    /// Records the walk as one line per event. The attribute chain order depends on the
+    /// writer's map order, so the attributes of a node are recorded sorted.
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+        attributes: Vec<String>,
+        depth: usize,
+    }
+
+    impl Recorder {
+        fn flush_attributes(&mut self) {
+            self.attributes.sort();
+            self.events.append(&mut self.attributes);
+        }
+    }
+
+    impl LSFVisitor for Recorder {
+        fn enter_region(&mut self, name: &str) {
+            self.flush_attributes();
+            self.events
+                .push(format!("{}region {name}", "  ".repeat(self.depth)));
+            self.depth += 1;
+        }
+
+        fn enter_node(&mut self, name: &str) {
+            self.flush_attributes();
+            self.events
+                .push(format!("{}node {name}", "  ".repeat(self.depth)));
+            self.depth += 1;
+        }
+
+        fn attribute(&mut self, name: &str, value: &NodeAttribute) {
+            let indent = "  ".repeat(self.depth);
+            self.attributes.push(format!("{indent}{name} = {value:?}"));
+        }
+
+        fn exit_node(&mut self) {
+            self.flush_attributes();
+            self.depth -= 1;
+        }
+    }
+
+    fn record_resource(
+        resource: &Resource,
+        node_idx: usize,
+        depth: usize,
+        events: &mut Vec<String>,
+    ) {
+        let node = &resource.regions.node_instances[node_idx];
+        let indent = "  ".repeat(depth);
+        let kind = if node.parent.is_some() {
+            "node"
+        } else {
+            "region"
+        };
+        events.push(format!("{indent}{kind} {}", node.name));
+
+        let mut attributes: Vec<String> = node
+            .attributes
+            .iter()
+            .map(|(name, value)| format!("{indent}  {name} = {value:?}"))
+            .collect();
+        attributes.sort();
+        events.extend(attributes);
+
+        // Siblings are walked in node table order
+        let mut children: Vec<usize> = node.children.values().flatten().copied().collect();
+        children.sort();
+        for child_idx in children {
+            record_resource(resource, child_idx, depth + 1, events);
+        }
+    }
+
+    #[test]
+    fn walk_matches_eager_read() -> Result<(), String> {
+        let bytes = LSFWriter::new().write_bytes(&sample_resource())?;
+        let eager = LSFReader::new().read_bytes(&bytes)?;
+
+        let mut expected = vec![];
+        let mut roots: Vec<usize> = eager.regions.regions_indices.values().copied().collect();
+        roots.sort();
+        for root_idx in roots {
+            record_resource(&eager, root_idx, 0, &mut expected);
+        }
+
+        let mut recorder = Recorder::default();
+        LSFReader::new().visit_bytes(&bytes, &mut recorder)?;
+        assert_eq!(recorder.depth, 0);
+        assert_eq!(recorder.events, expected);
+        Ok(())
+    }
+}
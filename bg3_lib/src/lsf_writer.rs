@@ -0,0 +1,743 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::bin_utils::{self, WriteExt};
+use crate::lsf_reader::{
+    LSFVersion, LSOF_SIGNATURE, Node, NodeAttribute, NodeAttributeValue, Resource,
+    TranslatedFSString, TranslatedString,
+};
+
+/// LZ4 compression, with the default compression level.
+pub const DEFAULT_COMPRESSION_FLAGS: u8 = 0x22;
+
+/// Buckets of the names table. Names are only looked up by position, so any count works.
+const NAME_HASH_BUCKETS: usize = 0x200;
+
+/// Writes resources as LSF files that `LSFReader` reads back.
+///
+/// Nodes are written depth-first from each region, children grouped by name, and attributes
+/// in name order. Embedded resources of a node are re-encoded in place of their attribute;
+/// writing fails if the attribute was changed since the resource was decoded from it.
+///
+/// The versions written store translated strings by handle only, so translated strings
+/// holding their text, as read from pre-BG3 files, are rejected rather than dropped.
+#[derive(Debug, Clone)]
+pub struct LSFWriter {
+    version: LSFVersion,
+    compression_flags: u8,
+}
+
+impl Default for LSFWriter {
+    fn default() -> Self {
+        Self {
+            version: LSFVersion::VerBg3Patch3,
+            compression_flags: DEFAULT_COMPRESSION_FLAGS,
+        }
+    }
+}
+
+/// Sections of an LSF file, before compression.
+#[derive(Default)]
+struct LSFSections {
+    names: Vec<u8>,
+    nodes: Vec<u8>,
+    attributes: Vec<u8>,
+    values: Vec<u8>,
+}
+
+impl LSFWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// LSF version to write; versions before `VerBG3AdditionalBlob` are not supported.
+    pub fn with_version(mut self, version: LSFVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Compression method of the sections, as in package file flags.
+    pub fn with_compression(mut self, compression_flags: u8) -> Self {
+        self.compression_flags = compression_flags;
+        self
+    }
+
+    pub fn write<W: Write>(&self, resource: &Resource, writer: &mut W) -> Result<(), String> {
+        if self.version < LSFVersion::VerBG3AdditionalBlob {
+            return Err(format!(
+                "LSF versions below {} cannot be written (version requested: {})",
+                LSFVersion::VerBG3AdditionalBlob as u32,
+                self.version as u32
+            ));
+        }
+
+        let sections = self.build_sections(resource)?;
+        let names = self.compress_section(&sections.names, false)?;
+        let nodes = self.compress_section(&sections.nodes, true)?;
+        let attributes = self.compress_section(&sections.attributes, true)?;
+        let values = self.compress_section(&sections.values, true)?;
+
+        writer
+            .write_all(&LSOF_SIGNATURE)
+            .map_err(|e| format!("failed writing LSF signature: {e}"))?;
+        writer.write_u32(self.version as u32)?;
+        writer.write_i64(resource.metadata.game_version.to_i64())?;
+
+        writer.write_u32(section_size(sections.names.len())?)?;
+        writer.write_u32(section_size(names.len())?)?;
+        writer.write_u64(0)?;
+        writer.write_u32(section_size(sections.nodes.len())?)?;
+        writer.write_u32(section_size(nodes.len())?)?;
+        writer.write_u32(section_size(sections.attributes.len())?)?;
+        writer.write_u32(section_size(attributes.len())?)?;
+        writer.write_u32(section_size(sections.values.len())?)?;
+        writer.write_u32(section_size(values.len())?)?;
+        writer.write_u8(self.compression_flags)?;
+        writer.write_u8(0)?;
+        writer.write_u16(0)?;
+        // Nodes and attributes are written as long entries, with sibling links
+        writer.write_u32(1)?;
+
+        for (section, compressed) in [
+            (&sections.names, &names),
+            (&sections.nodes, &nodes),
+            (&sections.attributes, &attributes),
+            (&sections.values, &values),
+        ] {
+            let stored = if compressed.is_empty() {
+                section
+            } else {
+                compressed
+            };
+            writer
+                .write_all(stored)
+                .map_err(|e| format!("failed writing LSF section: {e}"))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_bytes(&self, resource: &Resource) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![];
+        self.write(resource, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Compressed bytes of a section, or nothing if it is stored uncompressed: the on-disk
+    /// size is then written as 0, which the reader takes to mean the section is stored as is.
+    fn compress_section(&self, section: &[u8], chunked: bool) -> Result<Vec<u8>, String> {
+        let is_compressed = self.compression_flags & 0x0F != 0;
+        if section.is_empty() || !is_compressed {
+            return Ok(vec![]);
+        }
+        if chunked {
+            bin_utils::compress_chunked(section, self.compression_flags)
+        } else {
+            bin_utils::compress(section, self.compression_flags)
+        }
+    }
+
+    fn build_sections(&self, resource: &Resource) -> Result<LSFSections, String> {
+        let node_order = node_order(resource);
+        let mut written_indices = HashMap::with_capacity(node_order.len());
+        for (written_idx, node_idx) in node_order.iter().enumerate() {
+            written_indices.insert(*node_idx, written_idx);
+        }
+
+        let next_siblings: HashMap<usize, usize> = resource
+            .regions
+            .node_instances
+            .iter()
+            .flat_map(|node| {
+                let siblings: Vec<usize> = children(node).collect();
+                siblings
+                    .windows(2)
+                    .map(|w| (w[0], w[1]))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let mut names = NamesTable::default();
+        let mut sections = LSFSections::default();
+        let mut attribute_count = 0;
+
+        for node_idx in &node_order {
+            let Some(node) = resource.regions.get_node(*node_idx) else {
+                continue;
+            };
+            let parent_index = match node.parent.and_then(|p| written_indices.get(&p)) {
+                Some(parent_idx) => *parent_idx as i32,
+                None => -1,
+            };
+            let next_sibling_index = next_siblings
+                .get(node_idx)
+                .and_then(|sibling| written_indices.get(sibling))
+                .map_or(-1, |sibling| *sibling as i32);
+
+            let mut attribute_names: Vec<&Arc<str>> = node.attributes.keys().collect();
+            attribute_names.sort();
+            let first_attribute_index = if attribute_names.is_empty() {
+                -1
+            } else {
+                attribute_count
+            };
+
+            sections.nodes.write_u32(names.index_of(&node.name)?)?;
+            sections.nodes.write_i32(parent_index)?;
+            sections.nodes.write_i32(next_sibling_index)?;
+            sections.nodes.write_i32(first_attribute_index)?;
+
+            for (i, name) in attribute_names.iter().enumerate() {
+                let Some(attribute) = node.attributes.get(*name) else {
+                    continue;
+                };
+                let offset = section_size(sections.values.len())?;
+                let type_id = attribute.type_id();
+                match (node.embedded.get(*name), &attribute.value) {
+                    (Some(embedded), NodeAttributeValue::Bytes(bytes))
+                        if embedded.is_decoded_from(bytes) =>
+                    {
+                        sections.values.extend(embedded.encode()?)
+                    }
+                    (Some(_), _) => {
+                        return Err(format!(
+                            "attribute {name} of {} was changed after the resource embedded in \
+                             it was decoded; remove the embedded resource to keep the change",
+                            node.name
+                        ));
+                    }
+                    (None, _) => write_value(attribute, &mut sections.values)?,
+                }
+                let length = sections.values.len() as u64 - offset as u64;
+                if length >= 1 << 26 {
+                    return Err(format!(
+                        "attribute {name} of {} is too large: {length} bytes",
+                        node.name
+                    ));
+                }
+                let next_attribute_index = if i + 1 < attribute_names.len() {
+                    attribute_count + 1
+                } else {
+                    -1
+                };
+
+                sections.attributes.write_u32(names.index_of(name)?)?;
+                sections
+                    .attributes
+                    .write_u32(type_id | ((length as u32) << 6))?;
+                sections.attributes.write_i32(next_attribute_index)?;
+                sections.attributes.write_u32(offset)?;
+                attribute_count += 1;
+            }
+        }
+
+        sections.names = names.encode()?;
+        Ok(sections)
+    }
+}
+
+fn write_value(attribute: &NodeAttribute, buf: &mut Vec<u8>) -> Result<(), String> {
+    match &attribute.value {
+        NodeAttributeValue::None => {}
+        NodeAttributeValue::String(value) => {
+            buf.extend_from_slice(value.as_bytes());
+            buf.push(0);
+        }
+        NodeAttributeValue::TranslatedString(value) => {
+            check_no_text(value)?;
+            buf.write_u16(value.version())?;
+            write_length_prefixed(buf, value.handle())?;
+        }
+        NodeAttributeValue::TranslatedFSString(value) => {
+            write_translated_fs_string(buf, value)?;
+        }
+        NodeAttributeValue::Bytes(bytes) | NodeAttributeValue::Raw { bytes, .. } => {
+            buf.extend_from_slice(bytes);
+        }
+        NodeAttributeValue::Byte(value) => buf.write_u8(*value)?,
+        NodeAttributeValue::Short(value) => buf.write_i16(*value)?,
+        NodeAttributeValue::UShort(value) => buf.write_u16(*value)?,
+        NodeAttributeValue::Int(value) => buf.write_i32(*value)?,
+        NodeAttributeValue::UInt(value) => buf.write_u32(*value)?,
+        NodeAttributeValue::Float(value) => buf.write_f32(*value)?,
+        NodeAttributeValue::Double(value) => buf.write_f64(*value)?,
+        NodeAttributeValue::IVec2(value) => write_i32s(buf, value)?,
+        NodeAttributeValue::IVec3(value) => write_i32s(buf, value)?,
+        NodeAttributeValue::IVec4(value) => write_i32s(buf, value)?,
+        NodeAttributeValue::Vec2(value) => write_f32s(buf, value)?,
+        NodeAttributeValue::Vec3(value) => write_f32s(buf, value)?,
+        NodeAttributeValue::Vec4(value) => write_f32s(buf, value)?,
+        NodeAttributeValue::Mat2(value) => write_f32s(buf, value.as_flattened())?,
+        NodeAttributeValue::Mat3(value) => write_f32s(buf, value.as_flattened())?,
+        NodeAttributeValue::Mat3x4(value) => write_f32s(buf, value.as_flattened())?,
+        NodeAttributeValue::Mat4x3(value) => write_f32s(buf, value.as_flattened())?,
+        NodeAttributeValue::Mat4(value) => write_f32s(buf, value.as_flattened())?,
+        NodeAttributeValue::Bool(value) => buf.write_u8(*value as u8)?,
+        NodeAttributeValue::UInt64(value) => buf.write_u64(*value)?,
+        NodeAttributeValue::Int64(value) => buf.write_i64(*value)?,
+        NodeAttributeValue::I8(value) => buf.write_i8(*value)?,
+        NodeAttributeValue::Uuid(value) => buf.extend_from_slice(value.as_bytes()),
+    }
+    Ok(())
+}
+
+/// Names of nodes and attributes, stored in hash buckets and referred to by bucket and position.
+struct NamesTable {
+    buckets: Vec<Vec<Arc<str>>>,
+    indices: HashMap<Arc<str>, u32>,
+}
+
+impl Default for NamesTable {
+    fn default() -> Self {
+        Self {
+            buckets: vec![vec![]; NAME_HASH_BUCKETS],
+            indices: HashMap::new(),
+        }
+    }
+}
+
+impl NamesTable {
+    /// Bucket in the high 16 bits and position in the low ones, adding the name if needed.
+    fn index_of(&mut self, name: &Arc<str>) -> Result<u32, String> {
+        if let Some(index) = self.indices.get(name) {
+            return Ok(*index);
+        }
+
+        // FNV-1a
+        let hash = name.bytes().fold(0x811c9dc5u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        });
+        let bucket_idx = hash as usize % NAME_HASH_BUCKETS;
+        let bucket = &mut self.buckets[bucket_idx];
+        if bucket.len() >= u16::MAX as usize {
+            return Err(format!(
+                "too many names in LSF names table bucket {bucket_idx}"
+            ));
+        }
+
+        let index = ((bucket_idx as u32) << 16) | bucket.len() as u32;
+        bucket.push(name.clone());
+        self.indices.insert(name.clone(), index);
+        Ok(index)
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, String> {
+        let mut buf = vec![];
+        buf.write_u32(self.buckets.len() as u32)?;
+        for bucket in &self.buckets {
+            let count = u16::try_from(bucket.len())
+                .map_err(|_| "too many names in an LSF names table bucket".to_string())?;
+            buf.write_u16(count)?;
+            for name in bucket {
+                let len = u16::try_from(name.len())
+                    .map_err(|_| format!("name is too long for the LSF names table: {name}"))?;
+                buf.write_u16(len)?;
+                buf.extend_from_slice(name.as_bytes());
+            }
+        }
+        Ok(buf)
+    }
+}
+
+/// Indices of the nodes to write: every region, each followed by its descendants, so that
+/// parents come before their children as the reader expects.
+fn node_order(resource: &Resource) -> Vec<usize> {
+    let mut order = Vec::with_capacity(resource.regions.node_instances.len());
+    let mut stack: Vec<usize> = resource
+        .regions
+        .regions_indices
+        .values()
+        .rev()
+        .copied()
+        .collect();
+    while let Some(node_idx) = stack.pop() {
+        let Some(node) = resource.regions.get_node(node_idx) else {
+            continue;
+        };
+        // Guard against children lists looping back on their ancestors
+        if order.len() > resource.regions.node_instances.len() {
+            break;
+        }
+        order.push(node_idx);
+        stack.extend(children(node).rev());
+    }
+    order
+}
+
+fn children(node: &Node) -> impl DoubleEndedIterator<Item = usize> + '_ {
+    node.children.values().flatten().copied()
+}
+
+fn section_size(len: usize) -> Result<u32, String> {
+    u32::try_from(len).map_err(|_| format!("LSF section is too large: {len} bytes"))
+}
+
+/// String with an `i32` length prefix, null terminator included.
+fn write_length_prefixed(buf: &mut Vec<u8>, value: &str) -> Result<(), String> {
+    let len = i32::try_from(value.len() + 1)
+        .map_err(|_| format!("string is too long: {} bytes", value.len()))?;
+    buf.write_i32(len)?;
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(0);
+    Ok(())
+}
+
+/// Fails for translated strings holding their text, which only LSF versions before
+/// `VerBG3` store.
+fn check_no_text(value: &TranslatedString) -> Result<(), String> {
+    match value.value() {
+        Some(text) => Err(format!(
+            "translated string {} holds its text \"{text}\", which only LSF versions below {} \
+             can store",
+            value.handle(),
+            LSFVersion::VerBG3 as u32
+        )),
+        None => Ok(()),
+    }
+}
+
+fn write_translated_fs_string(buf: &mut Vec<u8>, value: &TranslatedFSString) -> Result<(), String> {
+    check_no_text(value.base())?;
+    buf.write_u16(value.version())?;
+    write_length_prefixed(buf, value.handle())?;
+    let arguments_len = i32::try_from(value.arguments().len())
+        .map_err(|_| "too many translated string arguments".to_string())?;
+    buf.write_i32(arguments_len)?;
+    for argument in value {
+        write_length_prefixed(buf, argument.key())?;
+        write_translated_fs_string(buf, argument.string())?;
+        write_length_prefixed(buf, argument.value())?;
+    }
+    Ok(())
+}
+
+fn write_i32s(buf: &mut Vec<u8>, values: &[i32]) -> Result<(), String> {
+    values.iter().try_for_each(|value| buf.write_i32(*value))
+}
+
+fn write_f32s(buf: &mut Vec<u8>, values: &[f32]) -> Result<(), String> {
+    values.iter().try_for_each(|value| buf.write_f32(*value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abstract_file_info::CompressionMethod;
+    use crate::embedded_resource::EmbeddedEncoding;
+    use crate::lsf_reader::{DataType, LSFReader, TranslatedFSStringArgument};
+    use crate::test_support::{add_node, node_mut, sample_resource};
+
+    fn fs_string(
+        handle: &str,
+        version: u16,
+        arguments: Vec<TranslatedFSStringArgument>,
+    ) -> TranslatedFSString {
+        TranslatedFSString::new(
+            TranslatedString::new(handle.to_string(), version),
+            arguments,
+        )
+    }
+
+    /// A value of every data type, the unknown one included.
+    fn values_of_every_type() -> Vec<(DataType, NodeAttributeValue)> {
+        use NodeAttributeValue as V;
+        let nested = fs_string(
+            "hOuter",
+            1,
+            vec![
+                TranslatedFSStringArgument::new(
+                    "Name".to_string(),
+                    fs_string(
+                        "hInner",
+                        2,
+                        vec![TranslatedFSStringArgument::new(
+                            "Deep".to_string(),
+                            fs_string("hDeep", 0, vec![]),
+                            "3".to_string(),
+                        )],
+                    ),
+                    "Karlach".to_string(),
+                ),
+                TranslatedFSStringArgument::new(
+                    "Empty".to_string(),
+                    fs_string("", 0, vec![]),
+                    String::new(),
+                ),
+            ],
+        );
+        vec![
+            (DataType::None, V::None),
+            (DataType::Byte, V::Byte(200)),
+            (DataType::Short, V::Short(-300)),
+            (DataType::UShort, V::UShort(60000)),
+            (DataType::Int, V::Int(-70000)),
+            (DataType::UInt, V::UInt(4_000_000_000)),
+            (DataType::Float, V::Float(1.5)),
+            (DataType::Double, V::Double(-2.25)),
+            (DataType::IVec2, V::IVec2([1, -2])),
+            (DataType::IVec3, V::IVec3([1, -2, 3])),
+            (DataType::IVec4, V::IVec4([1, -2, 3, -4])),
+            (DataType::Vec2, V::Vec2([0.5, -1.0])),
+            (DataType::Vec3, V::Vec3([0.5, -1.0, 2.0])),
+            (DataType::Vec4, V::Vec4([0.5, -1.0, 2.0, 8.0])),
+            (DataType::Mat2, V::Mat2([[1.0, 2.0], [3.0, 4.0]])),
+            (
+                DataType::Mat3,
+                V::Mat3([[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]]),
+            ),
+            (
+                DataType::Mat3x4,
+                V::Mat3x4([
+                    [1.0, 2.0, 3.0, 4.0],
+                    [5.0, 6.0, 7.0, 8.0],
+                    [9.0, 10.0, 11.0, 12.0],
+                ]),
+            ),
+            (
+                DataType::Mat4x3,
+                V::Mat4x3([
+                    [1.0, 2.0, 3.0],
+                    [4.0, 5.0, 6.0],
+                    [7.0, 8.0, 9.0],
+                    [10.0, 11.0, 12.0],
+                ]),
+            ),
+            (
+                DataType::Mat4,
+                V::Mat4([
+                    [1.0, 0.0, 0.0, 0.0],
+                    [0.0, 1.0, 0.0, 0.0],
+                    [0.0, 0.0, 1.0, 0.0],
+                    [4.0, 5.0, 6.0, 1.0],
+                ]),
+            ),
+            (DataType::Bool, V::Bool(true)),
+            (DataType::String, V::String("text".into())),
+            (DataType::Path, V::String("Public/Game/Root.lsf".into())),
+            (DataType::FixedString, V::String("Fixed".into())),
+            (DataType::LSString, V::String("Ü-string".into())),
+            (DataType::ULongLong, V::UInt64(u64::MAX)),
+            (DataType::ScratchBuffer, V::Bytes(vec![0, 1, 2, 0xFF])),
+            (DataType::Long, V::Int64(-5)),
+            (DataType::Int8, V::I8(-7)),
+            (
+                DataType::TranslatedString,
+                V::TranslatedString(TranslatedString::new("h1234abcd".to_string(), 3)),
+            ),
+            (DataType::WString, V::String("wide".into())),
+            (DataType::LSWString, V::String("wider".into())),
+            (
+                DataType::Uuid,
+                V::Uuid(uuid::Uuid::from_u128(
+                    0x2c76687d_93a2_477b_8b18_8a14b549304c,
+                )),
+            ),
+            (DataType::Int64, V::Int64(i64::MIN)),
+            (DataType::TranslatedFSString, V::TranslatedFSString(nested)),
+            (
+                DataType::Unknown,
+                V::Raw {
+                    type_id: 40,
+                    bytes: vec![1, 2, 3],
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn every_data_type_round_trips() -> Result<(), String> {
+        let values = values_of_every_type();
+        for id in 0..=DataType::max_i32() as u32 {
+            let ty = DataType::from(id);
+            assert!(values.iter().any(|(t, _)| *t == ty), "no {ty:?} value");
+        }
+
+        let mut resource = Resource::new();
+        let root = add_node(&mut resource, "Test", None, vec![]);
+        let attributes: Vec<(String, NodeAttribute)> = values
+            .into_iter()
+            .map(|(ty, value)| (format!("{ty:?}"), NodeAttribute { ty, value }))
+            .collect();
+        add_node(
+            &mut resource,
+            "Node",
+            Some(root),
+            attributes
+                .iter()
+                .map(|(name, attribute)| (name.as_str(), attribute.clone()))
+                .collect(),
+        );
+
+        for version in [LSFVersion::VerBG3AdditionalBlob, LSFVersion::VerBg3Patch3] {
+            for compression in [0, DEFAULT_COMPRESSION_FLAGS, CompressionMethod::Zlib as u8] {
+                let bytes = LSFWriter::new()
+                    .with_version(version)
+                    .with_compression(compression)
+                    .write_bytes(&resource)?;
+                let read = LSFReader::new().read_bytes(&bytes)?;
+                let node = &read.regions.node_instances[1];
+                assert_eq!(node.attributes.len(), attributes.len());
+                for (name, attribute) in &attributes {
+                    assert_eq!(
+                        node.attributes.get(name.as_str()),
+                        Some(attribute),
+                        "{name}"
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn translated_strings_with_text_are_rejected() -> Result<(), String> {
+        let with_text = TranslatedString::with_value("h1".to_string(), 0, "Hello".to_string());
+        for value in [
+            NodeAttributeValue::TranslatedString(with_text.clone()),
+            NodeAttributeValue::TranslatedFSString(fs_string(
+                "h2",
+                0,
+                vec![TranslatedFSStringArgument::new(
+                    "Arg".to_string(),
+                    TranslatedFSString::new(with_text.clone(), vec![]),
+                    String::new(),
+                )],
+            )),
+        ] {
+            let mut resource = Resource::new();
+            add_node(
+                &mut resource,
+                "Test",
+                None,
+                vec![(
+                    "Text",
+                    NodeAttribute {
+                        ty: DataType::TranslatedString,
+                        value,
+                    },
+                )],
+            );
+            assert!(
+                LSFWriter::new()
+                    .write_bytes(&resource)
+                    .is_err_and(|e| e.contains("holds its text"))
+            );
+        }
+        Ok(())
+    }
+
+    fn blob(bytes: Vec<u8>) -> NodeAttribute {
+        NodeAttribute {
+            ty: DataType::ScratchBuffer,
+            value: NodeAttributeValue::Bytes(bytes),
+        }
+    }
+
+    /// Sample resource with, in `Config/Option`, an LZ4 frame holding the sample resource,
+    /// whose own `Config/Option` holds the sample resource as a plain LSF file.
+    fn resource_with_nested_blobs() -> Result<Resource, String> {
+        let mut middle = sample_resource();
+        node_mut(&mut middle, "Config/Option")?.attributes.insert(
+            "Data".into(),
+            blob(LSFWriter::new().write_bytes(&sample_resource())?),
+        );
+        let frame = bin_utils::compress_chunked(
+            &LSFWriter::new().write_bytes(&middle)?,
+            CompressionMethod::LZ4 as u8,
+        )?;
+
+        let mut resource = sample_resource();
+        node_mut(&mut resource, "Config/Option")?
+            .attributes
+            .insert("Data".into(), blob(frame));
+        let failures = resource.decode_embedded();
+        assert!(failures.is_empty(), "{failures:?}");
+        Ok(resource)
+    }
+
+    #[test]
+    fn nested_embedded_resources_round_trip() -> Result<(), String> {
+        const OPTION: usize = 1;
+        let mut resource = resource_with_nested_blobs()?;
+        let inner = resource
+            .embedded_mut(OPTION, "Data")
+            .and_then(|middle| middle.embedded_mut(OPTION, "Data"))
+            .ok_or("nested resource not decoded")?;
+        node_mut(inner, "Config")?
+            .attributes
+            .insert("Version".into(), NodeAttribute::parse(DataType::Int, "4")?);
+
+        let bytes = LSFWriter::new().write_bytes(&resource)?;
+        let mut read = LSFReader::new().read_bytes(&bytes)?;
+        assert!(read.decode_embedded().is_empty());
+        let outer = &read.regions.node_instances[OPTION].embedded["Data"];
+        assert_eq!(outer.encoding, EmbeddedEncoding::Lz4Frame);
+        let inner = read
+            .embedded(OPTION, "Data")
+            .and_then(|middle| middle.embedded(OPTION, "Data"))
+            .ok_or("nested resource not read back")?;
+        assert_eq!(
+            inner.regions.node_instances[0].attributes["Version"].value,
+            NodeAttributeValue::Int(4)
+        );
+
+        // Encoding ahead of time keeps the resources in step with their attributes
+        resource.encode_embedded()?;
+        assert!(LSFWriter::new().write_bytes(&resource)? == bytes);
+        Ok(())
+    }
+
+    #[test]
+    fn changed_embedded_blobs_fail_to_write() -> Result<(), String> {
+        let resource = resource_with_nested_blobs()?;
+
+        let mut replaced = resource.clone();
+        node_mut(&mut replaced, "Config/Option")?
+            .attributes
+            .insert("Data".into(), blob(vec![1, 2, 3]));
+        let mut retyped = resource.clone();
+        if let Some(data) = node_mut(&mut retyped, "Config/Option")?
+            .attributes
+            .get_mut("Data")
+        {
+            data.set_from_str(DataType::Int, "1")?;
+        }
+
+        for (mut changed, value) in [
+            (replaced, NodeAttributeValue::Bytes(vec![1, 2, 3])),
+            (retyped, NodeAttributeValue::Int(1)),
+        ] {
+            assert!(
+                LSFWriter::new()
+                    .write_bytes(&changed)
+                    .is_err_and(|e| e.contains("changed after"))
+            );
+            // Dropping the embedded resource keeps the change
+            assert!(changed.remove_embedded(1, "Data"));
+            let read = LSFReader::new().read_bytes(&LSFWriter::new().write_bytes(&changed)?)?;
+            assert_eq!(
+                read.regions.node_instances[1].attributes["Data"].value,
+                value
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn raw_values_round_trip() -> Result<(), String> {
+        let mut resource = Resource::new();
+        let root = add_node(&mut resource, "Test", None, vec![]);
+        let raw = NodeAttribute::parse(DataType::Unknown, "40:010203")?;
+        add_node(
+            &mut resource,
+            "Node",
+            Some(root),
+            vec![("Future", raw.clone())],
+        );
+
+        let bytes = LSFWriter::new().write_bytes(&resource)?;
+        let read = LSFReader::new().read_bytes(&bytes)?;
+        assert_eq!(read.regions.node_instances[1].attributes["Future"], raw);
+        Ok(())
+    }
+}
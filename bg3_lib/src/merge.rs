@@ -0,0 +1,648 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Display;
+use std::sync::Arc;
+
+use crate::diff::{self, DiffOptions};
+use crate::embedded_resource::EmbeddedResource;
+use crate::lsf_reader::{Node, NodeAttribute, NodeKind, RegionArena, Resource};
+use crate::node_path::NodePath;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeSide {
+    Ours,
+    Theirs,
+}
+
+/// Attribute changed, added or removed differently on both sides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeConflict {
+    pub path: NodePath,
+    pub name: String,
+    pub base: Option<NodeAttribute>,
+    pub ours: Option<NodeAttribute>,
+    pub theirs: Option<NodeAttribute>,
+}
+
+/// Change made on both sides in incompatible ways. Conflicts are resolved with our version.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeConflict {
+    Attribute(Box<AttributeConflict>),
+    /// Node removed on one side, and changed on the other.
+    Node {
+        path: NodePath,
+        removed_in: MergeSide,
+    },
+}
+
+impl MergeConflict {
+    pub fn path(&self) -> &NodePath {
+        match self {
+            Self::Attribute(conflict) => &conflict.path,
+            Self::Node { path, .. } => path,
+        }
+    }
+}
+
+impl Display for MergeConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = |attr: &Option<NodeAttribute>| match attr {
+            Some(attr) => format!("{attr} ({})", attr.ty),
+            None => "<none>".to_string(),
+        };
+        match self {
+            Self::Attribute(conflict) => write!(
+                f,
+                "{}@{}: base {}, ours {}, theirs {}",
+                conflict.path,
+                conflict.name,
+                value(&conflict.base),
+                value(&conflict.ours),
+                value(&conflict.theirs)
+            ),
+            Self::Node { path, removed_in } => {
+                write!(f, "{path}: removed in {removed_in:?}, changed in the other")
+            }
+        }
+    }
+}
+
+pub struct MergeResult {
+    pub resource: Resource,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl Resource {
+    /// Three-way merge of the changes made from `base` to `ours` and from `base` to `theirs`,
+    /// matching nodes by name and position.
+    pub fn merge(base: &Resource, ours: &Resource, theirs: &Resource) -> MergeResult {
+        Self::merge_with(base, ours, theirs, &DiffOptions::default())
+    }
+
+    /// Three-way merge, matching nodes and comparing values as `Resource::diff_with` does.
+    ///
+    /// The metadata and the order of children come from `ours`; children added in `theirs`
+    /// come after the existing ones of the same name. Decoded embedded resources are kept
+    /// from the side each attribute value is taken from.
+    pub fn merge_with(
+        base: &Resource,
+        ours: &Resource,
+        theirs: &Resource,
+        options: &DiffOptions,
+    ) -> MergeResult {
+        let mut merger = Merger {
+            base,
+            ours,
+            theirs,
+            options,
+            nodes: vec![],
+            conflicts: vec![],
+        };
+
+        let region_names: BTreeSet<_> = [base, ours, theirs]
+            .iter()
+            .flat_map(|r| r.regions.regions_indices.keys())
+            .collect();
+
+        let mut regions_indices = BTreeMap::new();
+        for name in region_names {
+            let index = |r: &Resource| r.regions.regions_indices.get(name).copied();
+            let merged = merger.merge_child(
+                &NodePath::region(name),
+                index(base),
+                index(ours),
+                index(theirs),
+                None,
+            );
+            if let Some(node_idx) = merged {
+                regions_indices.insert(name.clone(), node_idx);
+            }
+        }
+
+        MergeResult {
+            resource: Resource {
+                metadata: ours.metadata.clone(),
+                regions: RegionArena {
+                    regions_indices,
+                    node_instances: merger.nodes,
+                },
+            },
+            conflicts: merger.conflicts,
+        }
+    }
+}
+
+struct Merger<'a> {
+    base: &'a Resource,
+    ours: &'a Resource,
+    theirs: &'a Resource,
+    options: &'a DiffOptions,
+    nodes: Vec<Node>,
+    conflicts: Vec<MergeConflict>,
+}
+
+impl<'a> Merger<'a> {
+    /// Merges a node given its (matched) indices in each resource, and returns its new index,
+    /// if it is kept.
+    fn merge_child(
+        &mut self,
+        path: &NodePath,
+        base: Option<usize>,
+        ours: Option<usize>,
+        theirs: Option<usize>,
+        parent: Option<usize>,
+    ) -> Option<usize> {
+        match (base, ours, theirs) {
+            (_, Some(ours), Some(theirs)) => self.merge_node(path, base, ours, theirs, parent),
+            // Added on one side only
+            (None, Some(ours), None) => self.copy_subtree(self.ours, ours, parent),
+            (None, None, Some(theirs)) => self.copy_subtree(self.theirs, theirs, parent),
+            // Removed in theirs
+            (Some(base), Some(ours), None) => {
+                if !self.is_unchanged(path, base, self.ours, ours) {
+                    self.conflicts.push(MergeConflict::Node {
+                        path: path.clone(),
+                        removed_in: MergeSide::Theirs,
+                    });
+                    return self.copy_subtree(self.ours, ours, parent);
+                }
+                None
+            }
+            // Removed in ours
+            (Some(base), None, Some(theirs)) => {
+                if !self.is_unchanged(path, base, self.theirs, theirs) {
+                    self.conflicts.push(MergeConflict::Node {
+                        path: path.clone(),
+                        removed_in: MergeSide::Ours,
+                    });
+                }
+                None
+            }
+            (_, None, None) => None,
+        }
+    }
+
+    fn merge_node(
+        &mut self,
+        path: &NodePath,
+        base_idx: Option<usize>,
+        ours_idx: usize,
+        theirs_idx: usize,
+        parent: Option<usize>,
+    ) -> Option<usize> {
+        let (base_resource, ours_resource, theirs_resource): (&'a Resource, _, _) =
+            (self.base, self.ours, self.theirs);
+        let base = base_idx.and_then(|idx| base_resource.regions.get_node(idx));
+        let (Some(ours), Some(theirs)) = (
+            ours_resource.regions.get_node(ours_idx),
+            theirs_resource.regions.get_node(theirs_idx),
+        ) else {
+            return self.copy_subtree(ours_resource, ours_idx, parent);
+        };
+
+        let (attributes, embedded) = self.merge_attributes(path, base, ours, theirs);
+        let node_idx = self.nodes.len();
+        self.nodes.push(Node {
+            kind: ours.kind.clone(),
+            name: ours.name.clone(),
+            parent,
+            attributes,
+            children: Default::default(),
+            embedded,
+        });
+
+        let child_names: BTreeSet<&Arc<str>> = base
+            .into_iter()
+            .chain([ours, theirs])
+            .flat_map(|node| node.children.keys())
+            .collect();
+
+        for name in child_names {
+            let (base_children, ours_children, theirs_children) = (
+                children_named(base, name),
+                children_named(Some(ours), name),
+                children_named(Some(theirs), name),
+            );
+
+            let ours_matches = diff::match_children(
+                self.base,
+                base_children,
+                self.ours,
+                ours_children,
+                name,
+                self.options,
+            );
+            let theirs_matches = diff::match_children(
+                self.base,
+                base_children,
+                self.theirs,
+                theirs_children,
+                name,
+                self.options,
+            );
+            let theirs_by_base: HashMap<usize, Option<usize>> = theirs_matches
+                .iter()
+                .filter_map(|m| m.old.map(|b| (b, m.new)))
+                .collect();
+
+            // Children added on either side, matched with each other
+            let ours_added: Vec<_> = ours_matches
+                .iter()
+                .filter(|m| m.old.is_none())
+                .filter_map(|m| m.new)
+                .collect();
+            let theirs_added: Vec<_> = theirs_matches
+                .iter()
+                .filter(|m| m.old.is_none())
+                .filter_map(|m| m.new)
+                .collect();
+            let added_matches = diff::match_children(
+                self.ours,
+                &ours_added,
+                self.theirs,
+                &theirs_added,
+                name,
+                self.options,
+            );
+
+            let mut pending: Vec<_> = ours_matches
+                .into_iter()
+                .filter_map(|child| {
+                    let base_child = child.old?;
+                    let theirs_child = theirs_by_base.get(&base_child).copied().flatten();
+                    Some((child.segment, Some(base_child), child.new, theirs_child))
+                })
+                .chain(
+                    added_matches
+                        .into_iter()
+                        .map(|child| (child.segment, None, child.old, child.new)),
+                )
+                .collect();
+
+            // In ours order, then the children only found in theirs, in theirs order
+            let ours_positions: HashMap<usize, usize> = ours_children
+                .iter()
+                .enumerate()
+                .map(|(position, idx)| (*idx, position))
+                .collect();
+            pending.sort_by_key(|(_, _, ours_child, _)| {
+                ours_child
+                    .and_then(|idx| ours_positions.get(&idx).copied())
+                    .unwrap_or(usize::MAX)
+            });
+
+            for (segment, base_child, ours_child, theirs_child) in pending {
+                let child_path = path.child(segment);
+                let merged = self.merge_child(
+                    &child_path,
+                    base_child,
+                    ours_child,
+                    theirs_child,
+                    Some(node_idx),
+                );
+                if let Some(merged) = merged {
+                    self.nodes[node_idx].append_child(name, merged);
+                }
+            }
+        }
+
+        Some(node_idx)
+    }
+
+    /// Merged attributes, and the embedded resources of the side each value was taken from.
+    fn merge_attributes(
+        &mut self,
+        path: &NodePath,
+        base: Option<&Node>,
+        ours: &Node,
+        theirs: &Node,
+    ) -> (
+        HashMap<Arc<str>, NodeAttribute>,
+        BTreeMap<Arc<str>, EmbeddedResource>,
+    ) {
+        let names: BTreeSet<&Arc<str>> = base
+            .into_iter()
+            .chain([ours, theirs])
+            .flat_map(|node| node.attributes.keys())
+            .collect();
+
+        let mut attributes = HashMap::with_capacity(ours.attributes.len());
+        let mut embedded = BTreeMap::new();
+        for name in names {
+            let base_value = base.and_then(|node| node.attributes.get(name));
+            let ours_value = ours.attributes.get(name);
+            let theirs_value = theirs.attributes.get(name);
+
+            let (merged, side) =
+                if self.same(ours_value, theirs_value) || self.same(base_value, theirs_value) {
+                    (ours_value, ours)
+                } else if self.same(base_value, ours_value) {
+                    (theirs_value, theirs)
+                } else {
+                    self.conflicts
+                        .push(MergeConflict::Attribute(Box::new(AttributeConflict {
+                            path: path.clone(),
+                            name: name.to_string(),
+                            base: base_value.cloned(),
+                            ours: ours_value.cloned(),
+                            theirs: theirs_value.cloned(),
+                        })));
+                    (ours_value, ours)
+                };
+
+            if let Some(value) = merged {
+                attributes.insert(name.clone(), value.clone());
+            }
+            if let Some(resource) = side.embedded.get(name) {
+                embedded.insert(name.clone(), resource.clone());
+            }
+        }
+
+        (attributes, embedded)
+    }
+
+    fn same(&self, a: Option<&NodeAttribute>, b: Option<&NodeAttribute>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => diff::attributes_equal(a, b, self.options.float_tolerance),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    fn is_unchanged(
+        &self,
+        path: &NodePath,
+        base_idx: usize,
+        other: &Resource,
+        other_idx: usize,
+    ) -> bool {
+        diff::diff_subtrees(self.base, base_idx, other, other_idx, path, self.options).is_empty()
+    }
+
+    /// Copies a node and its descendants from `resource`, and returns the copy's index.
+    fn copy_subtree(
+        &mut self,
+        resource: &Resource,
+        node_idx: usize,
+        parent: Option<usize>,
+    ) -> Option<usize> {
+        let node = resource.regions.get_node(node_idx)?;
+        let copy_idx = self.nodes.len();
+
+        let kind = match parent {
+            None => NodeKind::Region {
+                name: node.name.clone(),
+            },
+            Some(_) => NodeKind::Node,
+        };
+        self.nodes.push(Node {
+            kind,
+            name: node.name.clone(),
+            parent,
+            attributes: node.attributes.clone(),
+            children: Default::default(),
+            embedded: node.embedded.clone(),
+        });
+
+        for (name, children) in &node.children {
+            for child_idx in children {
+                if let Some(child_copy) = self.copy_subtree(resource, *child_idx, Some(copy_idx)) {
+                    self.nodes[copy_idx].append_child(name, child_copy);
+                }
+            }
+        }
+
+        Some(copy_idx)
+    }
+}
+
+fn children_named<'n>(node: Option<&'n Node>, name: &str) -> &'n [usize] {
+    node.and_then(|n| n.children.get(name))
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsf_reader::{DataType, NodeAttributeValue};
+    use crate::lsf_writer::LSFWriter;
+    use crate::test_support::{add_node, node_mut, sample_resource};
+
+    fn int(value: i32) -> NodeAttribute {
+        NodeAttribute {
+            ty: DataType::Int,
+            value: NodeAttributeValue::Int(value),
+        }
+    }
+
+    fn keyed_options() -> DiffOptions {
+        DiffOptions {
+            match_keys: vec!["MapKey".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn map_keys(resource: &Resource) -> Vec<String> {
+        let templates =
+            &resource.regions.node_instances[resource.regions.regions_indices["Templates"]];
+        templates.children["Item"]
+            .iter()
+            .map(|idx| resource.regions.node_instances[*idx].attributes["MapKey"].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn clean_merge_combines_both_sides() -> Result<(), String> {
+        let base = sample_resource();
+        let mut ours = base.clone();
+        node_mut(&mut ours, "Config")?
+            .attributes
+            .insert("Version".into(), int(4));
+        node_mut(&mut ours, "Templates")?
+            .children
+            .values_mut()
+            .for_each(|items| {
+                items.remove(1);
+            });
+
+        let mut theirs = base.clone();
+        node_mut(&mut theirs, "Config/Option")?
+            .attributes
+            .insert("Count".into(), int(2));
+        let templates = theirs.regions.regions_indices["Templates"];
+        add_node(
+            &mut theirs,
+            "Extra",
+            Some(templates),
+            vec![("Count", int(1))],
+        );
+
+        let merged = Resource::merge(&base, &ours, &theirs);
+        assert!(merged.conflicts.is_empty());
+
+        let mut expected = ours.clone();
+        node_mut(&mut expected, "Config/Option")?
+            .attributes
+            .insert("Count".into(), int(2));
+        let templates = expected.regions.regions_indices["Templates"];
+        add_node(
+            &mut expected,
+            "Extra",
+            Some(templates),
+            vec![("Count", int(1))],
+        );
+        assert!(merged.resource.diff(&expected).is_empty());
+        assert!(expected.diff(&merged.resource).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn conflicts_keep_our_version() -> Result<(), String> {
+        let base = sample_resource();
+        let mut ours = base.clone();
+        node_mut(&mut ours, "Config")?
+            .attributes
+            .insert("Version".into(), int(4));
+        node_mut(&mut ours, "Config")?.children.clear();
+        node_mut(&mut ours, "Templates/Item[1]")?
+            .attributes
+            .insert("Count".into(), int(1));
+
+        let mut theirs = base.clone();
+        node_mut(&mut theirs, "Config")?
+            .attributes
+            .insert("Version".into(), int(5));
+        node_mut(&mut theirs, "Config/Option")?
+            .attributes
+            .insert("Count".into(), int(2));
+        node_mut(&mut theirs, "Templates")?
+            .children
+            .values_mut()
+            .for_each(|items| {
+                items.remove(1);
+            });
+
+        let merged = Resource::merge_with(&base, &ours, &theirs, &keyed_options());
+        let conflicts: Vec<String> = merged
+            .conflicts
+            .iter()
+            .map(MergeConflict::to_string)
+            .collect();
+        assert_eq!(
+            conflicts,
+            [
+                "Config@Version: base 3 (int32), ours 4 (int32), theirs 5 (int32)",
+                "Config/Option: removed in Ours, changed in the other",
+                "Templates/Item[MapKey=Shield]: removed in Theirs, changed in the other",
+            ]
+        );
+        assert!(
+            merged
+                .resource
+                .diff_with(&ours, &keyed_options())
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn children_follow_our_order() -> Result<(), String> {
+        let map_key = |key: &str| NodeAttribute {
+            ty: DataType::FixedString,
+            value: NodeAttributeValue::String(key.into()),
+        };
+
+        let base = sample_resource();
+        let mut ours = base.clone();
+        let templates = ours.regions.regions_indices["Templates"];
+        let bow = add_node(
+            &mut ours,
+            "Item",
+            Some(templates),
+            vec![("MapKey", map_key("Bow"))],
+        );
+        let items = node_mut(&mut ours, "Templates")?
+            .children
+            .get_mut("Item")
+            .ok_or("no items")?;
+        // Bow first, and the shield before the swords, which keep their order
+        items.retain(|idx| *idx != bow);
+        items.swap(0, 1);
+        items.insert(0, bow);
+
+        let mut theirs = base.clone();
+        let templates = theirs.regions.regions_indices["Templates"];
+        add_node(
+            &mut theirs,
+            "Item",
+            Some(templates),
+            vec![("MapKey", map_key("Axe"))],
+        );
+        node_mut(&mut theirs, "Templates/Item[2]")?
+            .attributes
+            .insert("Count".into(), int(3));
+
+        let merged = Resource::merge_with(&base, &ours, &theirs, &keyed_options());
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(
+            map_keys(&merged.resource),
+            ["Bow", "Shield", "Sword", "Sword", "Axe"]
+        );
+        let mut merged = merged.resource;
+        let last_sword = node_mut(&mut merged, "Templates/Item[3]")?;
+        assert_eq!(last_sword.attributes.get("Count"), Some(&int(3)));
+        Ok(())
+    }
+
+    #[test]
+    fn embedded_resources_come_from_the_merged_value() -> Result<(), String> {
+        let blob = |version: i32| -> Result<NodeAttribute, String> {
+            let mut embedded = sample_resource();
+            node_mut(&mut embedded, "Config")?
+                .attributes
+                .insert("Version".into(), int(version));
+            Ok(NodeAttribute {
+                ty: DataType::ScratchBuffer,
+                value: NodeAttributeValue::Bytes(LSFWriter::new().write_bytes(&embedded)?),
+            })
+        };
+        let with_blob = |resource: &Resource, version: i32| -> Result<Resource, String> {
+            let mut resource = resource.clone();
+            node_mut(&mut resource, "Config/Option")?
+                .attributes
+                .insert("Data".into(), blob(version)?);
+            if let Some(failure) = resource.decode_embedded().first() {
+                return Err(failure.reason.clone());
+            }
+            Ok(resource)
+        };
+
+        let data = |resource: &Resource| -> Result<Resource, String> {
+            let option = "Config/Option".parse::<NodePath>()?.resolve(resource);
+            option
+                .and_then(|idx| resource.embedded(idx, "Data"))
+                .cloned()
+                .ok_or_else(|| "no embedded resource".to_string())
+        };
+
+        let base = with_blob(&sample_resource(), 1)?;
+        let theirs = with_blob(&base, 2)?;
+        // Edits to the decoded resource are only written back with the resource
+        let mut ours = base.clone();
+        let option = "Config/Option".parse::<NodePath>()?.resolve(&ours);
+        let embedded = option
+            .and_then(|idx| ours.embedded_mut(idx, "Data"))
+            .ok_or("no embedded resource")?;
+        node_mut(embedded, "Config")?
+            .attributes
+            .insert("Version".into(), int(4));
+
+        let merged = Resource::merge(&base, &ours, &base).resource;
+        assert!(data(&merged)? == data(&ours)?);
+        assert!(data(&merged)? != data(&base)?);
+
+        let merged = Resource::merge(&base, &base, &theirs).resource;
+        assert!(data(&merged)? == data(&theirs)?);
+        Ok(())
+    }
+}
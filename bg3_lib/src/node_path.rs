@@ -0,0 +1,268 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lsf_reader::{Node, Resource};
+
+/// How a path segment picks one node among the children sharing its name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NodeSelector {
+    /// Position among the children with the same name, e.g. `Item[2]`.
+    Index(usize),
+    /// First child whose attribute displays as the given value, e.g. `Item[MapKey=Sword]`.
+    Attribute { name: String, value: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NodePathSegment {
+    pub name: String,
+    /// Without a selector, the first child with the name is picked.
+    pub selector: Option<NodeSelector>,
+}
+
+impl NodePathSegment {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            selector: None,
+        }
+    }
+
+    pub fn with_index(name: &str, index: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            selector: Some(NodeSelector::Index(index)),
+        }
+    }
+
+    pub fn with_attribute(name: &str, attribute: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            selector: Some(NodeSelector::Attribute {
+                name: attribute.to_string(),
+                value: value.to_string(),
+            }),
+        }
+    }
+
+    fn matches(&self, node: &Node, index: usize) -> bool {
+        match &self.selector {
+            None => index == 0,
+            Some(NodeSelector::Index(i)) => index == *i,
+            Some(NodeSelector::Attribute { name, value }) => node
+                .attributes
+                .get(name.as_str())
+                .is_some_and(|attr| attr.value.to_string() == *value),
+        }
+    }
+}
+
+impl Display for NodePathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", escape(&self.name))?;
+        match &self.selector {
+            None => Ok(()),
+            Some(NodeSelector::Index(i)) => write!(f, "[{i}]"),
+            Some(NodeSelector::Attribute { name, value }) => {
+                write!(f, "[{}={}]", escape(name), escape(value))
+            }
+        }
+    }
+}
+
+/// Characters with a meaning in paths, written with a leading backslash in names and values.
+const SPECIAL_CHARS: [char; 5] = ['\\', '/', '[', ']', '='];
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if SPECIAL_CHARS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Unescaped text up to the first unescaped `stops` character, and the rest of the input
+/// starting at that character.
+fn take_until<'a>(s: &'a str, stops: &[char]) -> Result<(String, &'a str), String> {
+    let mut text = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if stops.contains(&c) {
+            return Ok((text, &s[i..]));
+        }
+        if c == '\\' {
+            let (_, escaped) = chars
+                .next()
+                .ok_or_else(|| format!("trailing '\\' in '{s}'"))?;
+            text.push(escaped);
+        } else {
+            text.push(c);
+        }
+    }
+    Ok((text, ""))
+}
+
+/// Location of a node in a resource, written as `Region/Node[Key=Value]/Child[2]`.
+/// The first segment names the region. `\`, `/`, `[`, `]` and `=` in names and values
+/// are escaped with a backslash.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NodePath {
+    pub segments: Vec<NodePathSegment>,
+}
+
+impl NodePath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn region(name: &str) -> Self {
+        Self {
+            segments: vec![NodePathSegment::new(name)],
+        }
+    }
+
+    /// Path of a child of the node at this path.
+    pub fn child(&self, segment: NodePathSegment) -> Self {
+        let mut path = self.clone();
+        path.segments.push(segment);
+        path
+    }
+
+    /// Index of the node at this path in the resource's node arena.
+    pub fn resolve(&self, resource: &Resource) -> Option<usize> {
+        let (region, children) = self.segments.split_first()?;
+        let mut node_idx = *resource.regions.regions_indices.get(region.name.as_str())?;
+
+        for segment in children {
+            let node = resource.regions.get_node(node_idx)?;
+            node_idx = node
+                .children
+                .get(segment.name.as_str())?
+                .iter()
+                .enumerate()
+                .find(|(index, child_idx)| {
+                    resource
+                        .regions
+                        .get_node(**child_idx)
+                        .is_some_and(|child| segment.matches(child, *index))
+                })
+                .map(|(_, child_idx)| *child_idx)?;
+        }
+
+        Some(node_idx)
+    }
+}
+
+impl Display for NodePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                write!(f, "/")?;
+            }
+            write!(f, "{segment}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for NodePath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = vec![];
+        let mut rest = s;
+
+        while !rest.is_empty() {
+            let (name, after_name) = take_until(rest, &['/', '['])?;
+            if name.is_empty() {
+                return Err(format!("empty node name in path '{s}'"));
+            }
+            rest = after_name;
+
+            let selector = if let Some(selector) = rest.strip_prefix('[') {
+                let (selector, after_selector) = parse_selector(selector)?;
+                rest = after_selector
+                    .strip_prefix(']')
+                    .ok_or_else(|| format!("unterminated selector in path '{s}'"))?;
+                Some(selector)
+            } else {
+                None
+            };
+
+            rest = match rest.strip_prefix('/') {
+                Some(rest) if !rest.is_empty() => rest,
+                Some(_) => return Err(format!("trailing '/' in path '{s}'")),
+                None if rest.is_empty() => rest,
+                None => return Err(format!("expected '/' after segment '{name}' in path '{s}'")),
+            };
+            segments.push(NodePathSegment { name, selector });
+        }
+
+        if segments.is_empty() {
+            return Err("empty node path".to_string());
+        }
+
+        Ok(Self { segments })
+    }
+}
+
+impl TryFrom<String> for NodePath {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<NodePath> for String {
+    fn from(path: NodePath) -> Self {
+        path.to_string()
+    }
+}
+
+/// Selector up to its closing bracket, and the rest of the path starting at that bracket.
+fn parse_selector(selector: &str) -> Result<(NodeSelector, &str), String> {
+    let (name, rest) = take_until(selector, &['=', ']'])?;
+    if let Some(value) = rest.strip_prefix('=') {
+        let (value, rest) = take_until(value, &[']'])?;
+        Ok((NodeSelector::Attribute { name, value }, rest))
+    } else {
+        name.parse()
+            .map(|index| (NodeSelector::Index(index), rest))
+            .map_err(|e| format!("invalid node selector '{name}': {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn special_characters_round_trip() -> Result<(), String> {
+        let path = NodePath::region("Templates")
+            .child(NodePathSegment::with_attribute(
+                "Item",
+                "Path",
+                r"Public/Items[1]\a=b",
+            ))
+            .child(NodePathSegment::with_index("Odd/Name[", 2));
+        let text = path.to_string();
+        assert_eq!(
+            text,
+            r"Templates/Item[Path=Public\/Items\[1\]\\a\=b]/Odd\/Name\[[2]"
+        );
+        assert_eq!(text.parse::<NodePath>()?, path);
+        Ok(())
+    }
+
+    #[test]
+    fn dangling_escape_fails() {
+        assert!("Templates/Item\\".parse::<NodePath>().is_err());
+        assert!("Templates/Item[Key=a\\]".parse::<NodePath>().is_err());
+    }
+}
@@ -0,0 +1,672 @@
+use std::fmt::Display;
+
+/// Database holding the global flags that are set, one per fact.
+pub const GLOBAL_FLAG_DATABASE: &str = "DB_GlobalFlag";
+
+/// Osiris story database, as saved in the `StorySave.bin` file of a save.
+///
+/// Nodes, adapters, databases and goals refer to each other by index; an index of 0 means
+/// no reference. Everything read from the file is kept, so that the story can be written back.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Story {
+    pub header: OsirisHeader,
+    pub types: Vec<OsirisType>,
+    pub enums: Vec<OsirisEnum>,
+    pub div_objects: Vec<OsirisDivObject>,
+    pub functions: Vec<OsirisFunction>,
+    pub nodes: Vec<OsirisNode>,
+    pub adapters: Vec<OsirisAdapter>,
+    pub databases: Vec<OsirisDatabase>,
+    pub goals: Vec<OsirisGoal>,
+    pub global_actions: Vec<OsirisCall>,
+    /// Only present in versions 1.11 and 1.12.
+    pub external_strings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisHeader {
+    pub unknown: u8,
+    /// Text like `Osiris save file dd. 08/14/23 12:34:56. Version 1.8.`
+    pub version_string: String,
+    pub major_version: u8,
+    pub minor_version: u8,
+    pub big_endian: bool,
+    pub unused: u8,
+    /// Fixed-size version buffer, since version 1.2.
+    pub version_buffer: Vec<u8>,
+    /// Since version 1.3.
+    pub debug_flags: u32,
+}
+
+impl OsirisHeader {
+    /// Version as `major << 8 | minor`, comparable with the `OsirisVersion` constants.
+    pub fn version(&self) -> u16 {
+        ((self.major_version as u16) << 8) | self.minor_version as u16
+    }
+}
+
+/// Versions of the story format in which its layout changed.
+pub struct OsirisVersion;
+
+impl OsirisVersion {
+    pub const ADD_INIT_EXIT_CALLS: u16 = 0x0101;
+    pub const ADD_VERSION_STRING: u16 = 0x0102;
+    pub const ADD_DEBUG_FLAGS: u16 = 0x0103;
+    pub const SCRAMBLE: u16 = 0x0104;
+    pub const ADD_TYPE_MAP: u16 = 0x0105;
+    pub const ADD_QUERY: u16 = 0x0106;
+    pub const TYPE_ALIASES: u16 = 0x0109;
+    pub const ENHANCED_TYPES: u16 = 0x010a;
+    pub const EXTERNAL_STRING_TABLE: u16 = 0x010b;
+    pub const REMOVE_EXTERNAL_STRING_TABLE: u16 = 0x010c;
+    pub const ENUMS: u16 = 0x010d;
+    pub const LAST_SUPPORTED: u16 = Self::ENUMS;
+}
+
+/// Builtin value types. Other type ids are aliases declared in `Story::types`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OsirisBuiltinType {
+    None = 0,
+    Integer = 1,
+    Integer64 = 2,
+    Real = 3,
+    String = 4,
+    GuidString = 5,
+}
+
+impl OsirisBuiltinType {
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            0 => Some(Self::None),
+            1 => Some(Self::Integer),
+            2 => Some(Self::Integer64),
+            3 => Some(Self::Real),
+            4 => Some(Self::String),
+            5 => Some(Self::GuidString),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "NONE",
+            Self::Integer => "INTEGER",
+            Self::Integer64 => "INTEGER64",
+            Self::Real => "REAL",
+            Self::String => "STRING",
+            Self::GuidString => "GUIDSTRING",
+        }
+    }
+}
+
+/// Named type; `alias` is the builtin type it is stored as.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisType {
+    pub name: String,
+    pub index: u8,
+    pub alias: u8,
+}
+
+/// Named values of a declared type, whose alias gives how they are stored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisEnum {
+    pub type_id: u16,
+    pub elements: Vec<(String, u64)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisDivObject {
+    pub name: String,
+    pub ty: u8,
+    pub keys: [u32; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OsirisFunctionType {
+    Unknown = 0,
+    Event = 1,
+    Query = 2,
+    Call = 3,
+    Database = 4,
+    Proc = 5,
+    SysQuery = 6,
+    SysCall = 7,
+    UserQuery = 8,
+}
+
+impl From<u8> for OsirisFunctionType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Event,
+            2 => Self::Query,
+            3 => Self::Call,
+            4 => Self::Database,
+            5 => Self::Proc,
+            6 => Self::SysQuery,
+            7 => Self::SysCall,
+            8 => Self::UserQuery,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisFunction {
+    pub line: u32,
+    pub condition_references: u32,
+    pub action_references: u32,
+    pub node: u32,
+    pub ty: u8,
+    pub meta: [u32; 4],
+    pub name: String,
+    pub out_param_mask: Vec<u8>,
+    pub parameters: Vec<u16>,
+}
+
+impl OsirisFunction {
+    pub fn function_type(&self) -> OsirisFunctionType {
+        self.ty.into()
+    }
+}
+
+/// Value of a fact column, a call parameter or a constant. `type_id` is the type as stored,
+/// which may be an alias of a builtin type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsirisValue {
+    pub type_id: u32,
+    pub data: OsirisValueData,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OsirisValueData {
+    None,
+    Integer(i32),
+    Integer64(i64),
+    Real(f32),
+    /// Strings, GUID strings and the types aliased to them. `None` if no string is set.
+    String(Option<String>),
+    /// Element of an enumeration, stored as its underlying value.
+    Enum(Box<OsirisValueData>),
+    /// Older form of a value, an integer with a type id.
+    Legacy(i32),
+}
+
+impl OsirisValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.data {
+            OsirisValueData::String(s) => s.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.data {
+            OsirisValueData::Integer(v) | OsirisValueData::Legacy(v) => Some(v as i64),
+            OsirisValueData::Integer64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match self.data {
+            OsirisValueData::Real(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl Display for OsirisValueData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => Ok(()),
+            Self::Integer(v) | Self::Legacy(v) => write!(f, "{v}"),
+            Self::Integer64(v) => write!(f, "{v}"),
+            Self::Real(v) => write!(f, "{v}"),
+            Self::String(Some(s)) => write!(f, "\"{s}\""),
+            Self::String(None) => f.write_str("\"\""),
+            Self::Enum(v) => v.fmt(f),
+        }
+    }
+}
+
+impl Display for OsirisValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.data.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsirisTypedValue {
+    pub value: OsirisValue,
+    pub is_valid: bool,
+    pub out_param: bool,
+    pub is_a_type: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsirisVariable {
+    pub value: OsirisTypedValue,
+    pub index: i8,
+    pub unused: bool,
+    pub adapted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OsirisCallParameter {
+    Value(OsirisTypedValue),
+    Variable(OsirisVariable),
+}
+
+/// Call to a function, in rule actions and goal init/exit sections.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisCall {
+    pub name: String,
+    /// `None` when stored without a parameter list.
+    pub parameters: Option<Vec<OsirisCallParameter>>,
+    pub negate: bool,
+    pub goal_id_or_debug_hook: i32,
+}
+
+/// Edge of the rule graph: the node to activate next, and through which entry point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OsirisNodeEntry {
+    pub node: u32,
+    pub entry_point: u32,
+    pub goal: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisJoin {
+    pub next: OsirisNodeEntry,
+    pub left_parent: u32,
+    pub right_parent: u32,
+    pub adapter1: u32,
+    pub adapter2: u32,
+    pub database1: u32,
+    pub database1_indirection: u8,
+    pub database1_join: OsirisNodeEntry,
+    pub database2: u32,
+    pub database2_indirection: u8,
+    pub database2_join: OsirisNodeEntry,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisRelation {
+    pub next: OsirisNodeEntry,
+    pub parent: u32,
+    pub adapter: u32,
+    pub rel_database_node: u32,
+    pub rel_join: OsirisNodeEntry,
+    pub rel_database_indirection: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OsirisNodeKind {
+    Database {
+        referenced_by: Vec<OsirisNodeEntry>,
+    },
+    Proc {
+        referenced_by: Vec<OsirisNodeEntry>,
+    },
+    DivQuery,
+    And(OsirisJoin),
+    NotAnd(OsirisJoin),
+    RelOp {
+        relation: OsirisRelation,
+        left_value_index: i8,
+        right_value_index: i8,
+        left_value: OsirisValue,
+        right_value: OsirisValue,
+        rel_op: i32,
+    },
+    Rule {
+        relation: OsirisRelation,
+        calls: Vec<OsirisCall>,
+        variables: Vec<OsirisVariable>,
+        line: u32,
+        is_query: bool,
+    },
+    InternalQuery,
+    UserQuery,
+}
+
+impl OsirisNodeKind {
+    /// Type byte of the node in the story file.
+    pub fn type_id(&self) -> u8 {
+        match self {
+            Self::Database { .. } => 1,
+            Self::Proc { .. } => 2,
+            Self::DivQuery => 3,
+            Self::And(_) => 4,
+            Self::NotAnd(_) => 5,
+            Self::RelOp { .. } => 6,
+            Self::Rule { .. } => 7,
+            Self::InternalQuery => 8,
+            Self::UserQuery => 9,
+        }
+    }
+}
+
+/// Node of the rule graph. Database nodes carry the name of their database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsirisNode {
+    pub id: u32,
+    pub database: u32,
+    pub name: String,
+    pub num_params: u8,
+    pub kind: OsirisNodeKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisAdapter {
+    pub index: u32,
+    /// Constant values, with their logical column index.
+    pub constants: Vec<(u8, OsirisValue)>,
+    pub logical_indices: Vec<i8>,
+    pub logical_to_physical: Vec<(u8, u8)>,
+}
+
+/// Facts of a database; its name is the one of the database node referring to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisDatabase {
+    pub index: u32,
+    pub parameters: Vec<u16>,
+    pub facts: Vec<OsirisFact>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisFact {
+    pub columns: Vec<OsirisValue>,
+}
+
+impl Display for OsirisFact {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{column}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsirisGoal {
+    pub index: u32,
+    pub name: String,
+    pub sub_goal_combination: u8,
+    pub parent_goals: Vec<u32>,
+    pub sub_goals: Vec<u32>,
+    pub flags: u8,
+    pub init_calls: Vec<OsirisCall>,
+    pub exit_calls: Vec<OsirisCall>,
+}
+
+impl Story {
+    /// Name of a type id, resolving declared types and builtin ones.
+    pub fn type_name(&self, type_id: u32) -> Option<&str> {
+        self.types
+            .iter()
+            .find(|ty| ty.index as u32 == type_id)
+            .map(|ty| ty.name.as_str())
+            .or_else(|| OsirisBuiltinType::from_id(type_id).map(|ty| ty.name()))
+    }
+
+    pub fn database(&self, index: u32) -> Option<&OsirisDatabase> {
+        self.databases.iter().find(|db| db.index == index)
+    }
+
+    pub fn node(&self, id: u32) -> Option<&OsirisNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    pub fn goal(&self, index: u32) -> Option<&OsirisGoal> {
+        self.goals.iter().find(|goal| goal.index == index)
+    }
+
+    pub fn goal_named(&self, name: &str) -> Option<&OsirisGoal> {
+        self.goals.iter().find(|goal| goal.name == name)
+    }
+
+    pub fn function_named(&self, name: &str) -> impl Iterator<Item = &OsirisFunction> {
+        self.functions.iter().filter(move |f| f.name == name)
+    }
+
+    /// Databases with the given name, like `DB_GlobalFlag`, one per arity, with their node.
+    pub fn databases_named<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = (&'a OsirisNode, &'a OsirisDatabase)> {
+        self.nodes
+            .iter()
+            .filter(move |node| {
+                node.name == name && matches!(node.kind, OsirisNodeKind::Database { .. })
+            })
+            .filter_map(|node| Some((node, self.database(node.database)?)))
+    }
+
+    /// Every fact of the databases with the given name, whatever their arity.
+    pub fn facts(&self, database_name: &str) -> Vec<&OsirisFact> {
+        self.nodes
+            .iter()
+            .filter(|node| {
+                node.name == database_name && matches!(node.kind, OsirisNodeKind::Database { .. })
+            })
+            .filter_map(|node| self.database(node.database))
+            .flat_map(|database| &database.facts)
+            .collect()
+    }
+
+    /// Rule nodes, with the goal each one belongs to when known.
+    pub fn rules(&self) -> impl Iterator<Item = (&OsirisNode, Option<&OsirisGoal>)> {
+        self.nodes.iter().filter_map(|node| match &node.kind {
+            OsirisNodeKind::Rule { relation, .. } => Some((node, self.goal(relation.next.goal))),
+            _ => None,
+        })
+    }
+
+    /// Builtin type values of `type_id` are stored as, resolving declared types the way the
+    /// reader does.
+    pub fn builtin_type(&self, type_id: u32) -> Option<OsirisBuiltinType> {
+        let alias = self
+            .types
+            .iter()
+            .find(|ty| ty.index as u32 == type_id)
+            .map(|ty| {
+                if self.header.version() >= OsirisVersion::TYPE_ALIASES {
+                    ty.alias
+                } else {
+                    // Only string aliases existed before
+                    OsirisBuiltinType::String as u8
+                }
+            })
+            .filter(|alias| *alias != 0);
+        match alias {
+            Some(alias) => OsirisBuiltinType::from_id(alias as u32),
+            None => OsirisBuiltinType::from_id(type_id),
+        }
+    }
+
+    /// Parses `text` as a value of `type_id`. Strings are taken as is, without quotes, and
+    /// enumeration elements by name.
+    pub fn value_from_str(&self, type_id: u32, text: &str) -> Result<OsirisValue, String> {
+        let builtin_type = self
+            .builtin_type(type_id)
+            .ok_or_else(|| format!("unknown type {type_id}"))?;
+        let parse_error =
+            |e: &dyn Display| format!("'{text}' is not a valid {}: {e}", builtin_type.name());
+
+        if let Some(osiris_enum) = self.enums.iter().find(|e| e.type_id as u32 == type_id) {
+            let (name, value) = osiris_enum
+                .elements
+                .iter()
+                .find(|(name, _)| name == text)
+                .ok_or_else(|| {
+                    format!(
+                        "'{text}' is not an element of enumeration {}",
+                        self.type_name(type_id).unwrap_or_default()
+                    )
+                })?;
+            let data = match builtin_type {
+                OsirisBuiltinType::Integer => OsirisValueData::Integer(*value as i32),
+                OsirisBuiltinType::Integer64 => OsirisValueData::Integer64(*value as i64),
+                _ => OsirisValueData::String(Some(name.clone())),
+            };
+            return Ok(OsirisValue {
+                type_id,
+                data: OsirisValueData::Enum(Box::new(data)),
+            });
+        }
+
+        let data = match builtin_type {
+            OsirisBuiltinType::None => OsirisValueData::None,
+            OsirisBuiltinType::Integer => {
+                OsirisValueData::Integer(text.parse().map_err(|e| parse_error(&e))?)
+            }
+            OsirisBuiltinType::Integer64 => {
+                OsirisValueData::Integer64(text.parse().map_err(|e| parse_error(&e))?)
+            }
+            OsirisBuiltinType::Real => {
+                OsirisValueData::Real(text.parse().map_err(|e| parse_error(&e))?)
+            }
+            OsirisBuiltinType::String | OsirisBuiltinType::GuidString => {
+                OsirisValueData::String(Some(text.to_string()))
+            }
+        };
+        Ok(OsirisValue { type_id, data })
+    }
+
+    /// Database named `database_name` with as many columns as `columns`, with `columns`
+    /// parsed according to its parameter types.
+    fn fact_database_mut(
+        &mut self,
+        database_name: &str,
+        columns: &[&str],
+    ) -> Result<(&mut OsirisDatabase, OsirisFact), String> {
+        let index = self
+            .databases_named(database_name)
+            .find(|(_, database)| database.parameters.len() == columns.len())
+            .map(|(_, database)| database.index)
+            .ok_or_else(|| {
+                format!(
+                    "could not find database {database_name} with {} columns",
+                    columns.len()
+                )
+            })?;
+        let position = self
+            .databases
+            .iter()
+            .position(|database| database.index == index)
+            .ok_or_else(|| format!("could not find database {index}"))?;
+        let fact = OsirisFact {
+            columns: self.databases[position]
+                .parameters
+                .iter()
+                .zip(columns)
+                .map(|(type_id, text)| self.value_from_str(*type_id as u32, text))
+                .collect::<Result<_, _>>()?,
+        };
+        let database = &mut self.databases[position];
+        Ok((database, fact))
+    }
+
+    /// Adds a fact to an existing database, unless it is already there.
+    /// Returns whether the fact was added.
+    pub fn add_fact(&mut self, database_name: &str, columns: &[&str]) -> Result<bool, String> {
+        let (database, fact) = self.fact_database_mut(database_name, columns)?;
+        if database.facts.contains(&fact) {
+            return Ok(false);
+        }
+        database.facts.push(fact);
+        Ok(true)
+    }
+
+    /// Removes a fact from a database, returning how many copies of it were removed.
+    pub fn remove_fact(&mut self, database_name: &str, columns: &[&str]) -> Result<usize, String> {
+        let (database, fact) = self.fact_database_mut(database_name, columns)?;
+        let count = database.facts.len();
+        database.facts.retain(|f| *f != fact);
+        Ok(count - database.facts.len())
+    }
+
+    pub fn is_global_flag_set(&self, flag: &str) -> bool {
+        self.facts(GLOBAL_FLAG_DATABASE)
+            .iter()
+            .any(|fact| fact.columns.len() == 1 && fact.columns[0].as_str() == Some(flag))
+    }
+
+    /// Sets a global flag, returning whether it was not already set.
+    pub fn set_global_flag(&mut self, flag: &str) -> Result<bool, String> {
+        self.add_fact(GLOBAL_FLAG_DATABASE, &[flag])
+    }
+
+    /// Clears a global flag, returning whether it was set.
+    pub fn clear_global_flag(&mut self, flag: &str) -> Result<bool, String> {
+        Ok(self.remove_fact(GLOBAL_FLAG_DATABASE, &[flag])? > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::sample_story;
+
+    fn facts(story: &Story, database_name: &str) -> Vec<String> {
+        story
+            .facts(database_name)
+            .iter()
+            .map(|fact| fact.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn databases_are_found_by_name() {
+        let story = sample_story();
+        let counters: Vec<(u32, usize)> = story
+            .databases_named("DB_Counter")
+            .map(|(node, database)| (node.id, database.parameters.len()))
+            .collect();
+        assert_eq!(counters, [(2, 2), (3, 1)]);
+        assert_eq!(story.databases_named("DB_Missing").count(), 0);
+        // Only database nodes carry database names
+        assert_eq!(story.databases_named("PROC_Count").count(), 0);
+
+        assert_eq!(facts(&story, "DB_Counter"), ["(\"Gold\", 10)", "(\"\")"]);
+        assert_eq!(facts(&story, "DB_GlobalFlag"), ["(\"Flag_A\")"]);
+        assert_eq!(facts(&story, "DB_Color"), ["(2)"]);
+        assert!(facts(&story, "PROC_Count").is_empty());
+    }
+
+    #[test]
+    fn values_are_parsed_by_type() -> Result<(), String> {
+        let story = sample_story();
+        let data = |type_id: u32, text: &str| story.value_from_str(type_id, text).map(|v| v.data);
+
+        assert_eq!(data(1, "-5")?, OsirisValueData::Integer(-5));
+        assert_eq!(
+            data(2, "5000000000")?,
+            OsirisValueData::Integer64(5_000_000_000)
+        );
+        assert_eq!(data(3, "1.5")?, OsirisValueData::Real(1.5));
+        assert_eq!(
+            data(4, "text")?,
+            OsirisValueData::String(Some("text".to_string()))
+        );
+        // FLAG is an alias of GUIDSTRING
+        let flag = story.value_from_str(6, "Flag_B")?;
+        assert_eq!(flag.type_id, 6);
+        assert_eq!(flag.as_str(), Some("Flag_B"));
+        // COLOR is an enumeration stored as integers
+        assert_eq!(
+            data(7, "Blue")?,
+            OsirisValueData::Enum(Box::new(OsirisValueData::Integer(2)))
+        );
+
+        assert!(data(1, "1.5").is_err_and(|e| e.contains("not a valid INTEGER")));
+        assert!(data(7, "Green").is_err_and(|e| e.contains("not an element of enumeration COLOR")));
+        assert!(data(99, "1").is_err_and(|e| e.contains("unknown type 99")));
+        Ok(())
+    }
+}
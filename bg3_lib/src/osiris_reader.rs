@@ -0,0 +1,537 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+
+use crate::abstract_file_info::PackagedFileInfo;
+use crate::bin_utils::ReadExt;
+use crate::osiris::*;
+use crate::package_reader::PackageReader;
+
+/// Size of the version buffer following the header, since version 1.2
+pub(crate) const VERSION_BUFFER_SIZE: usize = 0x80;
+/// Strings are xor-ed with this byte, since version 1.4
+pub(crate) const STRING_SCRAMBLE: u8 = 0xAD;
+
+/// Reads the Osiris story database of a save (`StorySave.bin`).
+#[derive(Debug, Default)]
+pub struct OsirisReader {
+    version: u16,
+    scramble: u8,
+    /// Builtin type of each alias type id
+    type_aliases: HashMap<u32, u32>,
+    /// Type ids declared as enumerations
+    enum_types: HashSet<u32>,
+}
+
+impl OsirisReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(
+        &mut self,
+        package_reader: &mut PackageReader,
+        pfi: &PackagedFileInfo,
+    ) -> Result<Story, String> {
+        println!("Reading Osiris story {}", pfi.name.to_string_lossy());
+        let file_bytes = package_reader.decompress_file(pfi)?;
+        self.read_bytes(&file_bytes)
+    }
+
+    pub fn read_bytes(&mut self, bytes: &[u8]) -> Result<Story, String> {
+        *self = Self::default();
+        let mut stream = Cursor::new(bytes);
+        let stream = &mut stream;
+
+        let mut story = Story {
+            header: self.read_header(stream)?,
+            ..Default::default()
+        };
+        if self.version > OsirisVersion::LAST_SUPPORTED {
+            return Err(format!(
+                "Osiris version {}.{} is not supported",
+                story.header.major_version, story.header.minor_version
+            ));
+        }
+        if story.header.big_endian {
+            return Err("big endian Osiris stories are not supported".to_string());
+        }
+        if self.version >= OsirisVersion::SCRAMBLE {
+            self.scramble = STRING_SCRAMBLE;
+        }
+
+        if self.version >= OsirisVersion::ADD_TYPE_MAP {
+            story.types = self.read_list(stream, "type", Self::read_type)?;
+            for ty in &story.types {
+                let alias = if self.version >= OsirisVersion::TYPE_ALIASES {
+                    ty.alias
+                } else {
+                    // Only string aliases existed before
+                    OsirisBuiltinType::String as u8
+                };
+                if alias != 0 {
+                    self.type_aliases.insert(ty.index as u32, alias as u32);
+                }
+            }
+        }
+
+        if self.version >= OsirisVersion::ENUMS {
+            story.enums = self.read_list(stream, "enumeration", Self::read_enum)?;
+            self.enum_types = story.enums.iter().map(|e| e.type_id as u32).collect();
+        }
+
+        story.div_objects = self.read_list(stream, "div object", Self::read_div_object)?;
+        story.functions = self.read_list(stream, "function", Self::read_function)?;
+        story.nodes = self.read_list(stream, "node", Self::read_node)?;
+        story.adapters = self.read_list(stream, "adapter", Self::read_adapter)?;
+        story.databases = self.read_list(stream, "database", Self::read_database)?;
+        story.goals = self.read_list(stream, "goal", Self::read_goal)?;
+        story.global_actions = self.read_list(stream, "global action", Self::read_call)?;
+
+        if (OsirisVersion::EXTERNAL_STRING_TABLE..OsirisVersion::REMOVE_EXTERNAL_STRING_TABLE)
+            .contains(&self.version)
+        {
+            story.external_strings = self.read_list(stream, "string", Self::read_string)?;
+        }
+
+        Ok(story)
+    }
+
+    fn read_header(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisHeader, String> {
+        let mut header = OsirisHeader {
+            unknown: stream.read_u8()?,
+            version_string: self.read_string(stream)?,
+            major_version: stream.read_u8()?,
+            minor_version: stream.read_u8()?,
+            big_endian: read_bool(stream)?,
+            unused: stream.read_u8()?,
+            ..Default::default()
+        };
+        self.version = header.version();
+
+        if self.version >= OsirisVersion::ADD_VERSION_STRING {
+            header.version_buffer = vec![0; VERSION_BUFFER_SIZE];
+            stream
+                .read_exact(&mut header.version_buffer)
+                .map_err(|e| format!("failed reading Osiris version buffer: {e}"))?;
+        }
+        if self.version >= OsirisVersion::ADD_DEBUG_FLAGS {
+            header.debug_flags = stream.read_u32()?;
+        }
+
+        Ok(header)
+    }
+
+    /// Reads a `u32` count, then as many items.
+    fn read_list<T>(
+        &mut self,
+        stream: &mut Cursor<&[u8]>,
+        item_name: &str,
+        read_item: impl Fn(&mut Self, &mut Cursor<&[u8]>) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let count = stream.read_u32()?;
+        let mut items = vec![];
+        for item_idx in 0..count {
+            let item = read_item(self, stream).map_err(|e| {
+                format!(
+                    "failed reading {item_name} {item_idx} of {count} at offset {}: {e}",
+                    stream.position()
+                )
+            })?;
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    /// Reads a null-terminated, scrambled UTF-8 string.
+    fn read_string(&mut self, stream: &mut Cursor<&[u8]>) -> Result<String, String> {
+        let mut bytes = vec![];
+        loop {
+            match stream.read_u8()? ^ self.scramble {
+                0 => break,
+                b => bytes.push(b),
+            }
+        }
+        String::from_utf8(bytes).map_err(|e| format!("invalid UTF-8 string: {e}"))
+    }
+
+    fn read_type(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisType, String> {
+        Ok(OsirisType {
+            name: self.read_string(stream)?,
+            index: stream.read_u8()?,
+            alias: if self.version >= OsirisVersion::TYPE_ALIASES {
+                stream.read_u8()?
+            } else {
+                0
+            },
+        })
+    }
+
+    fn read_enum(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisEnum, String> {
+        let type_id = stream.read_u16()?;
+        let elements = self.read_list(stream, "enumeration element", |reader, stream| {
+            Ok((reader.read_string(stream)?, stream.read_u64()?))
+        })?;
+        Ok(OsirisEnum { type_id, elements })
+    }
+
+    fn read_div_object(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisDivObject, String> {
+        Ok(OsirisDivObject {
+            name: self.read_string(stream)?,
+            ty: stream.read_u8()?,
+            keys: [
+                stream.read_u32()?,
+                stream.read_u32()?,
+                stream.read_u32()?,
+                stream.read_u32()?,
+            ],
+        })
+    }
+
+    fn read_function(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisFunction, String> {
+        let mut function = OsirisFunction {
+            line: stream.read_u32()?,
+            condition_references: stream.read_u32()?,
+            action_references: stream.read_u32()?,
+            node: stream.read_u32()?,
+            ty: stream.read_u8()?,
+            meta: [
+                stream.read_u32()?,
+                stream.read_u32()?,
+                stream.read_u32()?,
+                stream.read_u32()?,
+            ],
+            name: self.read_string(stream)?,
+            ..Default::default()
+        };
+        function.out_param_mask =
+            self.read_list(stream, "out parameter mask", |_, stream| stream.read_u8())?;
+        function.parameters = read_parameter_types(stream)?;
+        Ok(function)
+    }
+
+    fn read_node(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisNode, String> {
+        let type_id = stream.read_u8()?;
+        let id = stream.read_u32()?;
+        let database = stream.read_u32()?;
+        let name = self.read_string(stream)?;
+        let num_params = if name.is_empty() {
+            0
+        } else {
+            stream.read_u8()?
+        };
+
+        let kind = match type_id {
+            1 => OsirisNodeKind::Database {
+                referenced_by: self.read_list(stream, "node reference", |_, s| read_entry(s))?,
+            },
+            2 => OsirisNodeKind::Proc {
+                referenced_by: self.read_list(stream, "node reference", |_, s| read_entry(s))?,
+            },
+            3 => OsirisNodeKind::DivQuery,
+            4 => OsirisNodeKind::And(read_join(stream)?),
+            5 => OsirisNodeKind::NotAnd(read_join(stream)?),
+            6 => OsirisNodeKind::RelOp {
+                relation: read_relation(stream)?,
+                left_value_index: stream.read_i8()?,
+                right_value_index: stream.read_i8()?,
+                left_value: self.read_value(stream)?,
+                right_value: self.read_value(stream)?,
+                rel_op: stream.read_i32()?,
+            },
+            7 => {
+                let relation = read_relation(stream)?;
+                let calls = self.read_list(stream, "rule call", Self::read_call)?;
+                let variable_count = stream.read_u8()?;
+                let mut variables = vec![];
+                for _ in 0..variable_count {
+                    match stream.read_u8()? {
+                        1 => variables.push(self.read_variable(stream)?),
+                        marker => return Err(format!("unexpected rule variable marker {marker}")),
+                    }
+                }
+                OsirisNodeKind::Rule {
+                    relation,
+                    calls,
+                    variables,
+                    line: stream.read_u32()?,
+                    is_query: self.version >= OsirisVersion::ADD_QUERY && read_bool(stream)?,
+                }
+            }
+            8 => OsirisNodeKind::InternalQuery,
+            9 => OsirisNodeKind::UserQuery,
+            _ => return Err(format!("unknown node type {type_id} of node {id}")),
+        };
+
+        Ok(OsirisNode {
+            id,
+            database,
+            name,
+            num_params,
+            kind,
+        })
+    }
+
+    fn read_adapter(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisAdapter, String> {
+        let index = stream.read_u32()?;
+
+        let constant_count = stream.read_u8()?;
+        let mut constants = vec![];
+        for _ in 0..constant_count {
+            let logical_index = stream.read_u8()?;
+            constants.push((logical_index, self.read_value(stream)?));
+        }
+
+        let logical_count = stream.read_u8()?;
+        let mut logical_indices = vec![];
+        for _ in 0..logical_count {
+            logical_indices.push(stream.read_i8()?);
+        }
+
+        let mapping_count = stream.read_u8()?;
+        let mut logical_to_physical = vec![];
+        for _ in 0..mapping_count {
+            logical_to_physical.push((stream.read_u8()?, stream.read_u8()?));
+        }
+
+        Ok(OsirisAdapter {
+            index,
+            constants,
+            logical_indices,
+            logical_to_physical,
+        })
+    }
+
+    fn read_database(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisDatabase, String> {
+        let index = stream.read_u32()?;
+        let parameters = read_parameter_types(stream)?;
+        let facts = self.read_list(stream, "fact", |reader, stream| {
+            let column_count = stream.read_u8()?;
+            let mut columns = vec![];
+            for _ in 0..column_count {
+                columns.push(reader.read_value(stream)?);
+            }
+            Ok(OsirisFact { columns })
+        })?;
+
+        Ok(OsirisDatabase {
+            index,
+            parameters,
+            facts,
+        })
+    }
+
+    fn read_goal(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisGoal, String> {
+        let mut goal = OsirisGoal {
+            index: stream.read_u32()?,
+            name: self.read_string(stream)?,
+            sub_goal_combination: stream.read_u8()?,
+            ..Default::default()
+        };
+        goal.parent_goals = self.read_list(stream, "parent goal", |_, s| s.read_u32())?;
+        goal.sub_goals = self.read_list(stream, "sub goal", |_, s| s.read_u32())?;
+        goal.flags = stream.read_u8()?;
+        if self.version >= OsirisVersion::ADD_INIT_EXIT_CALLS {
+            goal.init_calls = self.read_list(stream, "init call", Self::read_call)?;
+            goal.exit_calls = self.read_list(stream, "exit call", Self::read_call)?;
+        }
+        Ok(goal)
+    }
+
+    fn read_call(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisCall, String> {
+        let mut call = OsirisCall {
+            name: self.read_string(stream)?,
+            ..Default::default()
+        };
+
+        if !call.name.is_empty() {
+            if stream.read_u8()? != 0 {
+                let parameter_count = stream.read_u8()?;
+                let mut parameters = vec![];
+                for _ in 0..parameter_count {
+                    let parameter = match stream.read_u8()? {
+                        0 => OsirisCallParameter::Value(self.read_typed_value(stream)?),
+                        1 => OsirisCallParameter::Variable(self.read_variable(stream)?),
+                        marker => {
+                            return Err(format!("unexpected call parameter marker {marker}"));
+                        }
+                    };
+                    parameters.push(parameter);
+                }
+                call.parameters = Some(parameters);
+            }
+            call.negate = read_bool(stream)?;
+        }
+        call.goal_id_or_debug_hook = stream.read_i32()?;
+
+        Ok(call)
+    }
+
+    fn read_value(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisValue, String> {
+        match stream.read_u8()? {
+            b'0' => {
+                let type_id = self.read_type_id(stream)?;
+                let data = self.read_value_data(stream, self.builtin_type(type_id))?;
+                Ok(OsirisValue { type_id, data })
+            }
+            b'1' => Ok(OsirisValue {
+                type_id: stream.read_u32()?,
+                data: OsirisValueData::Legacy(stream.read_i32()?),
+            }),
+            b'e' => {
+                let type_id = stream.read_u16()? as u32;
+                if !self.enum_types.contains(&type_id) {
+                    return Err(format!("unknown enumeration type {type_id}"));
+                }
+                // Elements are stored as values of the type the enumeration is declared for
+                let data = self.read_value_data(stream, self.builtin_type(type_id))?;
+                Ok(OsirisValue {
+                    type_id,
+                    data: OsirisValueData::Enum(Box::new(data)),
+                })
+            }
+            marker => Err(format!("unknown value marker {marker:#04x}")),
+        }
+    }
+
+    fn read_type_id(&mut self, stream: &mut Cursor<&[u8]>) -> Result<u32, String> {
+        // Type ids shrank to 16 bits when enumerations were added
+        if self.version >= OsirisVersion::ENUMS {
+            Ok(stream.read_u16()? as u32)
+        } else {
+            stream.read_u32()
+        }
+    }
+
+    pub(crate) fn builtin_type(&self, type_id: u32) -> u32 {
+        self.type_aliases.get(&type_id).copied().unwrap_or(type_id)
+    }
+
+    fn read_value_data(
+        &mut self,
+        stream: &mut Cursor<&[u8]>,
+        builtin_type: u32,
+    ) -> Result<OsirisValueData, String> {
+        let data = match OsirisBuiltinType::from_id(builtin_type) {
+            Some(OsirisBuiltinType::None) => OsirisValueData::None,
+            Some(OsirisBuiltinType::Integer) => OsirisValueData::Integer(stream.read_i32()?),
+            Some(OsirisBuiltinType::Integer64) => OsirisValueData::Integer64(stream.read_i64()?),
+            Some(OsirisBuiltinType::Real) => OsirisValueData::Real(stream.read_f32()?),
+            // Strings, GUID strings and unknown types are stored as optional strings
+            _ => {
+                if stream.read_u8()? != 0 {
+                    OsirisValueData::String(Some(self.read_string(stream)?))
+                } else {
+                    OsirisValueData::String(None)
+                }
+            }
+        };
+        Ok(data)
+    }
+
+    fn read_typed_value(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisTypedValue, String> {
+        Ok(OsirisTypedValue {
+            value: self.read_value(stream)?,
+            is_valid: read_bool(stream)?,
+            out_param: read_bool(stream)?,
+            is_a_type: read_bool(stream)?,
+        })
+    }
+
+    fn read_variable(&mut self, stream: &mut Cursor<&[u8]>) -> Result<OsirisVariable, String> {
+        Ok(OsirisVariable {
+            value: self.read_typed_value(stream)?,
+            index: stream.read_i8()?,
+            unused: read_bool(stream)?,
+            adapted: read_bool(stream)?,
+        })
+    }
+}
+
+fn read_bool(stream: &mut Cursor<&[u8]>) -> Result<bool, String> {
+    Ok(stream.read_u8()? != 0)
+}
+
+/// Reads a `u8` count, then as many `u16` type ids.
+fn read_parameter_types(stream: &mut Cursor<&[u8]>) -> Result<Vec<u16>, String> {
+    let count = stream.read_u8()?;
+    let mut types = vec![];
+    for _ in 0..count {
+        types.push(stream.read_u16()?);
+    }
+    Ok(types)
+}
+
+fn read_entry(stream: &mut Cursor<&[u8]>) -> Result<OsirisNodeEntry, String> {
+    Ok(OsirisNodeEntry {
+        node: stream.read_u32()?,
+        entry_point: stream.read_u32()?,
+        goal: stream.read_u32()?,
+    })
+}
+
+fn read_join(stream: &mut Cursor<&[u8]>) -> Result<OsirisJoin, String> {
+    Ok(OsirisJoin {
+        next: read_entry(stream)?,
+        left_parent: stream.read_u32()?,
+        right_parent: stream.read_u32()?,
+        adapter1: stream.read_u32()?,
+        adapter2: stream.read_u32()?,
+        database1: stream.read_u32()?,
+        database1_indirection: stream.read_u8()?,
+        database1_join: read_entry(stream)?,
+        database2: stream.read_u32()?,
+        database2_indirection: stream.read_u8()?,
+        database2_join: read_entry(stream)?,
+    })
+}
+
+fn read_relation(stream: &mut Cursor<&[u8]>) -> Result<OsirisRelation, String> {
+    Ok(OsirisRelation {
+        next: read_entry(stream)?,
+        parent: stream.read_u32()?,
+        adapter: stream.read_u32()?,
+        rel_database_node: stream.read_u32()?,
+        rel_join: read_entry(stream)?,
+        rel_database_indirection: stream.read_u8()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osiris_writer::OsirisWriter;
+    use crate::test_support::sample_story;
+
+    #[test]
+    fn sample_story_is_read_back() -> Result<(), String> {
+        let story = sample_story();
+        let bytes = OsirisWriter::new().write_bytes(&story)?;
+        assert!(OsirisReader::new().read_bytes(&bytes)? == story);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_call_parameter_markers_fail() {
+        let mut reader = OsirisReader {
+            version: OsirisVersion::LAST_SUPPORTED,
+            ..Default::default()
+        };
+        // Name, parameter list flag, parameter count, then the parameter marker
+        let mut bytes = b"PROC_Test\0\x01\x01".to_vec();
+        bytes.push(2);
+        bytes.extend([0; 16]);
+        let result = reader.read_call(&mut Cursor::new(&bytes[..]));
+        assert!(result.is_err_and(|e| e.contains("unexpected call parameter marker 2")));
+
+        // Calls without a name have no parameter list
+        let bytes = [0, 0xFF, 0xFF, 0xFF, 0xFF];
+        let call = reader.read_call(&mut Cursor::new(&bytes[..]));
+        assert_eq!(
+            call,
+            Ok(OsirisCall {
+                goal_id_or_debug_hook: -1,
+                ..Default::default()
+            })
+        );
+    }
+}
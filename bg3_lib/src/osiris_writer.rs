@@ -0,0 +1,519 @@
+use std::io::Write;
+
+use crate::bin_utils::WriteExt;
+use crate::osiris::*;
+use crate::osiris_reader::{STRING_SCRAMBLE, VERSION_BUFFER_SIZE};
+
+/// Writes an Osiris story database in the version of its header, the way `OsirisReader`
+/// reads it.
+#[derive(Debug, Default)]
+pub struct OsirisWriter {
+    version: u16,
+    scramble: u8,
+}
+
+impl OsirisWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write<W: Write>(&mut self, story: &Story, writer: &mut W) -> Result<(), String> {
+        self.version = story.header.version();
+        self.scramble = 0;
+        if self.version > OsirisVersion::LAST_SUPPORTED {
+            return Err(format!(
+                "Osiris version {}.{} is not supported",
+                story.header.major_version, story.header.minor_version
+            ));
+        }
+        if story.header.big_endian {
+            return Err("big endian Osiris stories are not supported".to_string());
+        }
+
+        self.write_header(&story.header, writer)?;
+        if self.version >= OsirisVersion::SCRAMBLE {
+            self.scramble = STRING_SCRAMBLE;
+        }
+
+        if self.version >= OsirisVersion::ADD_TYPE_MAP {
+            self.write_list(writer, &story.types, Self::write_type)?;
+        }
+        if self.version >= OsirisVersion::ENUMS {
+            self.write_list(writer, &story.enums, Self::write_enum)?;
+        }
+
+        self.write_list(writer, &story.div_objects, Self::write_div_object)?;
+        self.write_list(writer, &story.functions, Self::write_function)?;
+        self.write_list(writer, &story.nodes, Self::write_node)?;
+        self.write_list(writer, &story.adapters, Self::write_adapter)?;
+        self.write_list(writer, &story.databases, Self::write_database)?;
+        self.write_list(writer, &story.goals, Self::write_goal)?;
+        self.write_list(writer, &story.global_actions, Self::write_call)?;
+
+        if (OsirisVersion::EXTERNAL_STRING_TABLE..OsirisVersion::REMOVE_EXTERNAL_STRING_TABLE)
+            .contains(&self.version)
+        {
+            self.write_list(writer, &story.external_strings, |w, writer, s| {
+                w.write_string(writer, s)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the story to a new buffer.
+    pub fn write_bytes(&mut self, story: &Story) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![];
+        self.write(story, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn write_header<W: Write>(
+        &mut self,
+        header: &OsirisHeader,
+        writer: &mut W,
+    ) -> Result<(), String> {
+        writer.write_u8(header.unknown)?;
+        self.write_string(writer, &header.version_string)?;
+        writer.write_u8(header.major_version)?;
+        writer.write_u8(header.minor_version)?;
+        writer.write_u8(header.big_endian as u8)?;
+        writer.write_u8(header.unused)?;
+
+        if self.version >= OsirisVersion::ADD_VERSION_STRING {
+            let mut version_buffer = [0u8; VERSION_BUFFER_SIZE];
+            let len = header.version_buffer.len().min(VERSION_BUFFER_SIZE);
+            version_buffer[..len].copy_from_slice(&header.version_buffer[..len]);
+            writer
+                .write_all(&version_buffer)
+                .map_err(|e| format!("failed writing Osiris version buffer: {e}"))?;
+        }
+        if self.version >= OsirisVersion::ADD_DEBUG_FLAGS {
+            writer.write_u32(header.debug_flags)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a `u32` count, then the items.
+    fn write_list<W: Write, T>(
+        &mut self,
+        writer: &mut W,
+        items: &[T],
+        write_item: impl Fn(&mut Self, &mut W, &T) -> Result<(), String>,
+    ) -> Result<(), String> {
+        writer.write_u32(
+            u32::try_from(items.len()).map_err(|_| format!("too many items: {}", items.len()))?,
+        )?;
+        for item in items {
+            write_item(self, writer, item)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a `u8` count, then the items.
+    fn write_short_list<W: Write, T>(
+        &mut self,
+        writer: &mut W,
+        items: &[T],
+        write_item: impl Fn(&mut Self, &mut W, &T) -> Result<(), String>,
+    ) -> Result<(), String> {
+        writer.write_u8(
+            u8::try_from(items.len())
+                .map_err(|_| format!("too many items for a short list: {}", items.len()))?,
+        )?;
+        for item in items {
+            write_item(self, writer, item)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a null-terminated, scrambled UTF-8 string.
+    fn write_string<W: Write>(&mut self, writer: &mut W, s: &str) -> Result<(), String> {
+        if s.as_bytes().contains(&0) {
+            return Err(format!("string '{s}' contains a null byte"));
+        }
+        let bytes: Vec<u8> = s.bytes().chain([0]).map(|b| b ^ self.scramble).collect();
+        writer
+            .write_all(&bytes)
+            .map_err(|e| format!("failed writing string '{s}': {e}"))
+    }
+
+    fn write_type<W: Write>(&mut self, writer: &mut W, ty: &OsirisType) -> Result<(), String> {
+        self.write_string(writer, &ty.name)?;
+        writer.write_u8(ty.index)?;
+        if self.version >= OsirisVersion::TYPE_ALIASES {
+            writer.write_u8(ty.alias)?;
+        }
+        Ok(())
+    }
+
+    fn write_enum<W: Write>(
+        &mut self,
+        writer: &mut W,
+        osiris_enum: &OsirisEnum,
+    ) -> Result<(), String> {
+        writer.write_u16(osiris_enum.type_id)?;
+        self.write_list(writer, &osiris_enum.elements, |w, writer, (name, value)| {
+            w.write_string(writer, name)?;
+            writer.write_u64(*value)
+        })
+    }
+
+    fn write_div_object<W: Write>(
+        &mut self,
+        writer: &mut W,
+        div_object: &OsirisDivObject,
+    ) -> Result<(), String> {
+        self.write_string(writer, &div_object.name)?;
+        writer.write_u8(div_object.ty)?;
+        for key in div_object.keys {
+            writer.write_u32(key)?;
+        }
+        Ok(())
+    }
+
+    fn write_function<W: Write>(
+        &mut self,
+        writer: &mut W,
+        function: &OsirisFunction,
+    ) -> Result<(), String> {
+        writer.write_u32(function.line)?;
+        writer.write_u32(function.condition_references)?;
+        writer.write_u32(function.action_references)?;
+        writer.write_u32(function.node)?;
+        writer.write_u8(function.ty)?;
+        for meta in function.meta {
+            writer.write_u32(meta)?;
+        }
+        self.write_string(writer, &function.name)?;
+        self.write_list(writer, &function.out_param_mask, |_, writer, mask| {
+            writer.write_u8(*mask)
+        })?;
+        self.write_short_list(writer, &function.parameters, |_, writer, ty| {
+            writer.write_u16(*ty)
+        })
+    }
+
+    fn write_node<W: Write>(&mut self, writer: &mut W, node: &OsirisNode) -> Result<(), String> {
+        writer.write_u8(node.kind.type_id())?;
+        writer.write_u32(node.id)?;
+        writer.write_u32(node.database)?;
+        self.write_string(writer, &node.name)?;
+        if !node.name.is_empty() {
+            writer.write_u8(node.num_params)?;
+        }
+
+        match &node.kind {
+            OsirisNodeKind::Database { referenced_by } | OsirisNodeKind::Proc { referenced_by } => {
+                self.write_list(writer, referenced_by, |_, writer, entry| {
+                    write_entry(writer, entry)
+                })?;
+            }
+            OsirisNodeKind::DivQuery
+            | OsirisNodeKind::InternalQuery
+            | OsirisNodeKind::UserQuery => {}
+            OsirisNodeKind::And(join) | OsirisNodeKind::NotAnd(join) => write_join(writer, join)?,
+            OsirisNodeKind::RelOp {
+                relation,
+                left_value_index,
+                right_value_index,
+                left_value,
+                right_value,
+                rel_op,
+            } => {
+                write_relation(writer, relation)?;
+                writer.write_i8(*left_value_index)?;
+                writer.write_i8(*right_value_index)?;
+                self.write_value(writer, left_value)?;
+                self.write_value(writer, right_value)?;
+                writer.write_i32(*rel_op)?;
+            }
+            OsirisNodeKind::Rule {
+                relation,
+                calls,
+                variables,
+                line,
+                is_query,
+            } => {
+                write_relation(writer, relation)?;
+                self.write_list(writer, calls, Self::write_call)?;
+                self.write_short_list(writer, variables, |w, writer, variable| {
+                    writer.write_u8(1)?;
+                    w.write_variable(writer, variable)
+                })?;
+                writer.write_u32(*line)?;
+                if self.version >= OsirisVersion::ADD_QUERY {
+                    writer.write_u8(*is_query as u8)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_adapter<W: Write>(
+        &mut self,
+        writer: &mut W,
+        adapter: &OsirisAdapter,
+    ) -> Result<(), String> {
+        writer.write_u32(adapter.index)?;
+        self.write_short_list(writer, &adapter.constants, |w, writer, (index, value)| {
+            writer.write_u8(*index)?;
+            w.write_value(writer, value)
+        })?;
+        self.write_short_list(writer, &adapter.logical_indices, |_, writer, index| {
+            writer.write_i8(*index)
+        })?;
+        self.write_short_list(
+            writer,
+            &adapter.logical_to_physical,
+            |_, writer, (logical, physical)| {
+                writer.write_u8(*logical)?;
+                writer.write_u8(*physical)
+            },
+        )
+    }
+
+    fn write_database<W: Write>(
+        &mut self,
+        writer: &mut W,
+        database: &OsirisDatabase,
+    ) -> Result<(), String> {
+        writer.write_u32(database.index)?;
+        self.write_short_list(writer, &database.parameters, |_, writer, ty| {
+            writer.write_u16(*ty)
+        })?;
+        self.write_list(writer, &database.facts, |w, writer, fact| {
+            w.write_short_list(writer, &fact.columns, Self::write_value)
+        })
+    }
+
+    fn write_goal<W: Write>(&mut self, writer: &mut W, goal: &OsirisGoal) -> Result<(), String> {
+        writer.write_u32(goal.index)?;
+        self.write_string(writer, &goal.name)?;
+        writer.write_u8(goal.sub_goal_combination)?;
+        self.write_list(writer, &goal.parent_goals, |_, writer, goal| {
+            writer.write_u32(*goal)
+        })?;
+        self.write_list(writer, &goal.sub_goals, |_, writer, goal| {
+            writer.write_u32(*goal)
+        })?;
+        writer.write_u8(goal.flags)?;
+        if self.version >= OsirisVersion::ADD_INIT_EXIT_CALLS {
+            self.write_list(writer, &goal.init_calls, Self::write_call)?;
+            self.write_list(writer, &goal.exit_calls, Self::write_call)?;
+        }
+        Ok(())
+    }
+
+    fn write_call<W: Write>(&mut self, writer: &mut W, call: &OsirisCall) -> Result<(), String> {
+        self.write_string(writer, &call.name)?;
+        if !call.name.is_empty() {
+            match &call.parameters {
+                Some(parameters) => {
+                    writer.write_u8(1)?;
+                    self.write_short_list(
+                        writer,
+                        parameters,
+                        |w, writer, parameter| match parameter {
+                            OsirisCallParameter::Variable(variable) => {
+                                writer.write_u8(1)?;
+                                w.write_variable(writer, variable)
+                            }
+                            OsirisCallParameter::Value(value) => {
+                                writer.write_u8(0)?;
+                                w.write_typed_value(writer, value)
+                            }
+                        },
+                    )?;
+                }
+                None => writer.write_u8(0)?,
+            }
+            writer.write_u8(call.negate as u8)?;
+        }
+        writer.write_i32(call.goal_id_or_debug_hook)
+    }
+
+    fn write_value<W: Write>(&mut self, writer: &mut W, value: &OsirisValue) -> Result<(), String> {
+        match &value.data {
+            OsirisValueData::Legacy(v) => {
+                writer.write_u8(b'1')?;
+                writer.write_u32(value.type_id)?;
+                writer.write_i32(*v)
+            }
+            OsirisValueData::Enum(data) => {
+                writer.write_u8(b'e')?;
+                writer.write_u16(u16::try_from(value.type_id).map_err(|_| {
+                    format!(
+                        "enumeration type id {} does not fit in 16 bits",
+                        value.type_id
+                    )
+                })?)?;
+                self.write_value_data(writer, data)
+            }
+            data => {
+                writer.write_u8(b'0')?;
+                if self.version >= OsirisVersion::ENUMS {
+                    writer.write_u16(u16::try_from(value.type_id).map_err(|_| {
+                        format!("type id {} does not fit in 16 bits", value.type_id)
+                    })?)?;
+                } else {
+                    writer.write_u32(value.type_id)?;
+                }
+                self.write_value_data(writer, data)
+            }
+        }
+    }
+
+    fn write_value_data<W: Write>(
+        &mut self,
+        writer: &mut W,
+        data: &OsirisValueData,
+    ) -> Result<(), String> {
+        match data {
+            OsirisValueData::None => Ok(()),
+            OsirisValueData::Integer(v) => writer.write_i32(*v),
+            OsirisValueData::Integer64(v) => writer.write_i64(*v),
+            OsirisValueData::Real(v) => writer.write_f32(*v),
+            OsirisValueData::String(Some(s)) => {
+                writer.write_u8(1)?;
+                self.write_string(writer, s)
+            }
+            OsirisValueData::String(None) => writer.write_u8(0),
+            OsirisValueData::Enum(_) | OsirisValueData::Legacy(_) => {
+                Err("enumeration and legacy values cannot be nested".to_string())
+            }
+        }
+    }
+
+    fn write_typed_value<W: Write>(
+        &mut self,
+        writer: &mut W,
+        value: &OsirisTypedValue,
+    ) -> Result<(), String> {
+        self.write_value(writer, &value.value)?;
+        writer.write_u8(value.is_valid as u8)?;
+        writer.write_u8(value.out_param as u8)?;
+        writer.write_u8(value.is_a_type as u8)
+    }
+
+    fn write_variable<W: Write>(
+        &mut self,
+        writer: &mut W,
+        variable: &OsirisVariable,
+    ) -> Result<(), String> {
+        self.write_typed_value(writer, &variable.value)?;
+        writer.write_i8(variable.index)?;
+        writer.write_u8(variable.unused as u8)?;
+        writer.write_u8(variable.adapted as u8)
+    }
+}
+
+fn write_entry<W: Write>(writer: &mut W, entry: &OsirisNodeEntry) -> Result<(), String> {
+    writer.write_u32(entry.node)?;
+    writer.write_u32(entry.entry_point)?;
+    writer.write_u32(entry.goal)
+}
+
+fn write_join<W: Write>(writer: &mut W, join: &OsirisJoin) -> Result<(), String> {
+    write_entry(writer, &join.next)?;
+    writer.write_u32(join.left_parent)?;
+    writer.write_u32(join.right_parent)?;
+    writer.write_u32(join.adapter1)?;
+    writer.write_u32(join.adapter2)?;
+    writer.write_u32(join.database1)?;
+    writer.write_u8(join.database1_indirection)?;
+    write_entry(writer, &join.database1_join)?;
+    writer.write_u32(join.database2)?;
+    writer.write_u8(join.database2_indirection)?;
+    write_entry(writer, &join.database2_join)
+}
+
+fn write_relation<W: Write>(writer: &mut W, relation: &OsirisRelation) -> Result<(), String> {
+    write_entry(writer, &relation.next)?;
+    writer.write_u32(relation.parent)?;
+    writer.write_u32(relation.adapter)?;
+    writer.write_u32(relation.rel_database_node)?;
+    write_entry(writer, &relation.rel_join)?;
+    writer.write_u8(relation.rel_database_indirection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osiris_reader::OsirisReader;
+    use crate::test_support::sample_story;
+
+    fn round_trip(story: &Story) -> Result<Story, String> {
+        let bytes = OsirisWriter::new().write_bytes(story)?;
+        let read = OsirisReader::new().read_bytes(&bytes)?;
+        assert_eq!(OsirisWriter::new().write_bytes(&read)?, bytes);
+        Ok(read)
+    }
+
+    #[test]
+    fn stories_round_trip_byte_for_byte() -> Result<(), String> {
+        let story = sample_story();
+        assert!(round_trip(&story)? == story);
+
+        // Before string scrambling, type aliases and enumerations
+        let mut story = Story {
+            header: sample_story().header,
+            ..Default::default()
+        };
+        story.header.minor_version = 3;
+        assert!(round_trip(&story)? == story);
+        Ok(())
+    }
+
+    #[test]
+    fn global_flags_are_set_and_cleared() -> Result<(), String> {
+        let mut story = sample_story();
+        assert!(story.is_global_flag_set("Flag_A"));
+        assert!(!story.is_global_flag_set("Flag_B"));
+
+        assert!(story.set_global_flag("Flag_B")?);
+        assert!(!story.set_global_flag("Flag_B")?);
+        assert!(story.clear_global_flag("Flag_A")?);
+        assert!(!story.clear_global_flag("Flag_A")?);
+
+        let story = round_trip(&story)?;
+        assert!(story.is_global_flag_set("Flag_B"));
+        assert!(!story.is_global_flag_set("Flag_A"));
+        let flag = story.facts(GLOBAL_FLAG_DATABASE)[0].columns[0].clone();
+        assert_eq!(flag.type_id, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn facts_are_added_and_removed() -> Result<(), String> {
+        let mut story = sample_story();
+        assert!(story.add_fact("DB_Counter", &["Silver", "3"])?);
+        assert!(!story.add_fact("DB_Counter", &["Silver", "3"])?);
+        assert!(story.add_fact("DB_Counter", &["Single"])?);
+        assert!(story.add_fact("DB_Color", &["Red"])?);
+
+        assert!(story.add_fact("DB_Counter", &["Silver", "three"]).is_err());
+        assert!(story.add_fact("DB_Counter", &["a", "1", "2"]).is_err());
+        assert!(story.add_fact("DB_Missing", &["a"]).is_err());
+
+        assert_eq!(story.remove_fact("DB_Counter", &["Gold", "10"])?, 1);
+        assert_eq!(story.remove_fact("DB_Counter", &["Gold", "10"])?, 0);
+
+        let story = round_trip(&story)?;
+        let facts: Vec<String> = story
+            .facts("DB_Counter")
+            .iter()
+            .map(|fact| fact.to_string())
+            .collect();
+        assert_eq!(facts, ["(\"Silver\", 3)", "(\"\")", "(\"Single\")"]);
+        let colors: Vec<String> = story
+            .facts("DB_Color")
+            .iter()
+            .map(|fact| fact.to_string())
+            .collect();
+        assert_eq!(colors, ["(2)", "(1)"]);
+        Ok(())
+    }
+}
@@ -5,15 +5,22 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::abstract_file_info::{CompressionMethod, PackagedFileInfo};
+use crate::abstract_file_info::PackagedFileInfo;
 use crate::bin_utils;
-use crate::bin_utils::ReadExt;
-use crate::file_entry::{FileEntry18, SIZE_OF_FILE_ENTRY_18};
+use crate::bin_utils::{LZ4_MAX_RATIO, ReadExt};
+use crate::file_entry::FileEntry18;
+use crate::loca::LocaReader;
+use crate::localization::LocalizationTable;
 use crate::lsf_reader::{LSFReader, Resource};
 use crate::lspk_header::LSPKHeader16;
 use crate::package_version::PackageVersion;
+use crate::record::{self, FixedSizeRecord};
+use crate::salvage::{self, LostEntry, SalvageReport};
 use crate::{LSPK_SIGNATURE, package::Package};
 
+/// Signature and `LSPKHeader16`, after which file data starts
+const HEADER_SIZE_V18: u64 = 40;
+
 pub struct PackageReader {
     file_name: String,
     reader: Cursor<Vec<u8>>,
@@ -104,6 +111,112 @@ impl PackageReader {
         Ok(package)
     }
 
+    /// Recovers what can be recovered from a truncated or corrupted package.
+    ///
+    /// If the file list can still be read, its entries are checked one by one and those whose
+    /// data is missing or broken are reported as lost. Otherwise, the data area is scanned for
+    /// payloads with a known signature, which are listed under `salvaged/`.
+    pub fn salvage(&mut self) -> Result<SalvageReport, String> {
+        println!("Salvaging {} ...", self.file_name);
+        let file_len = self.reader.get_ref().len() as u64;
+        let mut package = Package::new();
+        let mut lost = vec![];
+
+        self.reader
+            .rewind()
+            .map_err(|e| format!("failed to rewind package: {e}"))?;
+
+        let header = self.read_salvage_header();
+        let (data_start, file_list) = match &header {
+            Ok(header) => {
+                package.metadata.flags = header.flags;
+                package.metadata.priority = header.priority;
+                package.version = PackageVersion::V18;
+
+                let file_list = if header.file_list_offset < file_len {
+                    self.reader
+                        .seek(SeekFrom::Start(header.file_list_offset))
+                        .map_err(|e| format!("seek to file list offset failed: {e}"))
+                        .and_then(|_| self.read_file_list_v18())
+                } else {
+                    Err(format!(
+                        "file list offset {:#X} is past the end of the file ({file_len} bytes)",
+                        header.file_list_offset
+                    ))
+                };
+                (HEADER_SIZE_V18.min(file_len), file_list)
+            }
+            Err(e) => (0, Err(format!("unreadable package header: {e}"))),
+        };
+
+        let file_list_error = match file_list {
+            Ok(files) => {
+                for pfi in files {
+                    let end = pfi.offset_in_file + pfi.size_on_disk as u64;
+                    let result = if end > file_len {
+                        Err(format!(
+                            "data ends at {end:#X}, past the end of the file ({file_len} bytes)"
+                        ))
+                    } else {
+                        self.decompress_file(&pfi).map(|_| ())
+                    };
+
+                    match result {
+                        Ok(()) => package.files.push(pfi),
+                        Err(reason) => lost.push(LostEntry {
+                            name: pfi.name,
+                            reason,
+                        }),
+                    }
+                }
+                None
+            }
+            Err(e) => {
+                // A corrupt header can point the file list inside the header itself
+                let data_end = match &header {
+                    Ok(header) => header.file_list_offset.clamp(data_start, file_len),
+                    Err(_) => file_len,
+                };
+                let data = &self.reader.get_ref()[data_start as usize..data_end as usize];
+                package.files = salvage::scan_payloads(data, data_start);
+                Some(e)
+            }
+        };
+
+        println!(
+            "salvaged {} files, lost {} files",
+            package.files.len(),
+            lost.len()
+        );
+
+        Ok(SalvageReport {
+            package,
+            lost,
+            file_list_error,
+        })
+    }
+
+    fn read_salvage_header(&mut self) -> Result<LSPKHeader16, String> {
+        let mut signature = [0; 4];
+        self.reader
+            .read_exact(&mut signature)
+            .map_err(|e| format!("could not read 4-byte signature from beginning: {e}"))?;
+
+        if signature != LSPK_SIGNATURE {
+            return Err("missing LSPK signature".to_string());
+        }
+
+        let header: LSPKHeader16 =
+            bincode::serde::decode_from_std_read(&mut self.reader, bincode::config::legacy())
+                .map_err(|e| format!("failed to deserialize LSPKHeader16: {e}"))?;
+
+        if header.version != PackageVersion::V18 as u32 {
+            return Err(format!("unsupported package version {}", header.version));
+        }
+
+        Ok(header)
+    }
+
     fn read_file_list_v18(&mut self) -> Result<Vec<PackagedFileInfo>, String> {
         let num_files = self
             .reader
@@ -115,6 +228,21 @@ impl PackageReader {
             .read_u32()
             .map_err(|e| format!("failed reading compressed size bytes: {e}"))?;
 
+        // Both sizes are untrusted: check them before allocating anything for the list
+        let remaining = (self.reader.get_ref().len() as u64).saturating_sub(self.reader.position());
+        if compressed_size as u64 > remaining {
+            return Err(format!(
+                "compressed file list of {compressed_size} bytes is past the end of the file"
+            ));
+        }
+        let filebuffer_size = FileEntry18::SIZE as u64 * num_files as u64;
+        if filebuffer_size > compressed_size as u64 * LZ4_MAX_RATIO {
+            return Err(format!(
+                "{num_files} file entries cannot fit in a {compressed_size}-byte compressed file list"
+            ));
+        }
+        let filebuffer_size = filebuffer_size as usize;
+
         let mut compressed_file_list = vec![0u8; compressed_size as usize];
         let read = self
             .reader
@@ -125,7 +253,6 @@ impl PackageReader {
             return Err("0-sized compressed file list".to_string());
         }
 
-        let filebuffer_size = SIZE_OF_FILE_ENTRY_18 * num_files as usize;
         let uncompressed_list = lz4_flex::decompress(&compressed_file_list, filebuffer_size)
             .map_err(|e| format!("failed to decompress LZ4 package: {e}"))?;
 
@@ -136,19 +263,7 @@ impl PackageReader {
             ));
         }
 
-        // The following doesn't work, for some reason:
-        //      let uf_reader = BufReader::new(&uncompressed_list[..]);
-        //      let entries: Vec<FileEntry18> = match bincode::deserialize_from(uf_reader).unwrap();
-        // That's why we have to iterate over the bytes in chunks
-
-        let file_entries = uncompressed_list
-            .chunks_exact(SIZE_OF_FILE_ENTRY_18)
-            .map(|c| {
-                bincode::serde::decode_from_slice(c, bincode::config::legacy())
-                    .map(|(r, _)| r)
-                    .map_err(|e| format!("failed to deserialize FileEntry18 from binary: {e}"))
-            })
-            .collect::<Result<Vec<FileEntry18>, _>>()?;
+        let (file_entries, _) = record::decode_records::<FileEntry18>(&uncompressed_list);
 
         let files = file_entries
             .into_iter()
@@ -202,10 +317,7 @@ impl PackageReader {
             current_size += file_size;
 
             let pfi @ PackagedFileInfo {
-                name,
-                flags,
-                size_on_disk,
-                ..
+                name, size_on_disk, ..
             } = file;
             println!(
                 "unpacking {} ({} bytes) ({} out of {} bytes)",
@@ -220,13 +332,13 @@ impl PackageReader {
                 root_output_dir.clone()
             };
 
-            if !file_output_dir.exists() {
-                if let Err(e) = DirBuilder::new().create(&file_output_dir) {
-                    return Err(format!(
-                        "failed to create directory '{}': {e}",
-                        file_output_dir.to_string_lossy()
-                    ));
-                };
+            if !file_output_dir.exists()
+                && let Err(e) = DirBuilder::new().create(&file_output_dir)
+            {
+                return Err(format!(
+                    "failed to create directory '{}': {e}",
+                    file_output_dir.to_string_lossy()
+                ));
             }
 
             let file_path = if let Some(file_name) = pfi.name.file_name() {
@@ -235,10 +347,6 @@ impl PackageReader {
                 return Err("no file name".to_string());
             };
 
-            if (flags & 0x0F) == CompressionMethod::None as u8 {
-                todo!("implement uncompressed stream");
-            }
-
             if *size_on_disk > 0x7fffffff {
                 return Err(format!(
                     "File '{}' is over 2GB ({} bytes), which is not supported yet!",
@@ -270,6 +378,12 @@ impl PackageReader {
     }
 
     pub fn decompress_file(&mut self, pfi: &PackagedFileInfo) -> Result<Vec<u8>, String> {
+        let compressed = self.read_raw_file(pfi)?;
+        bin_utils::decompress(&compressed, pfi.uncompressed_size, pfi.flags, false)
+    }
+
+    /// Bytes of a file as stored in the package, still compressed.
+    pub fn read_raw_file(&mut self, pfi: &PackagedFileInfo) -> Result<Vec<u8>, String> {
         let mut compressed = vec![0u8; pfi.size_on_disk];
 
         self.reader
@@ -287,7 +401,7 @@ impl PackageReader {
             todo!("compute and check crc32");
         }
 
-        bin_utils::decompress(&compressed, pfi.uncompressed_size, pfi.flags, false)
+        Ok(compressed)
     }
 
     pub fn extract_file(
@@ -302,10 +416,7 @@ impl PackageReader {
         };
 
         let pfi @ PackagedFileInfo {
-            name,
-            flags,
-            size_on_disk,
-            ..
+            name, size_on_disk, ..
         } = file;
         let file_output_dir = if let Some(parent_dir) = name.parent() {
             root_output_dir.join(parent_dir)
@@ -313,13 +424,13 @@ impl PackageReader {
             root_output_dir.clone()
         };
 
-        if !file_output_dir.exists() {
-            if let Err(e) = DirBuilder::new().create(&file_output_dir) {
-                return Err(format!(
-                    "failed to create directory '{}': {e}",
-                    file_output_dir.to_string_lossy()
-                ));
-            };
+        if !file_output_dir.exists()
+            && let Err(e) = DirBuilder::new().create(&file_output_dir)
+        {
+            return Err(format!(
+                "failed to create directory '{}': {e}",
+                file_output_dir.to_string_lossy()
+            ));
         }
 
         let file_path = if let Some(file_name) = pfi.name.file_name() {
@@ -328,10 +439,6 @@ impl PackageReader {
             return Err("no file name".to_string());
         };
 
-        if (flags & 0x0F) == CompressionMethod::None as u8 {
-            todo!("implement uncompressed stream");
-        }
-
         if *size_on_disk > 0x7fffffff {
             return Err(format!(
                 "File '{}' is over 2GB ({size_on_disk} bytes), which is not supported yet!",
@@ -385,4 +492,116 @@ impl PackageReader {
             .map(|pfi| LSFReader::new().read(self, pfi))
             .collect()
     }
+
+    /// Loads every `Localization/<language>/*.loca` (or `*.xml`) file of the package into one table.
+    pub fn load_localization(
+        &mut self,
+        package: &Package,
+        language: &str,
+    ) -> Result<LocalizationTable, String> {
+        let mut table = LocalizationTable::new();
+        let loca_files = package.files.iter().filter(|pfi| {
+            let mut components = pfi.name.components().map(|c| c.as_os_str());
+            components
+                .next()
+                .is_some_and(|c| c.eq_ignore_ascii_case("localization"))
+                && components
+                    .next()
+                    .is_some_and(|c| c.eq_ignore_ascii_case(language))
+                && pfi.name.extension().is_some_and(|e| {
+                    e.eq_ignore_ascii_case("loca") || e.eq_ignore_ascii_case("xml")
+                })
+        });
+
+        for pfi in loca_files {
+            table.extend(LocaReader::new().read(self, pfi)?);
+        }
+
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader_for(bytes: Vec<u8>) -> PackageReader {
+        PackageReader {
+            file_name: "corrupt.lsv".to_string(),
+            reader: Cursor::new(bytes),
+        }
+    }
+
+    /// V18 package with `data` after the header, followed by a file list holding `num_files`
+    /// and `compressed_list`.
+    fn package_bytes(data: &[u8], num_files: u32, compressed_list: &[u8]) -> Vec<u8> {
+        let mut bytes = LSPK_SIGNATURE.to_vec();
+        bytes.extend_from_slice(&(PackageVersion::V18 as u32).to_le_bytes());
+        bytes.extend_from_slice(&(HEADER_SIZE_V18 + data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(8 + compressed_list.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes.extend_from_slice(&num_files.to_le_bytes());
+        bytes.extend_from_slice(&(compressed_list.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(compressed_list);
+        bytes
+    }
+
+    #[test]
+    fn salvage_with_oversized_file_list_counts() -> Result<(), String> {
+        for (num_files, compressed_size) in [(u32::MAX, 8), (1, u32::MAX)] {
+            let mut bytes = package_bytes(&[0xAB; 16], num_files, &[0; 8]);
+            let size_offset = bytes.len() - 12;
+            bytes[size_offset..size_offset + 4].copy_from_slice(&compressed_size.to_le_bytes());
+
+            let report = reader_for(bytes.clone()).salvage()?;
+            assert!(report.file_list_error.is_some());
+            assert!(report.package.files.is_empty());
+            assert!(reader_for(bytes).read().is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn salvage_recovers_zlib_payload_without_file_list() -> Result<(), String> {
+        let json = br#"{"Name": "recovered"}"#;
+        let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(json).map_err(|e| e.to_string())?;
+        let zlib = encoder.finish().map_err(|e| e.to_string())?;
+
+        let mut data = vec![0x11; 5];
+        data.extend(&zlib);
+        data.extend([0x22; 7]);
+        let mut reader = reader_for(package_bytes(&data, u32::MAX, &[0; 8]));
+        let report = reader.salvage()?;
+
+        assert!(report.file_list_error.is_some());
+        let [file] = &report.package.files[..] else {
+            panic!("expected one salvaged file, got {:?}", report.package.files);
+        };
+        assert_eq!(file.offset_in_file, HEADER_SIZE_V18 + 5);
+        assert_eq!(file.name, PathBuf::from("salvaged/0000002D.json"));
+        assert_eq!(reader.decompress_file(file)?, json);
+        Ok(())
+    }
+
+    #[test]
+    fn salvage_with_file_list_offset_inside_header() -> Result<(), String> {
+        let mut bytes = LSPK_SIGNATURE.to_vec();
+        bytes.extend_from_slice(&(PackageVersion::V18 as u32).to_le_bytes());
+        // File list offset, pointing back into the header
+        bytes.extend_from_slice(&10u64.to_le_bytes());
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&[0xAB; 64]);
+
+        let report = reader_for(bytes).salvage()?;
+        assert!(report.file_list_error.is_some());
+        assert!(report.package.files.is_empty());
+        Ok(())
+    }
 }
@@ -0,0 +1,263 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::LSPK_SIGNATURE;
+use crate::bin_utils::{self, WriteExt};
+use crate::file_entry::FileEntry18;
+use crate::package::Package;
+use crate::package_reader::PackageReader;
+use crate::package_version::PackageVersion;
+use crate::record::FixedSizeRecord;
+
+/// LZ4 compression, with the default compression level, used for files added without flags.
+pub const DEFAULT_FILE_FLAGS: u8 = 0x22;
+
+/// Signature and header, after which file data starts
+const HEADER_SIZE_V18: u64 = 40;
+
+struct PackagedData {
+    name: PathBuf,
+    flags: u8,
+    uncompressed_size: usize,
+    /// Bytes as stored in the package, compressed according to `flags`.
+    data: Vec<u8>,
+}
+
+/// Builds a v18 package, from scratch or from the files of an existing one.
+///
+/// Files copied from another package keep their stored bytes and are not recompressed.
+/// The MD5 of the header is left zeroed, which the game does not check.
+#[derive(Default)]
+pub struct PackageWriter {
+    flags: u8,
+    priority: u8,
+    files: Vec<PackagedData>,
+}
+
+impl PackageWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from every file of `package`, in the same order and with the same metadata.
+    pub fn from_package(reader: &mut PackageReader, package: &Package) -> Result<Self, String> {
+        let mut writer = Self {
+            flags: package.metadata.flags,
+            priority: package.metadata.priority,
+            files: Vec::with_capacity(package.files.len()),
+        };
+        for pfi in &package.files {
+            writer.files.push(PackagedData {
+                name: pfi.name.clone(),
+                flags: pfi.flags,
+                uncompressed_size: pfi.uncompressed_size,
+                data: reader.read_raw_file(pfi)?,
+            });
+        }
+        Ok(writer)
+    }
+
+    /// Names of the files to be written, in order.
+    pub fn file_names(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|file| file.name.as_path())
+    }
+
+    /// Compresses `bytes` according to `flags` and adds them as `name`, replacing a file of
+    /// the same name if there is one.
+    pub fn add_file(&mut self, name: &Path, bytes: &[u8], flags: u8) -> Result<(), String> {
+        let data = PackagedData {
+            name: name.to_path_buf(),
+            flags,
+            uncompressed_size: if flags & 0x0F == 0 { 0 } else { bytes.len() },
+            data: bin_utils::compress(bytes, flags)?,
+        };
+        match self.position(name) {
+            Some(i) => self.files[i] = data,
+            None => self.files.push(data),
+        }
+        Ok(())
+    }
+
+    /// Replaces the contents of an existing file, compressed the way the file was.
+    pub fn replace_file(&mut self, name: &Path, bytes: &[u8]) -> Result<(), String> {
+        let i = self
+            .position(name)
+            .ok_or_else(|| format!("could not find {} in packaged files", name.display()))?;
+        let flags = self.files[i].flags;
+        let name = self.files[i].name.clone();
+        self.add_file(&name, bytes, flags)
+    }
+
+    /// Removes a file, returning whether it was there.
+    pub fn remove_file(&mut self, name: &Path) -> bool {
+        match self.position(name) {
+            Some(i) => {
+                self.files.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// File names are compared case-insensitively, like the game does.
+    fn position(&self, name: &Path) -> Option<usize> {
+        let name = name.to_string_lossy();
+        self.files
+            .iter()
+            .position(|file| file.name.to_string_lossy().eq_ignore_ascii_case(&name))
+    }
+
+    /// Writes the header, the file data, then the compressed file list.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), String> {
+        let mut entries = Vec::with_capacity(self.files.len() * FileEntry18::SIZE);
+        let mut offset = HEADER_SIZE_V18;
+        for file in &self.files {
+            entries.extend_from_slice(&encode_entry(file, offset)?.encode());
+            offset += file.data.len() as u64;
+        }
+        let file_list_offset = offset;
+        let compressed_entries = lz4_flex::block::compress(&entries);
+        let num_files = u32::try_from(self.files.len())
+            .map_err(|_| format!("too many files: {}", self.files.len()))?;
+        let compressed_size = u32::try_from(compressed_entries.len())
+            .map_err(|_| "file list is too large".to_string())?;
+
+        writer
+            .write_all(&LSPK_SIGNATURE)
+            .map_err(|e| format!("failed writing package signature: {e}"))?;
+        writer.write_u32(PackageVersion::V18 as u32)?;
+        writer.write_u64(file_list_offset)?;
+        writer.write_u32(8 + compressed_size)?;
+        writer.write_u8(self.flags)?;
+        writer.write_u8(self.priority)?;
+        writer
+            .write_all(&[0u8; 16])
+            .map_err(|e| format!("failed writing package MD5: {e}"))?;
+        writer.write_u16(1)?;
+
+        for file in &self.files {
+            writer.write_all(&file.data).map_err(|e| {
+                format!(
+                    "failed writing data of {}: {e}",
+                    file.name.to_string_lossy()
+                )
+            })?;
+        }
+
+        writer.write_u32(num_files)?;
+        writer.write_u32(compressed_size)?;
+        writer
+            .write_all(&compressed_entries)
+            .map_err(|e| format!("failed writing file list: {e}"))
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path)
+            .map_err(|e| format!("failed creating {}: {e}", path.to_string_lossy()))?;
+        let mut writer = BufWriter::new(file);
+        self.write(&mut writer)?;
+        writer
+            .flush()
+            .map_err(|e| format!("failed writing {}: {e}", path.to_string_lossy()))
+    }
+}
+
+fn encode_entry(file: &PackagedData, offset: u64) -> Result<FileEntry18, String> {
+    let name_bytes = file.name.to_string_lossy().replace('\\', "/").into_bytes();
+    // The name is null-terminated
+    if name_bytes.len() >= 256 {
+        return Err(format!(
+            "file name {} is longer than 255 bytes",
+            file.name.to_string_lossy()
+        ));
+    }
+    let mut name = [0u8; 256];
+    name[..name_bytes.len()].copy_from_slice(&name_bytes);
+
+    if offset >> 48 != 0 {
+        return Err(format!("file offset {offset:#X} does not fit in 48 bits"));
+    }
+    let too_large = |_| format!("{} is too large", file.name.to_string_lossy());
+
+    Ok(FileEntry18 {
+        name,
+        offset_in_file_1: offset as u32,
+        offset_in_file_2: (offset >> 32) as u16,
+        archive_part: 0,
+        flags: file.flags,
+        size_on_disk: u32::try_from(file.data.len()).map_err(too_large)?,
+        uncompressed_size: u32::try_from(file.uncompressed_size).map_err(too_large)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn read_files(path: &Path) -> Result<Vec<(String, u8, Vec<u8>)>, String> {
+        let mut reader = PackageReader::new(path)?;
+        let package = reader.read()?;
+        package
+            .files
+            .iter()
+            .map(|pfi| {
+                let name = pfi.name.to_string_lossy().to_string();
+                Ok((name, pfi.flags, reader.decompress_file(pfi)?))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packages_round_trip_through_the_reader() -> Result<(), String> {
+        let dir = TempDir::new("package_writer")?;
+        let original_path = dir.path().join("original.lsv");
+        let edited_path = dir.path().join("edited.lsv");
+        let json = br#"{"Save Name": "Test"}"#.to_vec();
+        let lsf = b"LSOF".repeat(64);
+        let text = b"zlib compressed text ".repeat(16);
+
+        let mut writer = PackageWriter::new();
+        writer.add_file(Path::new("SaveInfo.json"), &json, 0)?;
+        writer.add_file(Path::new("Globals.lsf"), &lsf, DEFAULT_FILE_FLAGS)?;
+        writer.add_file(Path::new("Notes/readme.txt"), &text, 0x21)?;
+        writer.write_to_file(&original_path)?;
+
+        assert_eq!(
+            read_files(&original_path)?,
+            [
+                ("SaveInfo.json".to_string(), 0, json.clone()),
+                ("Globals.lsf".to_string(), DEFAULT_FILE_FLAGS, lsf),
+                ("Notes/readme.txt".to_string(), 0x21, text.clone()),
+            ]
+        );
+
+        let mut reader = PackageReader::new(&original_path)?;
+        let package = reader.read()?;
+        let mut writer = PackageWriter::from_package(&mut reader, &package)?;
+        writer.replace_file(Path::new("globals.LSF"), b"replaced")?;
+        assert!(writer.replace_file(Path::new("Missing.lsf"), b"").is_err());
+        assert!(writer.remove_file(Path::new("SaveInfo.json")));
+        assert!(!writer.remove_file(Path::new("SaveInfo.json")));
+        let names: Vec<&Path> = writer.file_names().collect();
+        assert_eq!(
+            names,
+            [Path::new("Globals.lsf"), Path::new("Notes/readme.txt")]
+        );
+        writer.write_to_file(&edited_path)?;
+
+        assert_eq!(
+            read_files(&edited_path)?,
+            [
+                (
+                    "Globals.lsf".to_string(),
+                    DEFAULT_FILE_FLAGS,
+                    b"replaced".to_vec()
+                ),
+                ("Notes/readme.txt".to_string(), 0x21, text),
+            ]
+        );
+        Ok(())
+    }
+}
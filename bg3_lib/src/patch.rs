@@ -0,0 +1,592 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lsf_reader::{DataType, Node, NodeAttribute, NodeKind, Resource};
+use crate::node_path::NodePath;
+
+/// List of edits to a `Resource`, kept as JSON or TOML text.
+///
+/// ```toml
+/// [[operations]]
+/// op = "set"
+/// path = "Globals/Party/Character[Name=Tav]"
+/// attribute = "Level"
+/// value = "12"
+///
+/// [[operations]]
+/// op = "remove"
+/// path = "Globals/Items/Item[MapKey=Sword]"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Patch {
+    #[serde(default)]
+    pub operations: Vec<PatchOperation>,
+}
+
+/// One edit of a patch. Nodes are addressed by `NodePath`, which picks among children of the
+/// same name by position or by attribute value, e.g. `Region/Node[MapKey=Value]/Child[2]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PatchOperation {
+    /// Sets an attribute of the node at `path`, parsing `value` as LSX does.
+    /// Without `type`, an existing attribute keeps its type; new attributes need one.
+    Set {
+        path: NodePath,
+        attribute: String,
+        value: String,
+        #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+        ty: Option<String>,
+    },
+    /// Adds a node, with its attributes and children, below the node at `parent`.
+    /// `index` is the position among the children of the same name, the last by default.
+    Insert {
+        parent: NodePath,
+        node: PatchNode,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        index: Option<usize>,
+    },
+    /// Removes the node at `path` with its children, or only one of its attributes.
+    Remove {
+        path: NodePath,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attribute: Option<String>,
+    },
+    /// Moves the node at `path`, with its children, below the node at `to`.
+    Move {
+        path: NodePath,
+        to: NodePath,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        index: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PatchNode {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, PatchAttribute>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<PatchNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatchAttribute {
+    /// LSX type name, like `int32` or `FixedString`.
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub value: String,
+}
+
+impl Patch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid JSON patch: {e}"))
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, String> {
+        toml::from_str(toml).map_err(|e| format!("invalid TOML patch: {e}"))
+    }
+
+    /// Reads a `.json` or `.toml` patch file.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read patch file {}: {e}", path.display()))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::from_json(&text),
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Self::from_toml(&text),
+            _ => Err(format!(
+                "unknown patch file extension of {}, expected .json or .toml",
+                path.display()
+            )),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("could not write JSON patch: {e}"))
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string_pretty(self).map_err(|e| format!("could not write TOML patch: {e}"))
+    }
+}
+
+impl Display for PatchOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Set {
+                path,
+                attribute,
+                value,
+                ..
+            } => write!(f, "set {path}@{attribute} to '{value}'"),
+            Self::Insert { parent, node, .. } => write!(f, "insert {} into {parent}", node.name),
+            Self::Remove {
+                path,
+                attribute: Some(attribute),
+            } => write!(f, "remove {path}@{attribute}"),
+            Self::Remove {
+                path,
+                attribute: None,
+            } => write!(f, "remove {path}"),
+            Self::Move { path, to, .. } => write!(f, "move {path} to {to}"),
+        }
+    }
+}
+
+/// Operation of a patch that could not be applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchFailure {
+    /// Position of the operation in `Patch::operations`.
+    pub index: usize,
+    pub operation: String,
+    pub reason: String,
+}
+
+impl Display for PatchFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "operation {} ({}): {}",
+            self.index, self.operation, self.reason
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PatchReport {
+    pub applied: usize,
+    pub failures: Vec<PatchFailure>,
+}
+
+impl PatchReport {
+    /// Whether every operation was applied.
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Resource {
+    /// Applies the operations of `patch` in order, each one seeing the result of the previous
+    /// ones. An operation that does not match is skipped, leaving the resource unchanged,
+    /// and reported.
+    pub fn apply_patch(&mut self, patch: &Patch) -> PatchReport {
+        let mut report = PatchReport::default();
+        let mut removed_nodes = false;
+
+        for (index, operation) in patch.operations.iter().enumerate() {
+            match self.apply_operation(operation) {
+                Ok(()) => report.applied += 1,
+                Err(reason) => report.failures.push(PatchFailure {
+                    index,
+                    operation: operation.to_string(),
+                    reason,
+                }),
+            }
+            removed_nodes |= matches!(
+                operation,
+                PatchOperation::Remove {
+                    attribute: None,
+                    ..
+                }
+            );
+        }
+
+        if removed_nodes {
+            self.regions.remove_unreachable();
+        }
+        report
+    }
+
+    fn apply_operation(&mut self, operation: &PatchOperation) -> Result<(), String> {
+        match operation {
+            PatchOperation::Set {
+                path,
+                attribute,
+                value,
+                ty,
+            } => {
+                let node_idx = self.resolve_patch_path(path)?;
+                let node = &mut self.regions.node_instances[node_idx];
+                let ty = match (ty, node.attributes.get(attribute.as_str())) {
+                    (Some(ty), _) => ty.parse()?,
+                    (None, Some(existing)) => existing.ty,
+                    (None, None) => {
+                        return Err(format!(
+                            "attribute {attribute} does not exist, and no type was given"
+                        ));
+                    }
+                };
+                let new_value = NodeAttribute::parse(ty, value)?;
+                node.attributes
+                    .insert(Arc::from(attribute.as_str()), new_value);
+                // A decoded resource would be written in place of the new value
+                node.embedded.remove(attribute.as_str());
+            }
+            PatchOperation::Insert {
+                parent,
+                node,
+                index,
+            } => {
+                let parent_idx = self.resolve_patch_path(parent)?;
+                // Parse everything first, so that a bad value leaves no partial subtree behind
+                let nodes = flatten_patch_node(node)?;
+                let first_idx = self.regions.node_instances.len();
+                for (position, mut new_node) in nodes.into_iter().enumerate() {
+                    let node_idx = first_idx + position;
+                    let name = new_node.name.clone();
+                    let parent_position = new_node.parent;
+                    new_node.parent = Some(parent_position.map_or(parent_idx, |p| first_idx + p));
+                    self.regions.node_instances.push(new_node);
+                    if let Some(p) = parent_position {
+                        self.regions.node_instances[first_idx + p].append_child(&name, node_idx);
+                    }
+                }
+                self.insert_child(parent_idx, first_idx, *index);
+            }
+            PatchOperation::Remove {
+                path,
+                attribute: Some(attribute),
+            } => {
+                let node_idx = self.resolve_patch_path(path)?;
+                let node = &mut self.regions.node_instances[node_idx];
+                node.attributes
+                    .remove(attribute.as_str())
+                    .ok_or_else(|| format!("attribute {attribute} does not exist"))?;
+                node.embedded.remove(attribute.as_str());
+            }
+            PatchOperation::Remove {
+                path,
+                attribute: None,
+            } => {
+                let node_idx = self.resolve_patch_path(path)?;
+                match self.regions.node_instances[node_idx].parent {
+                    Some(_) => self.detach(node_idx),
+                    None => {
+                        let name = self.regions.node_instances[node_idx].name.clone();
+                        self.regions.regions_indices.remove(&name);
+                    }
+                }
+            }
+            PatchOperation::Move { path, to, index } => {
+                let node_idx = self.resolve_patch_path(path)?;
+                let new_parent_idx = self.resolve_patch_path(to)?;
+                if self.regions.node_instances[node_idx].parent.is_none() {
+                    return Err("regions cannot be moved".to_string());
+                }
+
+                // The new parent must not be the moved node or one of its descendants
+                let mut ancestor = Some(new_parent_idx);
+                while let Some(ancestor_idx) = ancestor {
+                    if ancestor_idx == node_idx {
+                        return Err(format!("{to} is inside the moved node"));
+                    }
+                    ancestor = self.regions.node_instances[ancestor_idx].parent;
+                }
+
+                self.detach(node_idx);
+                self.regions.node_instances[node_idx].parent = Some(new_parent_idx);
+                self.insert_child(new_parent_idx, node_idx, *index);
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_patch_path(&self, path: &NodePath) -> Result<usize, String> {
+        path.resolve(self)
+            .filter(|idx| *idx < self.regions.node_instances.len())
+            .ok_or_else(|| format!("no node matches {path}"))
+    }
+
+    /// Removes a node from the children of its parent. The node stays in the arena.
+    fn detach(&mut self, node_idx: usize) {
+        let node = &self.regions.node_instances[node_idx];
+        let (Some(parent_idx), name) = (node.parent, node.name.clone()) else {
+            return;
+        };
+        let Some(parent) = self.regions.node_instances.get_mut(parent_idx) else {
+            return;
+        };
+        if let Some(siblings) = parent.children.get_mut(&name) {
+            siblings.retain(|idx| *idx != node_idx);
+            if siblings.is_empty() {
+                parent.children.remove(&name);
+            }
+        }
+    }
+
+    fn insert_child(&mut self, parent_idx: usize, child_idx: usize, index: Option<usize>) {
+        let name = self.regions.node_instances[child_idx].name.clone();
+        let siblings = self.regions.node_instances[parent_idx]
+            .children
+            .entry(name)
+            .or_default();
+        let index = index.unwrap_or(siblings.len()).min(siblings.len());
+        siblings.insert(index, child_idx);
+    }
+}
+
+/// Parsed nodes of a `PatchNode` tree in depth-first order, without children. Their parent
+/// is the position of the parent in the returned list (`None` for the root).
+fn flatten_patch_node(root: &PatchNode) -> Result<Vec<Node>, String> {
+    let mut nodes = vec![];
+    let mut stack = vec![(root, None)];
+
+    while let Some((node, parent_position)) = stack.pop() {
+        if node.name.is_empty() {
+            return Err("inserted nodes need a name".to_string());
+        }
+
+        let mut attributes = HashMap::with_capacity(node.attributes.len());
+        for (name, attribute) in &node.attributes {
+            let ty: DataType = attribute.ty.parse()?;
+            let value = NodeAttribute::parse(ty, &attribute.value)
+                .map_err(|e| format!("attribute {name} of {}: {e}", node.name))?;
+            attributes.insert(Arc::from(name.as_str()), value);
+        }
+
+        let position = nodes.len();
+        nodes.push(Node {
+            kind: NodeKind::Node,
+            name: Arc::from(node.name.as_str()),
+            parent: parent_position,
+            attributes,
+            children: Default::default(),
+            embedded: Default::default(),
+        });
+        // Reversed, so that children are appended to their parent in patch order
+        stack.extend(
+            node.children
+                .iter()
+                .rev()
+                .map(|child| (child, Some(position))),
+        );
+    }
+
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsf_reader::NodeAttributeValue;
+    use crate::lsf_writer::LSFWriter;
+    use crate::test_support::{node_mut, sample_resource};
+
+    fn path(path: &str) -> Result<NodePath, String> {
+        path.parse()
+    }
+
+    fn attribute(resource: &Resource, node: &str, name: &str) -> Result<Option<String>, String> {
+        let idx = path(node)?
+            .resolve(resource)
+            .ok_or_else(|| format!("{node} not found"))?;
+        Ok(resource.regions.node_instances[idx]
+            .attributes
+            .get(name)
+            .map(|attr| format!("{attr} ({})", attr.ty)))
+    }
+
+    fn map_keys(resource: &Resource, parent: &str) -> Result<Vec<String>, String> {
+        let idx = path(parent)?
+            .resolve(resource)
+            .ok_or_else(|| format!("{parent} not found"))?;
+        let items = resource.regions.node_instances[idx]
+            .children
+            .get("Item")
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        Ok(items
+            .iter()
+            .map(|idx| {
+                match resource.regions.node_instances[*idx]
+                    .attributes
+                    .get("MapKey")
+                {
+                    Some(key) => key.to_string(),
+                    None => "-".to_string(),
+                }
+            })
+            .collect())
+    }
+
+    fn set(
+        node: &str,
+        attribute: &str,
+        value: &str,
+        ty: Option<&str>,
+    ) -> Result<PatchOperation, String> {
+        Ok(PatchOperation::Set {
+            path: path(node)?,
+            attribute: attribute.to_string(),
+            value: value.to_string(),
+            ty: ty.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn set_keeps_or_changes_types() -> Result<(), String> {
+        let mut resource = sample_resource();
+        let patch = Patch {
+            operations: vec![
+                set("Config", "Version", "4", None)?,
+                set("Config/Option", "Scale", "1.5", Some("float"))?,
+                set("Config/Option", "Enabled", "7", Some("int32"))?,
+                set("Config/Option", "Missing", "1", None)?,
+                set("Config", "Version", "four", None)?,
+            ],
+        };
+
+        let report = resource.apply_patch(&patch);
+        assert_eq!(report.applied, 3);
+        assert!(!report.is_clean());
+        let failures: Vec<String> = report
+            .failures
+            .iter()
+            .map(PatchFailure::to_string)
+            .collect();
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].starts_with("operation 3 (set Config/Option@Missing to '1'): "));
+        assert!(failures[1].starts_with("operation 4 (set Config@Version to 'four'): "));
+
+        assert_eq!(
+            attribute(&resource, "Config", "Version")?.as_deref(),
+            Some("4 (int32)")
+        );
+        assert_eq!(
+            attribute(&resource, "Config/Option", "Scale")?.as_deref(),
+            Some("1.5 (float)")
+        );
+        assert_eq!(
+            attribute(&resource, "Config/Option", "Enabled")?.as_deref(),
+            Some("7 (int32)")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn set_replaces_embedded_resources() -> Result<(), String> {
+        let mut resource = sample_resource();
+        let blob = LSFWriter::new().write_bytes(&sample_resource())?;
+        node_mut(&mut resource, "Config/Option")?.attributes.insert(
+            "Data".into(),
+            NodeAttribute {
+                ty: DataType::ScratchBuffer,
+                value: NodeAttributeValue::Bytes(blob),
+            },
+        );
+        assert!(resource.decode_embedded().is_empty());
+        assert_eq!(node_mut(&mut resource, "Config/Option")?.embedded.len(), 1);
+
+        let report = resource.apply_patch(&Patch {
+            operations: vec![set("Config/Option", "Data", "0102", None)?],
+        });
+        assert!(report.is_clean());
+        assert!(
+            node_mut(&mut resource, "Config/Option")?
+                .embedded
+                .is_empty()
+        );
+
+        let written = LSFWriter::new().write_bytes(&resource)?;
+        let read = crate::lsf_reader::LSFReader::new().read_bytes(&written)?;
+        assert_eq!(
+            attribute(&read, "Config/Option", "Data")?.as_deref(),
+            Some("0102 (ScratchBuffer)")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn insert_remove_and_move_nodes() -> Result<(), String> {
+        let mut resource = sample_resource();
+        let patch = Patch::from_json(
+            r#"{
+                "operations": [
+                    {
+                        "op": "insert",
+                        "parent": "Templates",
+                        "index": 1,
+                        "node": {
+                            "name": "Item",
+                            "attributes": { "MapKey": { "type": "FixedString", "value": "Bow" } },
+                            "children": [{ "name": "Tag" }, { "name": "Extra" }]
+                        }
+                    },
+                    { "op": "remove", "path": "Templates/Item[MapKey=Shield]" },
+                    { "op": "remove", "path": "Templates/Item[MapKey=Bow]/Extra" },
+                    { "op": "remove", "path": "Config", "attribute": "Version" },
+                    { "op": "remove", "path": "Config", "attribute": "Version" },
+                    { "op": "move", "path": "Templates/Item[MapKey=Bow]", "to": "Config", "index": 0 },
+                    { "op": "move", "path": "Templates", "to": "Config" },
+                    { "op": "move", "path": "Config/Item", "to": "Config/Item/Tag" }
+                ]
+            }"#,
+        )?;
+
+        let report = resource.apply_patch(&patch);
+        let failed: Vec<usize> = report.failures.iter().map(|f| f.index).collect();
+        assert_eq!(failed, [4, 6, 7]);
+        assert_eq!(report.applied, 5);
+
+        assert_eq!(map_keys(&resource, "Templates")?, ["Sword", "Sword"]);
+        assert_eq!(map_keys(&resource, "Config")?, ["Bow"]);
+        assert_eq!(attribute(&resource, "Config", "Version")?, None);
+        let bow = path("Config/Item")?
+            .resolve(&resource)
+            .ok_or("Bow not found")?;
+        let children: Vec<&str> = resource.regions.node_instances[bow]
+            .children
+            .keys()
+            .map(|n| &**n)
+            .collect();
+        assert_eq!(children, ["Tag"]);
+        assert_eq!(
+            resource.regions.node_instances[bow].parent,
+            resource.regions.regions_indices.get("Config").copied()
+        );
+
+        // Removed nodes leave the arena, and the result can still be written
+        assert_eq!(resource.regions.node_instances.len(), 9);
+        LSFWriter::new().write_bytes(&resource)?;
+        Ok(())
+    }
+
+    #[test]
+    fn json_and_toml_patches_parse_the_same() -> Result<(), String> {
+        let toml = r#"
+            [[operations]]
+            op = "set"
+            path = "Globals/Party/Character[Name=Tav]"
+            attribute = "Level"
+            value = "12"
+
+            [[operations]]
+            op = "remove"
+            path = "Globals/Items/Item[MapKey=Sword]"
+        "#;
+        let patch = Patch::from_toml(toml)?;
+        assert_eq!(
+            patch.operations,
+            [
+                set("Globals/Party/Character[Name=Tav]", "Level", "12", None)?,
+                PatchOperation::Remove {
+                    path: path("Globals/Items/Item[MapKey=Sword]")?,
+                    attribute: None,
+                },
+            ]
+        );
+        assert_eq!(Patch::from_json(&patch.to_json()?)?, patch);
+        assert_eq!(Patch::from_toml(&patch.to_toml()?)?, patch);
+
+        assert!(Patch::from_json(r#"{"operations": [{"op": "rename"}]}"#).is_err());
+        assert!(Patch::from_toml("[[operations]]\nop = \"set\"\npath = \"Bad/\"").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+/// Table entry stored on disk as a fixed number of little-endian bytes.
+///
+/// Tables of such entries are decoded straight from their slice, one `SIZE`-byte chunk
+/// per entry, without going through a reader or relying on the in-memory layout of the type.
+pub trait FixedSizeRecord: Sized {
+    /// Size of one entry on disk, in bytes.
+    const SIZE: usize;
+
+    /// Decodes an entry from exactly `SIZE` bytes.
+    fn decode(bytes: &[u8]) -> Self;
+}
+
+/// Decodes all whole entries of `bytes`.
+/// Also returns the trailing bytes that were too short to hold one more entry.
+pub fn decode_records<T: FixedSizeRecord>(bytes: &[u8]) -> (Vec<T>, &[u8]) {
+    let chunks = bytes.chunks_exact(T::SIZE);
+    let remainder = chunks.remainder();
+    (chunks.map(T::decode).collect(), remainder)
+}
+
+pub(crate) fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+pub(crate) fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+pub(crate) fn i32_at(bytes: &[u8], offset: usize) -> i32 {
+    u32_at(bytes, offset) as i32
+}
@@ -0,0 +1,222 @@
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+use flate2::bufread::ZlibDecoder;
+
+use crate::abstract_file_info::{CompressionMethod, PackagedFileInfo};
+use crate::bin_utils::{LZ4_FRAME_MAGIC, ZSTD_FRAME_MAGIC};
+use crate::lsf_reader::lsf_file_size;
+use crate::package::Package;
+
+/// Result of salvaging a damaged package.
+pub struct SalvageReport {
+    /// Entries that could be recovered, extractable with the usual `PackageReader` API.
+    pub package: Package,
+    /// Entries of the original file list whose data could not be recovered.
+    pub lost: Vec<LostEntry>,
+    /// Why the original file list could not be used, if it couldn't.
+    /// When set, `package` only holds entries rebuilt by scanning the data area,
+    /// and the names of the lost entries are unknown.
+    pub file_list_error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LostEntry {
+    pub name: PathBuf,
+    pub reason: String,
+}
+
+// hexadecimal values for "RIFF" and "WEBP"
+const RIFF_SIGNATURE: [u8; 4] = [0x52, 0x49, 0x46, 0x46];
+const WEBP_SIGNATURE: [u8; 4] = [0x57, 0x45, 0x42, 0x50];
+
+/// Scans `data` for payloads with a recognizable signature, and rebuilds package entries for them.
+/// `base_offset` is the offset of `data` in the package file.
+///
+/// Recognized payloads are uncompressed LSF, WebP and JSON files, as well as zstd frames,
+/// LZ4 frames and zlib streams. Plain LZ4 blocks have no signature and cannot be found this way.
+pub(crate) fn scan_payloads(data: &[u8], base_offset: u64) -> Vec<PackagedFileInfo> {
+    let mut files = vec![];
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let Some((size_on_disk, uncompressed_size, method)) = identify_payload(&data[offset..])
+        else {
+            offset += 1;
+            continue;
+        };
+
+        let payload = &data[offset..offset + size_on_disk];
+        let extension = match method {
+            CompressionMethod::None => sniff_extension(payload),
+            _ => crate::bin_utils::decompress(payload, uncompressed_size, method as u8, false)
+                .map(|d| sniff_extension(&d))
+                .unwrap_or("bin"),
+        };
+
+        let offset_in_file = base_offset + offset as u64;
+        files.push(PackagedFileInfo {
+            offset_in_file,
+            size_on_disk,
+            uncompressed_size,
+            archive_part: 0,
+            flags: method as u8,
+            crc: 0,
+            name: PathBuf::from(format!("salvaged/{offset_in_file:08X}.{extension}")),
+        });
+
+        offset += size_on_disk;
+    }
+
+    files
+}
+
+/// Size on disk, uncompressed size and compression of the payload starting at `data`, if any.
+fn identify_payload(data: &[u8]) -> Option<(usize, usize, CompressionMethod)> {
+    if data.starts_with(&ZSTD_FRAME_MAGIC) {
+        let size = zstd::zstd_safe::find_frame_compressed_size(data).ok()?;
+        let uncompressed = zstd::decode_all(data.get(..size)?).ok()?;
+        return Some((size, uncompressed.len(), CompressionMethod::ZSTD));
+    }
+
+    if data.starts_with(&LZ4_FRAME_MAGIC) {
+        let size = lz4_frame_size(data)?;
+        let mut uncompressed = vec![];
+        lz4_flex::frame::FrameDecoder::new(data.get(..size)?)
+            .read_to_end(&mut uncompressed)
+            .ok()?;
+        return Some((size, uncompressed.len(), CompressionMethod::LZ4));
+    }
+
+    if is_zlib_header(data) {
+        let mut decoder = ZlibDecoder::new(data);
+        let mut uncompressed = vec![];
+        decoder.read_to_end(&mut uncompressed).ok()?;
+        if uncompressed.is_empty() {
+            return None;
+        }
+        return Some((
+            decoder.total_in() as usize,
+            uncompressed.len(),
+            CompressionMethod::Zlib,
+        ));
+    }
+
+    let raw_size = lsf_file_size(data)
+        .or_else(|| webp_size(data))
+        .or_else(|| json_object_size(data))?;
+
+    Some((raw_size, raw_size, CompressionMethod::None))
+}
+
+fn sniff_extension(data: &[u8]) -> &'static str {
+    if lsf_file_size(data).is_some() {
+        "lsf"
+    } else if webp_size(data).is_some() {
+        "webp"
+    } else if json_object_size(data).is_some() {
+        "json"
+    } else {
+        "bin"
+    }
+}
+
+fn is_zlib_header(data: &[u8]) -> bool {
+    match data {
+        [cmf @ 0x78, flg @ (0x01 | 0x5E | 0x9C | 0xDA), ..] => {
+            (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0
+        }
+        _ => false,
+    }
+}
+
+fn webp_size(data: &[u8]) -> Option<usize> {
+    if !data.starts_with(&RIFF_SIGNATURE) || data.get(8..12)? != WEBP_SIGNATURE {
+        return None;
+    }
+
+    let chunk_size = u32::from_le_bytes(data.get(4..8)?.try_into().ok()?) as usize;
+    let size = chunk_size + 8;
+    (size <= data.len()).then_some(size)
+}
+
+/// Size of the frame by walking its block headers, as LZ4 frames don't store it.
+fn lz4_frame_size(data: &[u8]) -> Option<usize> {
+    let flags = *data.get(4)?;
+    if flags >> 6 != 0b01 {
+        return None;
+    }
+
+    let block_checksum = flags & 0x10 != 0;
+    let content_size = flags & 0x08 != 0;
+    let content_checksum = flags & 0x04 != 0;
+    let dict_id = flags & 0x01 != 0;
+
+    // magic, FLG and BD bytes, optional content size and dictionary id, header checksum
+    let mut pos = 6 + if content_size { 8 } else { 0 } + if dict_id { 4 } else { 0 } + 1;
+
+    loop {
+        let block_size = u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?);
+        pos += 4;
+        if block_size == 0 {
+            break;
+        }
+
+        pos += (block_size & 0x7FFF_FFFF) as usize;
+        if block_checksum {
+            pos += 4;
+        }
+    }
+
+    if content_checksum {
+        pos += 4;
+    }
+
+    (pos <= data.len()).then_some(pos)
+}
+
+/// Size of the JSON object at the start of `data`, found by matching braces.
+/// Only objects starting with a key are considered, to keep random `{` bytes from matching.
+fn json_object_size(data: &[u8]) -> Option<usize> {
+    if *data.first()? != b'{' {
+        return None;
+    }
+
+    let first_token = data[1..].iter().find(|b| !b.is_ascii_whitespace())?;
+    if *first_token != b'"' {
+        return None;
+    }
+
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, b) in data.iter().enumerate() {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    let size = i + 1;
+                    return std::str::from_utf8(&data[..size]).ok().map(|_| size);
+                }
+            }
+            // Control characters other than whitespace don't appear in JSON text
+            b if *b < 0x20 && !b.is_ascii_whitespace() => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
@@ -0,0 +1,247 @@
+use std::path::{Path, PathBuf};
+
+use crate::abstract_file_info::PackagedFileInfo;
+use crate::lsf_reader::{LSFReader, Resource};
+use crate::lsf_writer::LSFWriter;
+use crate::osiris::Story;
+use crate::osiris_reader::OsirisReader;
+use crate::osiris_writer::OsirisWriter;
+use crate::package::Package;
+use crate::package_reader::PackageReader;
+use crate::package_writer::PackageWriter;
+#[cfg(feature = "save-game")]
+use crate::save_game::SaveGame;
+use crate::save_info::SaveInfo;
+use crate::save_meta::SaveMeta;
+use crate::thumbnail::Thumbnail;
+
+/// A `.lsv` save file, opened once and queried through its packaged files.
+pub struct Save {
+    path: PathBuf,
+    reader: PackageReader,
+    package: Package,
+}
+
+impl Save {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut reader = PackageReader::new(path)?;
+        let package = reader.read()?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader,
+            package,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn package(&self) -> &Package {
+        &self.package
+    }
+
+    pub fn reader(&mut self) -> &mut PackageReader {
+        &mut self.reader
+    }
+
+    /// Packaged file with the given name, compared case-insensitively.
+    pub fn find_file(&self, name: &str) -> Option<&PackagedFileInfo> {
+        self.package
+            .files
+            .iter()
+            .find(|pfi| pfi.name.to_string_lossy().eq_ignore_ascii_case(name))
+    }
+
+    pub fn read_file(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let pfi = self
+            .find_file(name)
+            .ok_or_else(|| format!("could not find {name} in packaged files"))?
+            .clone();
+        self.reader.decompress_file(&pfi)
+    }
+
+    pub fn info(&mut self) -> Result<SaveInfo, String> {
+        SaveInfo::from_bytes(&self.read_file("SaveInfo.json")?)
+    }
+
+    pub fn meta(&mut self) -> Result<SaveMeta, String> {
+        let resource = LSFReader::new().read_bytes(&self.read_file("meta.lsf")?)?;
+        SaveMeta::from_resource(&resource)
+    }
+
+    /// Osiris story database of the save, from `StorySave.bin`.
+    pub fn story(&mut self) -> Result<Story, String> {
+        OsirisReader::new().read_bytes(&self.read_file("StorySave.bin")?)
+    }
+
+    pub fn globals(&mut self) -> Result<Resource, String> {
+        self.reader.load_globals(&self.package)
+    }
+
+    /// Characters and party of the save, from `Globals.lsf`.
+    #[cfg(feature = "save-game")]
+    pub fn save_game(&mut self) -> Result<SaveGame, String> {
+        SaveGame::from_globals(self.globals()?)
+    }
+
+    /// Packaged `.WebP` screenshot of the save.
+    pub fn thumbnail_file(&self) -> Option<&PackagedFileInfo> {
+        self.package.files.iter().find(|pfi| {
+            pfi.name
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("webp"))
+        })
+    }
+
+    pub fn thumbnail(&mut self) -> Result<Thumbnail, String> {
+        let pfi = self
+            .thumbnail_file()
+            .ok_or("could not find a WebP thumbnail in packaged files")?
+            .clone();
+        Thumbnail::from_webp(&self.reader.decompress_file(&pfi)?)
+    }
+
+    /// Writer holding every file of the save, to repack it with some of them replaced.
+    pub fn package_writer(&mut self) -> Result<PackageWriter, String> {
+        PackageWriter::from_package(&mut self.reader, &self.package)
+    }
+
+    /// Writes a copy of the save to `output_path`, with `story` as its `StorySave.bin`.
+    pub fn write_with_story(&mut self, story: &Story, output_path: &Path) -> Result<(), String> {
+        let story_bytes = OsirisWriter::new().write_bytes(story)?;
+        let mut writer = self.package_writer()?;
+        writer.replace_file(Path::new("StorySave.bin"), &story_bytes)?;
+        writer.write_to_file(output_path)
+    }
+
+    /// Writes a copy of the save to `output_path`, with `globals` as its `Globals.lsf`,
+    /// like the resource of an edited `SaveGame`.
+    pub fn write_with_globals(
+        &mut self,
+        globals: &Resource,
+        output_path: &Path,
+    ) -> Result<(), String> {
+        let globals_bytes = LSFWriter::new().write_bytes(globals)?;
+        let mut writer = self.package_writer()?;
+        writer.replace_file(Path::new("Globals.lsf"), &globals_bytes)?;
+        writer.write_to_file(output_path)
+    }
+
+    /// Writes a copy of the save to `output_path`, with its thumbnail replaced by `image`,
+    /// in any supported format, scaled to the size of the original thumbnail.
+    pub fn write_with_thumbnail(&mut self, image: &[u8], output_path: &Path) -> Result<(), String> {
+        let name = self
+            .thumbnail_file()
+            .ok_or("could not find a WebP thumbnail in packaged files")?
+            .name
+            .clone();
+        let original = self.thumbnail()?;
+        let replacement = Thumbnail::from_image(image, original.width(), original.height())?;
+        let mut writer = self.package_writer()?;
+        writer.replace_file(&name, &replacement.to_webp()?)?;
+        writer.write_to_file(output_path)
+    }
+}
+
+pub struct SaveListing {
+    pub path: PathBuf,
+    pub info: Result<SaveInfo, String>,
+}
+
+/// Reads the `SaveInfo.json` of every `.lsv` file in `dir` and its subdirectories, most
+/// recent save first. Saves that could not be read come last, with the reason.
+pub fn list_saves(dir: &Path) -> Result<Vec<SaveListing>, String> {
+    let mut save_paths = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| format!("could not list {}: {e}", dir.to_string_lossy()))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("could not list {}: {e}", dir.to_string_lossy()))?
+                .path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("lsv"))
+            {
+                save_paths.push(path);
+            }
+        }
+    }
+
+    let mut saves: Vec<_> = save_paths
+        .into_iter()
+        .map(|path| {
+            let info = Save::open(&path).and_then(|mut save| save.info());
+            SaveListing { path, info }
+        })
+        .collect();
+    saves.sort_by_key(|listing| {
+        std::cmp::Reverse(
+            listing
+                .info
+                .as_ref()
+                .ok()
+                .map(|info| info.timestamp.unwrap_or_default()),
+        )
+    });
+
+    Ok(saves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn write_save(path: &Path, save_info: &str) -> Result<(), String> {
+        let mut writer = PackageWriter::new();
+        writer.add_file(Path::new("SaveInfo.json"), save_info.as_bytes(), 0)?;
+        writer.write_to_file(path)
+    }
+
+    #[test]
+    fn saves_are_listed_most_recent_first() -> Result<(), String> {
+        let dir = TempDir::new("list_saves")?;
+        let nested = dir.path().join("Story").join("Tav-1");
+        std::fs::create_dir_all(&nested).map_err(|e| e.to_string())?;
+
+        write_save(
+            &dir.path().join("old.lsv"),
+            r#"{"Save Name": "Old", "Save Time": 100}"#,
+        )?;
+        write_save(
+            &nested.join("new.LSV"),
+            r#"{"SaveName": "New", "TimeStamp": "200"}"#,
+        )?;
+        write_save(&nested.join("undated.lsv"), r#"{"Save Name": "Undated"}"#)?;
+        write_save(&nested.join("broken.lsv"), "not json")?;
+        std::fs::write(dir.path().join("notes.txt"), "not a save").map_err(|e| e.to_string())?;
+
+        let saves = list_saves(dir.path())?;
+        let names: Vec<String> = saves
+            .iter()
+            .map(|listing| match &listing.info {
+                Ok(info) => info.save_name.clone(),
+                Err(_) => listing
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+            })
+            .collect();
+        assert_eq!(names, ["New", "Old", "Undated", "broken.lsv"]);
+        assert!(
+            saves[3]
+                .info
+                .as_ref()
+                .is_err_and(|e| e.contains("invalid SaveInfo.json"))
+        );
+        assert_eq!(saves[0].path, nested.join("new.LSV"));
+        Ok(())
+    }
+}
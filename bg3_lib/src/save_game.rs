@@ -0,0 +1,781 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::lsf_reader::{DataType, Node, NodeAttribute, NodeAttributeValue, Resource};
+
+/// Attributes holding the UUID of an entity, by order of preference.
+const UUID_ATTRIBUTES: [&str; 4] = ["GUID", "UUID", "MapKey", "Uuid"];
+/// Attributes naming the root template an entity was created from.
+const TEMPLATE_ATTRIBUTES: [&str; 5] = [
+    "CurrentTemplate",
+    "OriginalTemplate",
+    "Template",
+    "TemplateID",
+    "TemplateName",
+];
+/// Component holding the player data of characters controlled by a player.
+const PLAYER_DATA_NODE: &str = "PlayerData";
+/// Entity nodes of characters, and the node they are grouped under.
+const CHARACTER_NODE: &str = "Character";
+const CHARACTERS_NODE: &str = "Characters";
+/// Node whose descendants refer to the party members by UUID.
+const PARTY_NODE: &str = "Party";
+
+/// Where a field is stored: a path of component nodes below the entity node, each the first
+/// child with that name, and the attribute names the field is known to use there.
+type FieldLocation = (&'static [&'static str], &'static [&'static str]);
+
+const NAME_FIELD: [FieldLocation; 2] = [
+    (&["PlayerData", "PlayerCustomData"], &["Name"]),
+    (&[], &["CustomName"]),
+];
+const LEVEL_FIELD: [FieldLocation; 1] = [(&["Stats"], &["Level"])];
+const EXPERIENCE_FIELD: [FieldLocation; 1] = [(&["Stats"], &["Experience"])];
+const RACE_FIELD: [FieldLocation; 1] = [(&["PlayerData", "PlayerCustomData"], &["Race"])];
+const CLASS_FIELD: [FieldLocation; 1] = [(&["PlayerData", "PlayerCustomData"], &["ClassType"])];
+const POSITION_FIELD: [FieldLocation; 1] = [(&[], &["Translate"])];
+
+/// Value of a character, with the node and attribute it was read from and is written to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterField<T> {
+    pub value: T,
+    pub node: usize,
+    pub attribute: Arc<str>,
+}
+
+/// Character entity found in `Globals.lsf`.
+///
+/// Fields are only looked up in the entity node and its known components, see `SaveGame`;
+/// a field stored anywhere else is reported missing rather than guessed, so that editing it
+/// cannot write to an unrelated node. Race and class are kept as text, be they names or
+/// UUIDs, and translated names as `handle;version`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Character {
+    /// Index of the entity node.
+    pub node: usize,
+    pub uuid: CharacterField<Uuid>,
+    pub name: Option<CharacterField<String>>,
+    pub level: Option<CharacterField<u32>>,
+    pub experience: Option<CharacterField<u64>>,
+    pub race: Option<CharacterField<String>>,
+    pub class: Option<CharacterField<String>>,
+    pub position: Option<CharacterField<[f32; 3]>>,
+    /// Whether the character is controlled by a player.
+    pub is_player: bool,
+    /// Whether the character is referred to by the party data.
+    pub in_party: bool,
+}
+
+/// Characters of a save, read from its `Globals.lsf` resource and edited in place.
+///
+/// Characters are the `Character` nodes below a `Characters` node that carry both a UUID and
+/// a template. Party members are the characters whose UUID is held by a UUID attribute of a
+/// node below a `Party` node. Fields are read from this layout:
+///
+/// ```text
+/// Characters
+///     Character                 GUID, CurrentTemplate, Translate, CustomName, IsPlayer
+///         Stats                 Level, Experience
+///         PlayerData
+///             PlayerCustomData  Name, Race, ClassType
+/// Party
+///     ...                       GUID
+/// ```
+///
+/// This layout has only been checked against resources built to follow it, not against the
+/// `Globals.lsf` of a real BG3 save, whose entities keep most of their data in `NewAge` blobs.
+/// The module is behind the `save-game` feature until it is.
+pub struct SaveGame {
+    globals: Resource,
+    characters: Vec<Character>,
+}
+
+impl SaveGame {
+    pub fn from_globals(globals: Resource) -> Result<Self, String> {
+        let party_uuids = party_uuids(&globals);
+        let mut characters = vec![];
+        for node_idx in 0..globals.regions.node_instances.len() {
+            if let Some(character) = read_character(&globals, node_idx, &party_uuids) {
+                characters.push(character);
+            }
+        }
+        if characters.is_empty() {
+            return Err("could not find any character in Globals.lsf".to_string());
+        }
+
+        Ok(Self {
+            globals,
+            characters,
+        })
+    }
+
+    pub fn characters(&self) -> &[Character] {
+        &self.characters
+    }
+
+    /// Characters controlled by a player.
+    pub fn players(&self) -> impl Iterator<Item = &Character> {
+        self.characters
+            .iter()
+            .filter(|character| character.is_player)
+    }
+
+    pub fn party(&self) -> impl Iterator<Item = &Character> {
+        self.characters
+            .iter()
+            .filter(|character| character.in_party)
+    }
+
+    /// Position of a character in `characters`, by UUID.
+    pub fn find_character(&self, uuid: Uuid) -> Option<usize> {
+        self.characters
+            .iter()
+            .position(|character| character.uuid.value == uuid)
+    }
+
+    /// The edited resource, to be written back as the `Globals.lsf` of the save.
+    pub fn globals(&self) -> &Resource {
+        &self.globals
+    }
+
+    pub fn into_globals(self) -> Resource {
+        self.globals
+    }
+
+    /// Changes the UUID of the entity only; references to it elsewhere are left as they are.
+    pub fn set_uuid(&mut self, character: usize, uuid: Uuid) -> Result<(), String> {
+        let field = &self.character(character)?.uuid;
+        let (node, attribute) = (field.node, field.attribute.clone());
+        set_from_str(&mut self.globals, node, &attribute, &uuid.to_string())?;
+        self.characters[character].uuid.value = uuid;
+        Ok(())
+    }
+
+    pub fn set_name(&mut self, character: usize, name: &str) -> Result<(), String> {
+        let (node, attribute) = self.location(character, |c| c.name.as_ref(), "name")?;
+        set_from_str(&mut self.globals, node, &attribute, name)?;
+        if let Some(field) = &mut self.characters[character].name {
+            field.value = name.to_string();
+        }
+        Ok(())
+    }
+
+    pub fn set_level(&mut self, character: usize, level: u32) -> Result<(), String> {
+        let (node, attribute) = self.location(character, |c| c.level.as_ref(), "level")?;
+        set_integer(&mut self.globals, node, &attribute, level as u64)?;
+        if let Some(field) = &mut self.characters[character].level {
+            field.value = level;
+        }
+        Ok(())
+    }
+
+    pub fn set_experience(&mut self, character: usize, experience: u64) -> Result<(), String> {
+        let (node, attribute) =
+            self.location(character, |c| c.experience.as_ref(), "experience")?;
+        set_integer(&mut self.globals, node, &attribute, experience)?;
+        if let Some(field) = &mut self.characters[character].experience {
+            field.value = experience;
+        }
+        Ok(())
+    }
+
+    /// Race as a name or UUID, in the form the attribute already uses.
+    pub fn set_race(&mut self, character: usize, race: &str) -> Result<(), String> {
+        let (node, attribute) = self.location(character, |c| c.race.as_ref(), "race")?;
+        set_from_str(&mut self.globals, node, &attribute, race)?;
+        if let Some(field) = &mut self.characters[character].race {
+            field.value = race.to_string();
+        }
+        Ok(())
+    }
+
+    /// Class as a name or UUID, in the form the attribute already uses.
+    pub fn set_class(&mut self, character: usize, class: &str) -> Result<(), String> {
+        let (node, attribute) = self.location(character, |c| c.class.as_ref(), "class")?;
+        set_from_str(&mut self.globals, node, &attribute, class)?;
+        if let Some(field) = &mut self.characters[character].class {
+            field.value = class.to_string();
+        }
+        Ok(())
+    }
+
+    pub fn set_position(&mut self, character: usize, position: [f32; 3]) -> Result<(), String> {
+        let (node, attribute) = self.location(character, |c| c.position.as_ref(), "position")?;
+        let attr = attribute_mut(&mut self.globals, node, &attribute)?;
+        match &mut attr.value {
+            NodeAttributeValue::Vec3(value) => *value = position,
+            NodeAttributeValue::Vec4(value) => value[..3].copy_from_slice(&position),
+            _ => return Err(format!("attribute {attribute} is not a vector")),
+        }
+        if let Some(field) = &mut self.characters[character].position {
+            field.value = position;
+        }
+        Ok(())
+    }
+
+    fn character(&self, character: usize) -> Result<&Character, String> {
+        self.characters
+            .get(character)
+            .ok_or_else(|| format!("could not find character at index {character}"))
+    }
+
+    /// Node and attribute of a field, or an error naming the field if the character has none.
+    fn location<T>(
+        &self,
+        character: usize,
+        field: impl Fn(&Character) -> Option<&CharacterField<T>>,
+        field_name: &str,
+    ) -> Result<(usize, Arc<str>), String> {
+        let character = self.character(character)?;
+        field(character)
+            .map(|field| (field.node, field.attribute.clone()))
+            .ok_or_else(|| format!("character {} has no {field_name}", character.uuid.value))
+    }
+}
+
+fn read_character(
+    globals: &Resource,
+    node_idx: usize,
+    party_uuids: &BTreeSet<Uuid>,
+) -> Option<Character> {
+    let node = globals.regions.get_node(node_idx)?;
+    let uuid = entity_uuid(node_idx, node)?;
+    if !is_character(globals, node_idx) {
+        return None;
+    }
+
+    let find = |locations: &[FieldLocation]| {
+        locations.iter().find_map(|(components, names)| {
+            let component_idx = component(globals, node_idx, components)?;
+            let component = globals.regions.get_node(component_idx)?;
+            names.iter().find_map(|name| {
+                component
+                    .attributes
+                    .get_key_value(*name)
+                    .map(|(attribute, value)| (component_idx, attribute.clone(), value))
+            })
+        })
+    };
+    let field = |locations: &[FieldLocation]| {
+        find(locations).map(|(node, attribute, value)| CharacterField {
+            value: value.to_string(),
+            node,
+            attribute,
+        })
+    };
+    let number = |locations: &[FieldLocation]| {
+        let (node, attribute, value) = find(locations)?;
+        Some(CharacterField {
+            value: value.value.as_u64()?,
+            node,
+            attribute,
+        })
+    };
+
+    let is_player = component(globals, node_idx, &[PLAYER_DATA_NODE]).is_some()
+        || node
+            .attributes
+            .get("IsPlayer")
+            .and_then(|attr| attr.value.as_bool())
+            .unwrap_or(false);
+
+    Some(Character {
+        node: node_idx,
+        in_party: party_uuids.contains(&uuid.value),
+        uuid,
+        name: field(&NAME_FIELD),
+        level: number(&LEVEL_FIELD).and_then(|field| {
+            Some(CharacterField {
+                value: u32::try_from(field.value).ok()?,
+                node: field.node,
+                attribute: field.attribute,
+            })
+        }),
+        experience: number(&EXPERIENCE_FIELD),
+        race: field(&RACE_FIELD),
+        class: field(&CLASS_FIELD),
+        position: find(&POSITION_FIELD).and_then(|(node, attribute, value)| {
+            let value = match &value.value {
+                NodeAttributeValue::Vec3(v) => *v,
+                NodeAttributeValue::Vec4(v) => [v[0], v[1], v[2]],
+                _ => return None,
+            };
+            Some(CharacterField {
+                value,
+                node,
+                attribute,
+            })
+        }),
+        is_player,
+    })
+}
+
+/// UUID of a node with both a UUID and a template attribute.
+fn entity_uuid(node_idx: usize, node: &Node) -> Option<CharacterField<Uuid>> {
+    if !TEMPLATE_ATTRIBUTES
+        .iter()
+        .any(|name| node.attributes.contains_key(*name))
+    {
+        return None;
+    }
+    UUID_ATTRIBUTES.iter().find_map(|name| {
+        let (attribute, value) = node.attributes.get_key_value(*name)?;
+        Some(CharacterField {
+            value: attribute_uuid(value)?,
+            node: node_idx,
+            attribute: attribute.clone(),
+        })
+    })
+}
+
+fn attribute_uuid(attribute: &NodeAttribute) -> Option<Uuid> {
+    match &attribute.value {
+        NodeAttributeValue::Uuid(uuid) => Some(*uuid),
+        NodeAttributeValue::String(s) => Uuid::parse_str(s).ok(),
+        _ => None,
+    }
+}
+
+/// Whether the node is a `Character` directly below `Characters`, so that nodes merely named
+/// like characters, or items in the inventory of a character, are not taken for characters.
+fn is_character(globals: &Resource, node_idx: usize) -> bool {
+    globals.regions.get_node(node_idx).is_some_and(|node| {
+        *node.name == *CHARACTER_NODE
+            && node
+                .parent
+                .and_then(|parent| globals.regions.get_node(parent))
+                .is_some_and(|parent| *parent.name == *CHARACTERS_NODE)
+    })
+}
+
+/// Whether a strict ancestor of the node is named `name`.
+fn has_named_ancestor(globals: &Resource, node_idx: usize, name: &str) -> bool {
+    let mut current = globals
+        .regions
+        .get_node(node_idx)
+        .and_then(|node| node.parent);
+    let mut depth = 0;
+    while let Some(idx) = current {
+        let Some(node) = globals.regions.get_node(idx) else {
+            return false;
+        };
+        if *node.name == *name {
+            return true;
+        }
+        // Guard against parent links looping back on themselves
+        depth += 1;
+        if depth > globals.regions.node_instances.len() {
+            return false;
+        }
+        current = node.parent;
+    }
+    false
+}
+
+/// Component node at a path of child names below the entity node.
+fn component(globals: &Resource, node_idx: usize, path: &[&str]) -> Option<usize> {
+    path.iter().try_fold(node_idx, |idx, name| {
+        globals
+            .regions
+            .get_node(idx)?
+            .children
+            .get(*name)?
+            .first()
+            .copied()
+    })
+}
+
+/// UUIDs held by the UUID attributes of the nodes below `Party`.
+fn party_uuids(globals: &Resource) -> BTreeSet<Uuid> {
+    globals
+        .regions
+        .node_instances
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| has_named_ancestor(globals, *idx, PARTY_NODE))
+        .flat_map(|(_, node)| {
+            UUID_ATTRIBUTES
+                .iter()
+                .filter_map(|name| node.attributes.get(*name).and_then(attribute_uuid))
+        })
+        .collect()
+}
+
+fn attribute_mut<'a>(
+    globals: &'a mut Resource,
+    node_idx: usize,
+    attribute: &str,
+) -> Result<&'a mut NodeAttribute, String> {
+    globals
+        .regions
+        .node_instances
+        .get_mut(node_idx)
+        .ok_or_else(|| format!("could not find node at index {node_idx}"))?
+        .attributes
+        .get_mut(attribute)
+        .ok_or_else(|| format!("could not find attribute {attribute} of node {node_idx}"))
+}
+
+/// Parses `value` as the type the attribute already has.
+fn set_from_str(
+    globals: &mut Resource,
+    node_idx: usize,
+    attribute: &str,
+    value: &str,
+) -> Result<(), String> {
+    let attr = attribute_mut(globals, node_idx, attribute)?;
+    let ty = attr.ty;
+    attr.set_from_str(ty, value)
+}
+
+/// Stores an integer in the type the attribute already has, if it fits.
+fn set_integer(
+    globals: &mut Resource,
+    node_idx: usize,
+    attribute: &str,
+    value: u64,
+) -> Result<(), String> {
+    let attr = attribute_mut(globals, node_idx, attribute)?;
+    match attr.ty {
+        DataType::Byte
+        | DataType::Short
+        | DataType::UShort
+        | DataType::Int
+        | DataType::UInt
+        | DataType::ULongLong
+        | DataType::Long
+        | DataType::Int8
+        | DataType::Int64 => {
+            let ty = attr.ty;
+            attr.set_from_str(ty, &value.to_string())
+                .map_err(|_| format!("{value} does not fit in attribute {attribute} ({ty})"))
+        }
+        ty => Err(format!("attribute {attribute} is not an integer ({ty})")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::add_node;
+
+    fn attr(ty: DataType, value: NodeAttributeValue) -> NodeAttribute {
+        NodeAttribute { ty, value }
+    }
+
+    fn uuid(n: u128) -> NodeAttribute {
+        attr(DataType::Uuid, NodeAttributeValue::Uuid(Uuid::from_u128(n)))
+    }
+
+    fn text(ty: DataType, value: &str) -> NodeAttribute {
+        attr(ty, NodeAttributeValue::String(value.into()))
+    }
+
+    /// Globals with three characters (a player with every field, a party member named by
+    /// `CustomName` and a bare player), and nodes that only look like characters or party
+    /// members.
+    fn sample_globals() -> Resource {
+        let mut globals = Resource::new();
+        let root = add_node(&mut globals, "Globals", None, vec![]);
+        let characters = add_node(&mut globals, "Characters", Some(root), vec![]);
+
+        let tav = add_node(
+            &mut globals,
+            "Character",
+            Some(characters),
+            vec![
+                ("GUID", uuid(1)),
+                ("CurrentTemplate", text(DataType::FixedString, "tav")),
+                (
+                    "Translate",
+                    attr(DataType::Vec3, NodeAttributeValue::Vec3([1.0, 2.0, 3.0])),
+                ),
+            ],
+        );
+        add_node(
+            &mut globals,
+            "Stats",
+            Some(tav),
+            vec![
+                ("Level", attr(DataType::Byte, NodeAttributeValue::Byte(3))),
+                (
+                    "Experience",
+                    attr(DataType::Int, NodeAttributeValue::Int(900)),
+                ),
+            ],
+        );
+        let player_data = add_node(&mut globals, "PlayerData", Some(tav), vec![]);
+        add_node(
+            &mut globals,
+            "PlayerCustomData",
+            Some(player_data),
+            vec![
+                ("Name", text(DataType::LSString, "Tav")),
+                ("Race", text(DataType::FixedString, "Human")),
+                ("ClassType", text(DataType::FixedString, "Wizard")),
+            ],
+        );
+        // Items carry a UUID and a template too, but are not below Characters
+        let inventory = add_node(&mut globals, "Inventory", Some(tav), vec![]);
+        add_node(
+            &mut globals,
+            "Character",
+            Some(inventory),
+            vec![
+                ("GUID", uuid(5)),
+                ("Template", text(DataType::FixedString, "item")),
+            ],
+        );
+
+        let laezel = add_node(
+            &mut globals,
+            "Character",
+            Some(characters),
+            vec![
+                (
+                    "GUID",
+                    text(DataType::FixedString, &Uuid::from_u128(2).to_string()),
+                ),
+                ("Template", text(DataType::FixedString, "laezel")),
+                ("CustomName", text(DataType::LSString, "Lae'zel")),
+                (
+                    "Translate",
+                    attr(
+                        DataType::Vec4,
+                        NodeAttributeValue::Vec4([4.0, 5.0, 6.0, 1.0]),
+                    ),
+                ),
+                (
+                    "IsPlayer",
+                    attr(DataType::Bool, NodeAttributeValue::Bool(false)),
+                ),
+            ],
+        );
+        add_node(
+            &mut globals,
+            "Stats",
+            Some(laezel),
+            vec![("Level", attr(DataType::Int, NodeAttributeValue::Int(5)))],
+        );
+        add_node(
+            &mut globals,
+            "Character",
+            Some(characters),
+            vec![
+                ("GUID", uuid(3)),
+                ("Template", text(DataType::FixedString, "bare")),
+                (
+                    "IsPlayer",
+                    attr(DataType::Bool, NodeAttributeValue::Bool(true)),
+                ),
+            ],
+        );
+        add_node(
+            &mut globals,
+            "CharacterCreation",
+            Some(characters),
+            vec![
+                ("GUID", uuid(4)),
+                ("Template", text(DataType::FixedString, "cc")),
+            ],
+        );
+
+        let party = add_node(&mut globals, "Party", Some(root), vec![]);
+        add_node(&mut globals, "Member", Some(party), vec![("GUID", uuid(1))]);
+        add_node(
+            &mut globals,
+            "Member",
+            Some(party),
+            vec![
+                (
+                    "UUID",
+                    text(DataType::FixedString, &Uuid::from_u128(2).to_string()),
+                ),
+                ("Owner", uuid(3)),
+            ],
+        );
+        add_node(
+            &mut globals,
+            "PartyPresets",
+            Some(root),
+            vec![("GUID", uuid(3))],
+        );
+        globals
+    }
+
+    #[test]
+    fn characters_players_and_party_are_found() -> Result<(), String> {
+        let save_game = SaveGame::from_globals(sample_globals())?;
+        let uuids: Vec<Uuid> = save_game
+            .characters()
+            .iter()
+            .map(|character| character.uuid.value)
+            .collect();
+        assert_eq!(uuids, [1, 2, 3].map(Uuid::from_u128));
+        let players: Vec<Uuid> = save_game.players().map(|c| c.uuid.value).collect();
+        assert_eq!(players, [1, 3].map(Uuid::from_u128));
+        let party: Vec<Uuid> = save_game.party().map(|c| c.uuid.value).collect();
+        assert_eq!(party, [1, 2].map(Uuid::from_u128));
+        assert_eq!(save_game.find_character(Uuid::from_u128(2)), Some(1));
+        assert_eq!(save_game.find_character(Uuid::from_u128(4)), None);
+
+        let [tav, laezel, bare] = save_game.characters() else {
+            return Err("expected three characters".into());
+        };
+        let value =
+            |field: &Option<CharacterField<String>>| field.as_ref().map(|f| f.value.clone());
+        assert_eq!(value(&tav.name).as_deref(), Some("Tav"));
+        assert_eq!(value(&tav.race).as_deref(), Some("Human"));
+        assert_eq!(value(&tav.class).as_deref(), Some("Wizard"));
+        assert_eq!(tav.level.as_ref().map(|f| f.value), Some(3));
+        assert_eq!(tav.experience.as_ref().map(|f| f.value), Some(900));
+        assert_eq!(
+            tav.position.as_ref().map(|f| f.value),
+            Some([1.0, 2.0, 3.0])
+        );
+        assert_eq!(value(&laezel.name).as_deref(), Some("Lae'zel"));
+        assert_eq!(laezel.level.as_ref().map(|f| f.value), Some(5));
+        assert_eq!(
+            laezel.position.as_ref().map(|f| f.value),
+            Some([4.0, 5.0, 6.0])
+        );
+        assert!(!laezel.is_player);
+        assert_eq!(
+            (&bare.name, &bare.level, &bare.experience, &bare.position),
+            (&None, &None, &None, &None)
+        );
+
+        assert!(SaveGame::from_globals(Resource::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn setters_write_to_the_globals() -> Result<(), String> {
+        let mut save_game = SaveGame::from_globals(sample_globals())?;
+        save_game.set_name(0, "Astarion")?;
+        save_game.set_experience(0, 1200)?;
+        save_game.set_race(0, "Elf")?;
+        save_game.set_class(0, "Rogue")?;
+        save_game.set_position(0, [7.0, 8.0, 9.0])?;
+        save_game.set_position(1, [-1.0, -2.0, -3.0])?;
+        save_game.set_uuid(1, Uuid::from_u128(20))?;
+
+        // A level that does not fit the attribute leaves it unchanged
+        assert!(
+            save_game
+                .set_level(0, 300)
+                .is_err_and(|e| e.contains("does not fit"))
+        );
+        assert!(
+            save_game
+                .set_name(2, "Nobody")
+                .is_err_and(|e| e.contains("has no name"))
+        );
+        assert!(save_game.set_race(3, "Elf").is_err());
+
+        let tav = &save_game.characters()[0];
+        assert_eq!(
+            tav.name.as_ref().map(|f| f.value.as_str()),
+            Some("Astarion")
+        );
+        assert_eq!(tav.level.as_ref().map(|f| f.value), Some(3));
+        assert_eq!(save_game.find_character(Uuid::from_u128(20)), Some(1));
+
+        // Read back from the edited resource, the characters are the same, except that the
+        // party still refers to the old UUID
+        let edited = SaveGame::from_globals(save_game.globals().clone())?;
+        let mut expected = save_game.characters().to_vec();
+        expected[1].in_party = false;
+        assert_eq!(edited.characters(), expected);
+        let globals = save_game.into_globals();
+        let translate = |node: usize| {
+            globals.regions.node_instances[node].attributes["Translate"]
+                .value
+                .clone()
+        };
+        assert_eq!(
+            translate(edited.characters()[0].node),
+            NodeAttributeValue::Vec3([7.0, 8.0, 9.0])
+        );
+        assert_eq!(
+            translate(edited.characters()[1].node),
+            NodeAttributeValue::Vec4([-1.0, -2.0, -3.0, 1.0])
+        );
+        let experience = edited.characters()[0]
+            .experience
+            .as_ref()
+            .ok_or("no experience")?;
+        assert_eq!(
+            globals.regions.node_instances[experience.node].attributes["Experience"].value,
+            NodeAttributeValue::Int(1200)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fields_outside_known_components_are_not_guessed() -> Result<(), String> {
+        let mut globals = Resource::new();
+        let root = add_node(&mut globals, "Globals", None, vec![]);
+        let characters = add_node(&mut globals, "Characters", Some(root), vec![]);
+        let character = add_node(
+            &mut globals,
+            "Character",
+            Some(characters),
+            vec![
+                (
+                    "GUID",
+                    NodeAttribute {
+                        ty: DataType::Uuid,
+                        value: NodeAttributeValue::Uuid(Uuid::from_u128(1)),
+                    },
+                ),
+                (
+                    "CurrentTemplate",
+                    NodeAttribute {
+                        ty: DataType::FixedString,
+                        value: NodeAttributeValue::String("template".into()),
+                    },
+                ),
+            ],
+        );
+        let spells = add_node(&mut globals, "Spells", Some(character), vec![]);
+        add_node(
+            &mut globals,
+            "Spell",
+            Some(spells),
+            vec![(
+                "Level",
+                NodeAttribute {
+                    ty: DataType::Int,
+                    value: NodeAttributeValue::Int(3),
+                },
+            )],
+        );
+
+        let mut save_game = SaveGame::from_globals(globals.clone())?;
+        assert_eq!(save_game.characters().len(), 1);
+        assert_eq!(save_game.characters()[0].level, None);
+        assert!(save_game.set_level(0, 10).is_err());
+        assert!(save_game.globals() == &globals);
+
+        let stats = add_node(
+            &mut globals,
+            "Stats",
+            Some(character),
+            vec![(
+                "Level",
+                NodeAttribute {
+                    ty: DataType::Int,
+                    value: NodeAttributeValue::Int(4),
+                },
+            )],
+        );
+        let mut save_game = SaveGame::from_globals(globals)?;
+        save_game.set_level(0, 10)?;
+        let level = &save_game.globals().regions.node_instances[stats].attributes["Level"];
+        assert_eq!(level.value, NodeAttributeValue::Int(10));
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use std::time::{Duration, SystemTime};
+
+use serde_json::{Map, Value};
+
+/// Contents of the `SaveInfo.json` file of a save.
+///
+/// The keys differ slightly between game versions, so every field is looked up under the
+/// names it is known to use, and missing fields are left empty rather than failing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveInfo {
+    pub save_name: String,
+    pub leader_name: Option<String>,
+    pub party: Vec<SaveInfoCharacter>,
+    pub game_version: Option<String>,
+    pub difficulty: Option<String>,
+    pub current_level: Option<String>,
+    /// Seconds since the Unix epoch at which the save was made.
+    pub timestamp: Option<u64>,
+    pub play_time: Option<Duration>,
+    pub mods: Vec<SaveInfoMod>,
+    /// The whole JSON document, for the fields not modeled here.
+    pub raw: Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveInfoCharacter {
+    /// Origin name for origin characters, or the custom name.
+    pub name: Option<String>,
+    pub race: Option<String>,
+    pub level: Option<u32>,
+    pub classes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveInfoMod {
+    pub name: String,
+    pub uuid: Option<String>,
+}
+
+impl SaveInfo {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: Value =
+            serde_json::from_str(json).map_err(|e| format!("invalid SaveInfo.json: {e}"))?;
+        let fields = Some(
+            raw.as_object()
+                .ok_or("SaveInfo.json does not contain a JSON object")?,
+        );
+
+        let party = field(fields, &["Active Party", "ActiveParty", "Party"])
+            .map(|party| field(party.as_object(), &["Characters"]).unwrap_or(party))
+            .and_then(Value::as_array)
+            .map(|characters| characters.iter().map(read_character).collect())
+            .unwrap_or_default();
+
+        let mods = field(fields, &["Mods", "Active Mods", "ActiveMods"])
+            .and_then(Value::as_array)
+            .map(|mods| mods.iter().filter_map(read_mod).collect())
+            .unwrap_or_default();
+
+        Ok(Self {
+            save_name: string_field(fields, &["Save Name", "SaveName", "Name"]).unwrap_or_default(),
+            leader_name: string_field(fields, &["Leader Name", "LeaderName"]),
+            party,
+            game_version: string_field(fields, &["Version", "Game Version", "GameVersion"]),
+            difficulty: string_field(fields, &["Difficulty", "Game Difficulty"]),
+            current_level: string_field(fields, &["Current Level", "CurrentLevel"]),
+            timestamp: number_field(fields, &["Save Time", "SaveTime", "TimeStamp", "Timestamp"]),
+            play_time: number_field(fields, &["Play Time", "PlayTime", "Game Time", "GameTime"])
+                .map(Duration::from_secs),
+            mods,
+            raw,
+        })
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        // Some files start with a UTF-8 byte order mark
+        let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        let json = std::str::from_utf8(bytes)
+            .map_err(|e| format!("SaveInfo.json is not valid UTF-8: {e}"))?;
+        Self::from_json(json)
+    }
+
+    pub fn save_time(&self) -> Option<SystemTime> {
+        self.timestamp
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Names of the party members, leader first when known.
+    pub fn party_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.leader_name.as_deref().into_iter().collect();
+        for name in self.party.iter().filter_map(|c| c.name.as_deref()) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+}
+
+fn read_character(value: &Value) -> SaveInfoCharacter {
+    let fields = value.as_object();
+    let classes = match field(fields, &["Classes", "Class"]) {
+        Some(Value::Array(classes)) => classes
+            .iter()
+            .filter_map(|class| match class {
+                Value::Object(class) => string_field(Some(class), &["Main", "Name", "Class"]),
+                other => value_to_string(other),
+            })
+            .collect(),
+        Some(other) => value_to_string(other).into_iter().collect(),
+        None => vec![],
+    };
+
+    SaveInfoCharacter {
+        name: string_field(fields, &["Origin", "Name", "Character Name"]),
+        race: string_field(fields, &["Race"]),
+        level: number_field(fields, &["Level"]).and_then(|level| level.try_into().ok()),
+        classes,
+    }
+}
+
+fn read_mod(value: &Value) -> Option<SaveInfoMod> {
+    match value {
+        Value::Object(fields) => Some(SaveInfoMod {
+            name: string_field(Some(fields), &["Name", "ModName"])?,
+            uuid: string_field(Some(fields), &["UUID", "Uuid", "GUID"]),
+        }),
+        other => value_to_string(other).map(|name| SaveInfoMod { name, uuid: None }),
+    }
+}
+
+fn field<'a>(fields: Option<&'a Map<String, Value>>, names: &[&str]) -> Option<&'a Value> {
+    let fields = fields?;
+    names.iter().find_map(|name| {
+        fields.get(*name).or_else(|| {
+            fields
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value)
+        })
+    })
+}
+
+fn string_field(fields: Option<&Map<String, Value>>, names: &[&str]) -> Option<String> {
+    field(fields, names).and_then(value_to_string)
+}
+
+/// Integer field, also accepted as a numeric string or a float.
+fn number_field(fields: Option<&Map<String, Value>>, names: &[&str]) -> Option<u64> {
+    match field(fields, names)? {
+        Value::Number(n) => n.as_u64().or_else(|| n.as_f64().map(|f| f as u64)),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Displays strings and numbers, and joins arrays of them with `, `.
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Array(values) => {
+            let parts: Vec<String> = values.iter().filter_map(value_to_string).collect();
+            (!parts.is_empty()).then(|| parts.join(", "))
+        }
+        Value::Null | Value::Object(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaced_keys_are_read() -> Result<(), String> {
+        let info = SaveInfo::from_json(
+            r#"{
+                "Save Name": "Before the Gate",
+                "Leader Name": "Tav",
+                "Active Party": {
+                    "Characters": [
+                        { "Origin": "Tav", "Race": "Elf", "Level": 5, "Classes": [{ "Main": "Wizard" }] },
+                        { "Origin": "Shadowheart", "Level": "5", "Class": "Cleric" },
+                        { "Race": "Human" }
+                    ]
+                },
+                "Version": "4.1.1.6000000",
+                "Difficulty": ["Balanced", "Honour"],
+                "Current Level": "WLD_Main_A",
+                "Save Time": "1700000000",
+                "Play Time": 3600.5,
+                "Mods": [{ "Name": "GustavDev", "UUID": "28ac9ce2-2aba-8cda-b3b5-6e922f71b6b8" }, "Other"]
+            }"#,
+        )?;
+
+        assert_eq!(info.save_name, "Before the Gate");
+        assert_eq!(info.game_version.as_deref(), Some("4.1.1.6000000"));
+        assert_eq!(info.difficulty.as_deref(), Some("Balanced, Honour"));
+        assert_eq!(info.current_level.as_deref(), Some("WLD_Main_A"));
+        assert_eq!(info.timestamp, Some(1_700_000_000));
+        assert_eq!(
+            info.save_time(),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+        assert_eq!(info.play_time, Some(Duration::from_secs(3600)));
+
+        assert_eq!(
+            info.party,
+            [
+                SaveInfoCharacter {
+                    name: Some("Tav".to_string()),
+                    race: Some("Elf".to_string()),
+                    level: Some(5),
+                    classes: vec!["Wizard".to_string()],
+                },
+                SaveInfoCharacter {
+                    name: Some("Shadowheart".to_string()),
+                    race: None,
+                    level: Some(5),
+                    classes: vec!["Cleric".to_string()],
+                },
+                SaveInfoCharacter {
+                    race: Some("Human".to_string()),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(info.party_names(), ["Tav", "Shadowheart"]);
+
+        let mods: Vec<_> = info
+            .mods
+            .iter()
+            .map(|m| (m.name.as_str(), m.uuid.as_deref()))
+            .collect();
+        assert_eq!(
+            mods,
+            [
+                ("GustavDev", Some("28ac9ce2-2aba-8cda-b3b5-6e922f71b6b8")),
+                ("Other", None),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn keys_are_matched_without_case_or_spaces() -> Result<(), String> {
+        let json = r#"{
+            "savename": "Quicksave",
+            "LeaderName": "Karlach",
+            "activeparty": [{ "name": "Astarion", "level": 3 }, { "Name": "Karlach" }],
+            "TimeStamp": 1700000000,
+            "gametime": 60,
+            "ActiveMods": [{ "ModName": "Shared", "Guid": "ed539163-bb70-431b-96a7-f5b2eda5376b" }],
+            "Unmodeled": [1, 2]
+        }"#;
+        let mut bytes = b"\xEF\xBB\xBF".to_vec();
+        bytes.extend(json.as_bytes());
+        let info = SaveInfo::from_bytes(&bytes)?;
+
+        assert_eq!(info.save_name, "Quicksave");
+        assert_eq!(info.party_names(), ["Karlach", "Astarion"]);
+        assert_eq!(info.party[0].level, Some(3));
+        assert_eq!(info.timestamp, Some(1_700_000_000));
+        assert_eq!(info.play_time, Some(Duration::from_secs(60)));
+        assert_eq!(info.mods.len(), 1);
+        assert_eq!(
+            info.mods[0].uuid.as_deref(),
+            Some("ed539163-bb70-431b-96a7-f5b2eda5376b")
+        );
+        assert_eq!(info.raw["Unmodeled"][1], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_fields_are_left_empty() -> Result<(), String> {
+        let info = SaveInfo::from_json("{}")?;
+        assert_eq!(
+            info,
+            SaveInfo {
+                raw: Value::Object(Map::new()),
+                ..Default::default()
+            }
+        );
+        assert!(SaveInfo::from_json("[]").is_err());
+        assert!(SaveInfo::from_bytes(b"\xFF{}").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use crate::lsf_reader::{Node, NodeAttribute, PackedVersion, Resource};
+
+/// Modules shipped with the game, which every save lists among its mods.
+const BUILTIN_MODULE_NAMES: [&str; 9] = [
+    "Gustav",
+    "GustavDev",
+    "GustavX",
+    "Shared",
+    "SharedDev",
+    "Honour",
+    "HonourX",
+    "MainUI",
+    "ModBrowser",
+];
+
+/// Module listed in the `ModuleSettings` of a save.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModuleDesc {
+    pub uuid: String,
+    pub name: String,
+    pub folder: String,
+    pub version: PackedVersion,
+    pub md5: Option<String>,
+    pub publish_handle: Option<u64>,
+}
+
+impl ModuleDesc {
+    /// Whether the module ships with the game rather than being a mod.
+    pub fn is_builtin(&self) -> bool {
+        BUILTIN_MODULE_NAMES.contains(&self.name.as_str())
+    }
+}
+
+/// Contents of the `meta.lsf` file of a save.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveMeta {
+    engine_version: PackedVersion,
+    mods: Vec<ModuleDesc>,
+}
+
+impl SaveMeta {
+    /// Reads the `ModuleShortDesc` nodes found below the `MetaData` region, and the engine
+    /// version of the resource.
+    pub fn from_resource(resource: &Resource) -> Result<Self, String> {
+        let region_idx = *resource
+            .regions
+            .regions_indices
+            .get("MetaData")
+            .ok_or("could not find the MetaData region in meta.lsf")?;
+
+        let mut mods = vec![];
+        let mut stack = vec![region_idx];
+        while let Some(node_idx) = stack.pop() {
+            let node = resource
+                .regions
+                .get_node(node_idx)
+                .ok_or_else(|| format!("could not find node at index {node_idx}"))?;
+
+            if &*node.name == "ModuleShortDesc" {
+                mods.push(read_module(node)?);
+                continue;
+            }
+            // Reversed, so that modules come out in file order
+            stack.extend(node.children.values().flatten().rev());
+        }
+
+        Ok(Self {
+            engine_version: resource.metadata.game_version,
+            mods,
+        })
+    }
+
+    pub fn engine_version(&self) -> PackedVersion {
+        self.engine_version
+    }
+
+    /// Every module of the save, in load order, including the ones of the game itself.
+    pub fn mods(&self) -> &[ModuleDesc] {
+        &self.mods
+    }
+
+    /// Modules needed to load the save besides the ones shipped with the game.
+    pub fn required_mods(&self) -> impl Iterator<Item = &ModuleDesc> {
+        self.mods.iter().filter(|module| !module.is_builtin())
+    }
+
+    pub fn find_mod(&self, uuid: &str) -> Option<&ModuleDesc> {
+        self.mods
+            .iter()
+            .find(|module| module.uuid.eq_ignore_ascii_case(uuid))
+    }
+}
+
+fn read_module(node: &Node) -> Result<ModuleDesc, String> {
+    let attribute = |name: &str| node.attributes.get(name).map(NodeAttribute::to_string);
+
+    let version = if let Some(version) = node.attributes.get("Version64") {
+        version.value.as_i64().map(PackedVersion::from)
+    } else {
+        node.attributes
+            .get("Version")
+            .and_then(|version| version.value.as_i32())
+            .map(PackedVersion::from)
+    };
+
+    Ok(ModuleDesc {
+        uuid: attribute("UUID").ok_or("ModuleShortDesc has no UUID attribute")?,
+        name: attribute("Name").unwrap_or_default(),
+        folder: attribute("Folder").unwrap_or_default(),
+        version: version.unwrap_or_default(),
+        md5: attribute("MD5").filter(|md5| !md5.is_empty()),
+        publish_handle: node
+            .attributes
+            .get("PublishHandle")
+            .and_then(|handle| handle.value.as_u64()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsf_reader::{DataType, NodeAttributeValue};
+    use crate::test_support::add_node;
+
+    fn string(value: &str) -> NodeAttribute {
+        NodeAttribute {
+            ty: DataType::LSString,
+            value: NodeAttributeValue::String(value.into()),
+        }
+    }
+
+    fn add_module(
+        resource: &mut Resource,
+        parent: usize,
+        uuid: &str,
+        name: &str,
+        mut attributes: Vec<(&str, NodeAttribute)>,
+    ) {
+        attributes.extend([
+            ("UUID", string(uuid)),
+            ("Name", string(name)),
+            ("Folder", string(name)),
+        ]);
+        add_node(resource, "ModuleShortDesc", Some(parent), attributes);
+    }
+
+    fn meta_resource() -> Resource {
+        let mut resource = Resource::new();
+        resource.metadata.game_version = PackedVersion::new(4, 1, 1, 6);
+        let region = add_node(&mut resource, "MetaData", None, vec![]);
+        let meta = add_node(&mut resource, "MetaData", Some(region), vec![]);
+        let settings = add_node(&mut resource, "ModuleSettings", Some(meta), vec![]);
+        let mods = add_node(&mut resource, "Mods", Some(settings), vec![]);
+
+        let version64 = |packed: i64| NodeAttribute {
+            ty: DataType::Int64,
+            value: NodeAttributeValue::Int64(packed),
+        };
+        let version = |packed: i32| NodeAttribute {
+            ty: DataType::Int,
+            value: NodeAttributeValue::Int(packed),
+        };
+
+        add_module(
+            &mut resource,
+            mods,
+            "28ac9ce2-2aba-8cda-b3b5-6e922f71b6b8",
+            "GustavDev",
+            vec![(
+                "Version64",
+                version64((4 << 55) | (1 << 47) | (1 << 31) | 6),
+            )],
+        );
+        add_module(
+            &mut resource,
+            mods,
+            "AAAAAAAA-0000-0000-0000-000000000001",
+            "OldStyle",
+            vec![
+                ("Version", version((1 << 28) | (2 << 24) | (3 << 16) | 4)),
+                ("MD5", string("")),
+                (
+                    "PublishHandle",
+                    NodeAttribute {
+                        ty: DataType::ULongLong,
+                        value: NodeAttributeValue::UInt64(12345),
+                    },
+                ),
+            ],
+        );
+        add_module(
+            &mut resource,
+            mods,
+            "aaaaaaaa-0000-0000-0000-000000000002",
+            "NewStyle",
+            vec![
+                ("Version64", version64((2 << 55) | 7)),
+                ("Version", version(1 << 28)),
+                ("MD5", string("d41d8cd98f00b204e9800998ecf8427e")),
+            ],
+        );
+        resource
+    }
+
+    #[test]
+    fn modules_are_read_in_order() -> Result<(), String> {
+        let meta = SaveMeta::from_resource(&meta_resource())?;
+        assert_eq!(meta.engine_version(), PackedVersion::new(4, 1, 1, 6));
+
+        let names: Vec<&str> = meta.mods().iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["GustavDev", "OldStyle", "NewStyle"]);
+        let required: Vec<&str> = meta.required_mods().map(|m| m.name.as_str()).collect();
+        assert_eq!(required, ["OldStyle", "NewStyle"]);
+
+        assert_eq!(
+            meta.mods()[1],
+            ModuleDesc {
+                uuid: "AAAAAAAA-0000-0000-0000-000000000001".to_string(),
+                name: "OldStyle".to_string(),
+                folder: "OldStyle".to_string(),
+                version: PackedVersion::new(1, 2, 3, 4),
+                md5: None,
+                publish_handle: Some(12345),
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn version64_is_preferred_over_version() -> Result<(), String> {
+        let meta = SaveMeta::from_resource(&meta_resource())?;
+        let versions: Vec<PackedVersion> = meta.mods().iter().map(|m| m.version).collect();
+        assert_eq!(
+            versions,
+            [
+                PackedVersion::new(4, 1, 1, 6),
+                PackedVersion::new(1, 2, 3, 4),
+                PackedVersion::new(2, 0, 0, 7),
+            ]
+        );
+
+        let new_style = meta
+            .find_mod("AAAAAAAA-0000-0000-0000-000000000002")
+            .ok_or("NewStyle not found")?;
+        assert_eq!(
+            new_style.md5.as_deref(),
+            Some("d41d8cd98f00b204e9800998ecf8427e")
+        );
+        assert!(
+            meta.find_mod("aaaaaaaa-0000-0000-0000-000000000003")
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_modules_are_filtered() {
+        for name in BUILTIN_MODULE_NAMES {
+            let module = ModuleDesc {
+                name: name.to_string(),
+                ..Default::default()
+            };
+            assert!(module.is_builtin());
+        }
+        let module = ModuleDesc {
+            name: "gustavdev".to_string(),
+            ..Default::default()
+        };
+        assert!(!module.is_builtin());
+    }
+
+    #[test]
+    fn malformed_meta_fails() {
+        let mut resource = Resource::new();
+        add_node(&mut resource, "Config", None, vec![]);
+        assert!(SaveMeta::from_resource(&resource).is_err());
+
+        let region = add_node(&mut resource, "MetaData", None, vec![]);
+        add_node(
+            &mut resource,
+            "ModuleShortDesc",
+            Some(region),
+            vec![("Name", string("NoUuid"))],
+        );
+        assert!(SaveMeta::from_resource(&resource).is_err());
+    }
+}
@@ -0,0 +1,476 @@
+//! Helpers shared by the unit tests to build resources and LSF files by hand.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::lsf_reader::{DataType, Node, NodeAttribute, NodeAttributeValue, NodeKind, Resource};
+use crate::node_path::NodePath;
+use crate::osiris::*;
+
+/// Adds a node with the given attributes to `resource`, as a region root when it has no
+/// parent, and returns its index.
+pub(crate) fn add_node(
+    resource: &mut Resource,
+    name: &str,
+    parent: Option<usize>,
+    attributes: Vec<(&str, NodeAttribute)>,
+) -> usize {
+    let idx = resource.regions.node_instances.len();
+    let name: Arc<str> = name.into();
+    let kind = match parent {
+        Some(parent) => {
+            resource.regions.node_instances[parent].append_child(&name, idx);
+            NodeKind::Node
+        }
+        None => {
+            resource.regions.regions_indices.insert(name.clone(), idx);
+            NodeKind::Region { name: name.clone() }
+        }
+    };
+    resource.regions.node_instances.push(Node {
+        kind,
+        name,
+        parent,
+        attributes: attributes
+            .into_iter()
+            .map(|(name, attribute)| (name.into(), attribute))
+            .collect(),
+        ..Default::default()
+    });
+    idx
+}
+
+/// Node at a path written as `NodePath` displays it.
+pub(crate) fn node_mut<'a>(resource: &'a mut Resource, path: &str) -> Result<&'a mut Node, String> {
+    let idx = path
+        .parse::<NodePath>()?
+        .resolve(resource)
+        .ok_or_else(|| format!("{path} not found"))?;
+    Ok(&mut resource.regions.node_instances[idx])
+}
+
+fn attribute(ty: DataType, value: NodeAttributeValue) -> NodeAttribute {
+    NodeAttribute { ty, value }
+}
+
+/// Resource with two regions, children sharing a name, nested nodes and a few attribute types.
+pub(crate) fn sample_resource() -> Resource {
+    let mut resource = Resource::new();
+    let config = add_node(
+        &mut resource,
+        "Config",
+        None,
+        vec![(
+            "Version",
+            attribute(DataType::Int, NodeAttributeValue::Int(3)),
+        )],
+    );
+    add_node(
+        &mut resource,
+        "Option",
+        Some(config),
+        vec![(
+            "Enabled",
+            attribute(DataType::Bool, NodeAttributeValue::Bool(true)),
+        )],
+    );
+
+    let templates = add_node(&mut resource, "Templates", None, vec![]);
+    for (idx, name) in ["Sword", "Shield", "Sword"].into_iter().enumerate() {
+        let item = add_node(
+            &mut resource,
+            "Item",
+            Some(templates),
+            vec![
+                (
+                    "MapKey",
+                    attribute(
+                        DataType::FixedString,
+                        NodeAttributeValue::String(name.into()),
+                    ),
+                ),
+                (
+                    "Scale",
+                    attribute(
+                        DataType::Vec3,
+                        NodeAttributeValue::Vec3([idx as f32, 1.0, 2.0]),
+                    ),
+                ),
+            ],
+        );
+        add_node(
+            &mut resource,
+            "Tag",
+            Some(item),
+            vec![(
+                "Uuid",
+                attribute(
+                    DataType::Uuid,
+                    NodeAttributeValue::Uuid(uuid::Uuid::from_u128(idx as u128)),
+                ),
+            )],
+        );
+    }
+    resource
+}
+
+/// Uncompressed LSF file (version 7) with a `Test` region holding one `Node`, whose
+/// attributes are given as name, on-disk type id and value bytes.
+pub(crate) fn lsf_with_attributes(attributes: &[(&str, u32, Vec<u8>)]) -> Vec<u8> {
+    // One bucket for node names, one for attribute names
+    let mut names = vec![];
+    names.extend(2u32.to_le_bytes());
+    let node_names = ["Test", "Node"];
+    let attribute_names: Vec<&str> = attributes.iter().map(|(name, ..)| *name).collect();
+    for bucket in [&node_names[..], &attribute_names[..]] {
+        names.extend((bucket.len() as u16).to_le_bytes());
+        for name in bucket {
+            names.extend((name.len() as u16).to_le_bytes());
+            names.extend(name.as_bytes());
+        }
+    }
+
+    let mut nodes = vec![];
+    for (name_offset, parent, first_attribute) in [(0u32, -1i32, -1i32), (1, 0, 0)] {
+        let first_attribute = if attributes.is_empty() {
+            -1
+        } else {
+            first_attribute
+        };
+        nodes.extend(name_offset.to_le_bytes());
+        nodes.extend(parent.to_le_bytes());
+        nodes.extend((-1i32).to_le_bytes());
+        nodes.extend(first_attribute.to_le_bytes());
+    }
+
+    let mut attribute_table = vec![];
+    let mut values = vec![];
+    for (idx, (_, type_id, value)) in attributes.iter().enumerate() {
+        let next = if idx + 1 < attributes.len() {
+            idx as i32 + 1
+        } else {
+            -1
+        };
+        attribute_table.extend(((1u32 << 16) | idx as u32).to_le_bytes());
+        attribute_table.extend((type_id | ((value.len() as u32) << 6)).to_le_bytes());
+        attribute_table.extend(next.to_le_bytes());
+        attribute_table.extend((values.len() as u32).to_le_bytes());
+        values.extend(value);
+    }
+
+    let mut file = b"LSOF".to_vec();
+    file.extend(7u32.to_le_bytes());
+    file.extend((4i64 << 55).to_le_bytes());
+    // Sections are stored uncompressed: uncompressed size, then 0 as size on disk
+    file.extend((names.len() as u32).to_le_bytes());
+    file.extend(0u32.to_le_bytes());
+    file.extend(0u64.to_le_bytes());
+    for section in [&nodes, &attribute_table, &values] {
+        file.extend((section.len() as u32).to_le_bytes());
+        file.extend(0u32.to_le_bytes());
+    }
+    // compression flags, two unknown fields, has_sibling_data
+    file.extend([0, 0, 0, 0]);
+    file.extend(1u32.to_le_bytes());
+
+    for section in [names, nodes, attribute_table, values] {
+        file.extend(section);
+    }
+    file
+}
+
+/// Directory under the system temporary directory, removed with its contents when dropped.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new(name: &str) -> Result<Self, String> {
+        let path = std::env::temp_dir().join(format!("bg3_lib_{name}_{}", std::process::id()));
+        std::fs::create_dir_all(&path)
+            .map_err(|e| format!("could not create {}: {e}", path.display()))?;
+        Ok(Self(path))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn osiris_string(type_id: u32, value: &str) -> OsirisValue {
+    OsirisValue {
+        type_id,
+        data: OsirisValueData::String(Some(value.to_string())),
+    }
+}
+
+fn osiris_typed(value: OsirisValue) -> OsirisTypedValue {
+    OsirisTypedValue {
+        value,
+        is_valid: true,
+        out_param: false,
+        is_a_type: false,
+    }
+}
+
+fn osiris_variable(index: i8, value: OsirisValue) -> OsirisVariable {
+    OsirisVariable {
+        value: osiris_typed(value),
+        index,
+        unused: false,
+        adapted: true,
+    }
+}
+
+fn osiris_database_node(id: u32, database: u32, name: &str, num_params: u8) -> OsirisNode {
+    OsirisNode {
+        id,
+        database,
+        name: name.to_string(),
+        num_params,
+        kind: OsirisNodeKind::Database {
+            referenced_by: vec![OsirisNodeEntry {
+                node: 5,
+                entry_point: 1,
+                goal: 1,
+            }],
+        },
+    }
+}
+
+/// Story of the latest version, with a node of every kind, enumerations, type aliases and
+/// a few databases: `DB_GlobalFlag` (with `Flag_A` set), `DB_Counter` with one and two
+/// columns, and `DB_Color` of an enumeration type.
+pub(crate) fn sample_story() -> Story {
+    // FLAG is a GUIDSTRING, COLOR an INTEGER enumeration
+    const FLAG: u32 = 6;
+    const COLOR: u32 = 7;
+    const STRING: u32 = OsirisBuiltinType::String as u32;
+    const INTEGER: u32 = OsirisBuiltinType::Integer as u32;
+
+    let mut version_buffer = vec![0; 0x80];
+    version_buffer[..4].copy_from_slice(b"1.13");
+    let relation = OsirisRelation {
+        next: OsirisNodeEntry {
+            node: 7,
+            entry_point: 0,
+            goal: 1,
+        },
+        parent: 1,
+        adapter: 1,
+        rel_database_node: 2,
+        rel_join: OsirisNodeEntry::default(),
+        rel_database_indirection: 1,
+    };
+    let call = OsirisCall {
+        name: "PROC_Count".to_string(),
+        parameters: Some(vec![
+            OsirisCallParameter::Variable(osiris_variable(
+                0,
+                OsirisValue {
+                    type_id: FLAG,
+                    data: OsirisValueData::String(None),
+                },
+            )),
+            OsirisCallParameter::Value(osiris_typed(OsirisValue {
+                type_id: INTEGER,
+                data: OsirisValueData::Integer(-3),
+            })),
+        ]),
+        negate: true,
+        goal_id_or_debug_hook: 1,
+    };
+
+    Story {
+        header: OsirisHeader {
+            unknown: 0,
+            version_string: "Osiris save file dd. 01/01/24 00:00:00. Version 1.13.".to_string(),
+            major_version: 1,
+            minor_version: 13,
+            big_endian: false,
+            unused: 0,
+            version_buffer,
+            debug_flags: 0x0800_0001,
+        },
+        types: vec![
+            OsirisType {
+                name: "FLAG".to_string(),
+                index: FLAG as u8,
+                alias: OsirisBuiltinType::GuidString as u8,
+            },
+            OsirisType {
+                name: "COLOR".to_string(),
+                index: COLOR as u8,
+                alias: OsirisBuiltinType::Integer as u8,
+            },
+        ],
+        enums: vec![OsirisEnum {
+            type_id: COLOR as u16,
+            elements: vec![("Red".to_string(), 1), ("Blue".to_string(), 2)],
+        }],
+        div_objects: vec![OsirisDivObject {
+            name: "Div".to_string(),
+            ty: 1,
+            keys: [1, 2, 3, 4],
+        }],
+        functions: vec![OsirisFunction {
+            line: 12,
+            condition_references: 1,
+            action_references: 2,
+            node: 8,
+            ty: OsirisFunctionType::Proc as u8,
+            meta: [0, 1, 2, 3],
+            name: "PROC_Count".to_string(),
+            out_param_mask: vec![0],
+            parameters: vec![FLAG as u16, INTEGER as u16],
+        }],
+        nodes: vec![
+            osiris_database_node(1, 1, "DB_GlobalFlag", 1),
+            osiris_database_node(2, 2, "DB_Counter", 2),
+            osiris_database_node(3, 3, "DB_Counter", 1),
+            osiris_database_node(4, 4, "DB_Color", 1),
+            OsirisNode {
+                id: 5,
+                database: 0,
+                name: String::new(),
+                num_params: 0,
+                kind: OsirisNodeKind::Rule {
+                    relation: relation.clone(),
+                    calls: vec![
+                        call.clone(),
+                        OsirisCall {
+                            name: "PROC_Done".to_string(),
+                            ..Default::default()
+                        },
+                        OsirisCall::default(),
+                    ],
+                    variables: vec![
+                        osiris_variable(0, osiris_string(FLAG, "Flag_A")),
+                        osiris_variable(
+                            1,
+                            OsirisValue {
+                                type_id: INTEGER,
+                                data: OsirisValueData::Integer(0),
+                            },
+                        ),
+                    ],
+                    line: 40,
+                    is_query: false,
+                },
+            },
+            OsirisNode {
+                id: 6,
+                database: 0,
+                name: String::new(),
+                num_params: 0,
+                kind: OsirisNodeKind::RelOp {
+                    relation,
+                    left_value_index: 0,
+                    right_value_index: -1,
+                    left_value: OsirisValue {
+                        type_id: OsirisBuiltinType::None as u32,
+                        data: OsirisValueData::None,
+                    },
+                    right_value: OsirisValue {
+                        type_id: INTEGER,
+                        data: OsirisValueData::Integer(10),
+                    },
+                    rel_op: 2,
+                },
+            },
+            OsirisNode {
+                id: 7,
+                database: 0,
+                name: String::new(),
+                num_params: 0,
+                kind: OsirisNodeKind::And(OsirisJoin {
+                    left_parent: 1,
+                    right_parent: 2,
+                    database1: 1,
+                    database2: 2,
+                    ..Default::default()
+                }),
+            },
+            OsirisNode {
+                id: 8,
+                database: 0,
+                name: "PROC_Count".to_string(),
+                num_params: 2,
+                kind: OsirisNodeKind::Proc {
+                    referenced_by: vec![],
+                },
+            },
+        ],
+        adapters: vec![OsirisAdapter {
+            index: 1,
+            constants: vec![(1, osiris_string(STRING, "constant"))],
+            logical_indices: vec![0, -1],
+            logical_to_physical: vec![(0, 0)],
+        }],
+        databases: vec![
+            OsirisDatabase {
+                index: 1,
+                parameters: vec![FLAG as u16],
+                facts: vec![OsirisFact {
+                    columns: vec![osiris_string(FLAG, "Flag_A")],
+                }],
+            },
+            OsirisDatabase {
+                index: 2,
+                parameters: vec![STRING as u16, INTEGER as u16],
+                facts: vec![OsirisFact {
+                    columns: vec![
+                        osiris_string(STRING, "Gold"),
+                        OsirisValue {
+                            type_id: INTEGER,
+                            data: OsirisValueData::Integer(10),
+                        },
+                    ],
+                }],
+            },
+            OsirisDatabase {
+                index: 3,
+                parameters: vec![STRING as u16],
+                facts: vec![OsirisFact {
+                    columns: vec![OsirisValue {
+                        type_id: STRING,
+                        data: OsirisValueData::String(None),
+                    }],
+                }],
+            },
+            OsirisDatabase {
+                index: 4,
+                parameters: vec![COLOR as u16],
+                facts: vec![OsirisFact {
+                    columns: vec![OsirisValue {
+                        type_id: COLOR,
+                        data: OsirisValueData::Enum(Box::new(OsirisValueData::Integer(2))),
+                    }],
+                }],
+            },
+        ],
+        goals: vec![OsirisGoal {
+            index: 1,
+            name: "Start".to_string(),
+            sub_goal_combination: 0,
+            parent_goals: vec![],
+            sub_goals: vec![2],
+            flags: 1,
+            init_calls: vec![call],
+            exit_calls: vec![OsirisCall {
+                name: "PROC_Done".to_string(),
+                parameters: Some(vec![]),
+                ..Default::default()
+            }],
+        }],
+        global_actions: vec![OsirisCall {
+            goal_id_or_debug_hook: -1,
+            ..Default::default()
+        }],
+        external_strings: vec![],
+    }
+}
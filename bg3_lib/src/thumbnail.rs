@@ -0,0 +1,109 @@
+use std::io::Cursor;
+
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{ImageFormat, RgbaImage};
+
+/// Screenshot stored in a save as a `.WebP` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thumbnail {
+    image: RgbaImage,
+}
+
+impl Thumbnail {
+    /// Decodes a thumbnail from WebP bytes.
+    pub fn from_webp(bytes: &[u8]) -> Result<Self, String> {
+        let image = image::load_from_memory_with_format(bytes, ImageFormat::WebP)
+            .map_err(|e| format!("failed decoding WebP thumbnail: {e}"))?;
+        Ok(Self {
+            image: image.into_rgba8(),
+        })
+    }
+
+    /// Builds a replacement thumbnail of the given size from an image in any supported format.
+    /// The image is scaled to cover the whole thumbnail and cropped to its aspect ratio.
+    pub fn from_image(bytes: &[u8], width: u32, height: u32) -> Result<Self, String> {
+        let image = image::load_from_memory(bytes)
+            .map_err(|e| format!("failed decoding replacement image: {e}"))?;
+        Ok(Self {
+            image: image
+                .resize_to_fill(width, height, FilterType::Lanczos3)
+                .into_rgba8(),
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    /// Pixels as RGBA bytes, row by row.
+    pub fn rgba(&self) -> &[u8] {
+        self.image.as_raw()
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = Cursor::new(vec![]);
+        self.image
+            .write_to(&mut bytes, ImageFormat::Png)
+            .map_err(|e| format!("failed encoding thumbnail as PNG: {e}"))?;
+        Ok(bytes.into_inner())
+    }
+
+    /// Encodes the thumbnail as lossless WebP, the only WebP encoding available.
+    pub fn to_webp(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![];
+        self.image
+            .write_with_encoder(WebPEncoder::new_lossless(&mut bytes))
+            .map_err(|e| format!("failed encoding thumbnail as WebP: {e}"))?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([(x * 8) as u8, (y * 8) as u8, 128, 255 - x as u8])
+        })
+    }
+
+    #[test]
+    fn webp_decodes_to_rgba_and_png() -> Result<(), String> {
+        let original = Thumbnail {
+            image: gradient(24, 12),
+        };
+        let thumbnail = Thumbnail::from_webp(&original.to_webp()?)?;
+        assert_eq!((thumbnail.width(), thumbnail.height()), (24, 12));
+        // Lossless WebP keeps every pixel
+        assert_eq!(thumbnail.rgba(), original.rgba());
+
+        let png = image::load_from_memory_with_format(&thumbnail.to_png()?, ImageFormat::Png)
+            .map_err(|e| e.to_string())?;
+        assert_eq!(png.into_rgba8().as_raw(), original.rgba());
+
+        assert!(Thumbnail::from_webp(&thumbnail.to_png()?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn replacements_take_the_requested_size() -> Result<(), String> {
+        let png = Thumbnail {
+            image: gradient(40, 10),
+        }
+        .to_png()?;
+
+        for (width, height) in [(16, 9), (9, 16), (80, 20)] {
+            let thumbnail = Thumbnail::from_image(&png, width, height)?;
+            assert_eq!((thumbnail.width(), thumbnail.height()), (width, height));
+            assert_eq!(thumbnail.rgba().len(), (width * height * 4) as usize);
+        }
+        assert!(Thumbnail::from_image(b"not an image", 16, 9).is_err());
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use bg3_lib::{
     abstract_file_info::PackagedFileInfo,
-    lsf_reader::{Node, NodeAttributeValue, Resource},
+    lsf_reader::{Node, Resource},
 };
 use egui::{CollapsingHeader, Image, ScrollArea};
 use egui_file_dialog::FileDialog;
@@ -92,7 +92,7 @@ impl FileView {
 
         CollapsingHeader::new(header).show(ui, |ui| {
             for (attr_name, attr_val) in &node.attributes {
-                if let NodeAttributeValue::Bytes(bytes) = &attr_val.value {
+                if let Some(bytes) = attr_val.value.as_bytes() {
                     ui.horizontal(|ui| {
                         if ui.button("extract").clicked() {
                             self.file_dialog.pick_directory();
@@ -109,7 +109,7 @@ impl FileView {
                         println!("saved to {}", path.to_string_lossy());
                     }
                 } else {
-                    ui.label(format!("{attr_name}: {attr_val:?}"));
+                    ui.label(format!("{attr_name} ({}): {attr_val}", attr_val.ty));
                 }
             }
 
@@ -1,7 +1,7 @@
 mod file_view;
 mod package_content_view;
 
-use eframe::{run_native, App, NativeOptions};
+use eframe::{App, NativeOptions, run_native};
 use egui::{CentralPanel, Context, ScrollArea, SidePanel, TopBottomPanel};
 use egui_file_dialog::FileDialog;
 use file_view::FileView;
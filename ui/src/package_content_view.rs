@@ -24,7 +24,17 @@ impl PackageContentView {
     pub fn init(picked_path: &Path) -> Result<PackageContentView, String> {
         let mut pr = PackageReader::new(picked_path)?;
 
-        let package = pr.read()?;
+        let package = match pr.read() {
+            Ok(package) => package,
+            Err(e) => {
+                println!("failed to read package, trying to salvage it: {e}");
+                let report = pr.salvage()?;
+                for lost in &report.lost {
+                    println!("lost {}: {}", lost.name.to_string_lossy(), lost.reason);
+                }
+                report.package
+            }
+        };
 
         let list = package
             .files
@@ -152,9 +162,14 @@ impl PackageContentView {
             }
             FileType::Lsf => {
                 let mut lsf = LSFReader::new();
-                let lsf_result = lsf.read(&mut self.reader, &package_file.pfi);
+                let lsf_result = lsf.read_lenient(&mut self.reader, &package_file.pfi);
                 match lsf_result {
-                    Ok(resource) => FileViewType::Lsf(package_file.pfi.clone(), resource),
+                    Ok((resource, diagnostics)) => {
+                        for diagnostic in diagnostics {
+                            println!("{package_file_idx}: {diagnostic}");
+                        }
+                        FileViewType::Lsf(package_file.pfi.clone(), resource)
+                    }
                     Err(e) => FileViewType::ReadError {
                         error: e.clone(),
                         filename: package_file_idx.clone(),